@@ -0,0 +1,162 @@
+//! Programs that walk headers with [`packets::cursor::Cursor`], one per kind of walk, kept so that
+//! a change that the verifier would reject is caught before it reaches a real program.
+//!
+//! `verify.sh` builds them with `cargo +nightly build --release`, which needs `bpf-linker` and the
+//! `rust-src` component, then loads every program with the loader in `verify/` so that the kernel
+//! verifier checks each of them. Run it as root after any change to the cursor or to these
+//! programs.
+#![no_std]
+#![no_main]
+
+use aya_ebpf::bindings::xdp_action::{XDP_ABORTED, XDP_DROP, XDP_PASS, XDP_TX};
+use aya_ebpf::bindings::{TC_ACT_OK, TC_ACT_SHOT};
+use aya_ebpf::macros::{classifier, xdp};
+use aya_ebpf::programs::{TcContext, XdpContext};
+use packets::aya::{tc_pull_data, xdp_adjust_head, Frame};
+use packets::cursor::Cursor;
+use packets::ip::Proto;
+
+/// Bounds every walk: an outer Ethernet, IPv6 with a few extension headers, UDP, VXLAN and an
+/// inner Ethernet, IPv4 and TCP header all fit.
+const MAX: usize = 511;
+
+/// Walks Ethernet, IPv4 with options and TCP with options, dropping TCP to port 23.
+#[xdp]
+pub fn ipv4_tcp(mut ctx: XdpContext) -> u32 {
+    let frame = Frame::from(&mut ctx);
+    let mut c = Cursor::<MAX>::new(frame.range());
+    match drop_telnet(&mut c) {
+        Ok(true) => XDP_DROP,
+        Ok(false) => XDP_PASS,
+        Err(()) => XDP_ABORTED,
+    }
+}
+
+#[inline(always)]
+fn drop_telnet(c: &mut Cursor<MAX>) -> Result<bool, ()> {
+    if c.next_t::<packets::eth::Header>()?.ether_type != packets::eth::Type::IPV4 {
+        return Ok(false);
+    }
+    if c.next_ipv4()?.proto != Proto::TCP {
+        return Ok(false);
+    }
+    Ok(c.next_tcp()?.dest.get() == 23)
+}
+
+/// Walks Ethernet, IPv6 and up to eight extension headers to the transport header, passing only
+/// UDP.
+#[xdp]
+pub fn ipv6_ext(mut ctx: XdpContext) -> u32 {
+    let frame = Frame::from(&mut ctx);
+    let mut c = Cursor::<MAX>::new(frame.range());
+    match ipv6_transport(&mut c) {
+        Ok(Proto::UDP) if c.next_t::<packets::udp::Header>().is_ok() => XDP_PASS,
+        Ok(_) => XDP_DROP,
+        Err(()) => XDP_ABORTED,
+    }
+}
+
+#[inline(always)]
+fn ipv6_transport(c: &mut Cursor<MAX>) -> Result<Proto, ()> {
+    if c.next_t::<packets::eth::Header>()?.ether_type != packets::eth::Type::IPV6 {
+        return Err(());
+    }
+    let mut proto = c.next_t::<packets::ipv6::Header>()?.proto;
+    for _ in 0..8 {
+        match proto {
+            Proto::HOPOPTS | Proto::ROUTING | Proto::DSTOPTS => {
+                proto = c.next_ipv6_ext()?.next_header
+            }
+            Proto::FRAGMENT => proto = c.next_t::<packets::ipv6::FragmentHeader>()?.next_header,
+            _ => break,
+        }
+    }
+    Ok(proto)
+}
+
+/// Strips the outer Ethernet, IPv4, UDP and VXLAN headers of a VXLAN packet, moving the head of
+/// the packet and reading the inner headers from the new frame.
+#[xdp]
+pub fn vxlan_decap(mut ctx: XdpContext) -> u32 {
+    let frame = Frame::from(&mut ctx);
+    let mut c = Cursor::<MAX>::new(frame.range());
+    let Ok(Some(inner)) = vxlan_inner(&mut c) else {
+        return XDP_PASS;
+    };
+    let Ok(frame) = xdp_adjust_head(&mut ctx, inner as i32) else {
+        return XDP_ABORTED;
+    };
+    let mut c = Cursor::<MAX>::new(frame.range());
+    match c.next_t::<packets::eth::Header>() {
+        Ok(_) => XDP_PASS,
+        Err(()) => XDP_ABORTED,
+    }
+}
+
+/// Returns the offset of the inner Ethernet header of a VXLAN packet.
+#[inline(always)]
+fn vxlan_inner(c: &mut Cursor<MAX>) -> Result<Option<usize>, ()> {
+    if c.next_t::<packets::eth::Header>()?.ether_type != packets::eth::Type::IPV4 {
+        return Ok(None);
+    }
+    if c.next_ipv4()?.proto != Proto::UDP {
+        return Ok(None);
+    }
+    if c.next_t::<packets::udp::Header>()?.destination.get() != packets::vxlan::PORT {
+        return Ok(None);
+    }
+    if c.next_t::<packets::vxlan::Header>()?.vni().is_none() {
+        return Ok(None);
+    }
+    Ok(Some(c.offset()))
+}
+
+/// Answers ARP requests for 10.0.0.1 in place.
+#[xdp]
+pub fn arp_reply(mut ctx: XdpContext) -> u32 {
+    const MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const IP: [u8; 4] = [10, 0, 0, 1];
+
+    let frame = Frame::from(&mut ctx);
+    let mut c = Cursor::<MAX>::new(frame.range());
+    let Ok(mut eth) = c.next_t::<packets::eth::Header>() else {
+        return XDP_ABORTED;
+    };
+    if eth.ether_type != packets::eth::Type::ARP {
+        return XDP_PASS;
+    }
+    let Ok(mut arp) = c.next_t::<packets::arp::Header>() else {
+        return XDP_PASS;
+    };
+    if !arp.var().is_ethernet_ipv4() || arp.tpa != IP {
+        return XDP_PASS;
+    }
+    match packets::arp::reply(&mut eth, &mut arp, MAC) {
+        Ok(()) => XDP_TX,
+        Err(()) => XDP_PASS,
+    }
+}
+
+/// Pulls the whole of a non-linear skb into the linear area, then walks Ethernet, IPv4 with
+/// options and TCP with options from the frame that results.
+#[classifier]
+pub fn tc_ipv4_tcp(mut ctx: TcContext) -> i32 {
+    let Ok(frame) = tc_pull_data(&mut ctx, 0) else {
+        return TC_ACT_OK;
+    };
+    let mut c = Cursor::<MAX>::new(frame.range());
+    match drop_telnet(&mut c) {
+        Ok(true) => TC_ACT_SHOT,
+        Ok(false) | Err(()) => TC_ACT_OK,
+    }
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { core::hint::unreachable_unchecked() }
+}
+
+#[link_section = "license"]
+#[no_mangle]
+static LICENSE: [u8; 13] = *b"Dual MIT/GPL\0";
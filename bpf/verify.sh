@@ -0,0 +1,9 @@
+#!/bin/sh
+# Builds the programs for bpfel-unknown-none and has the kernel verifier load each of them.
+# Needs a nightly toolchain with the rust-src component, bpf-linker, and root.
+set -eu
+cd "$(dirname "$0")"
+cargo +nightly build --release
+host=$(rustc -vV | sed -n 's/^host: //p')
+cargo run --release --manifest-path verify/Cargo.toml --target "$host" -- \
+    target/bpfel-unknown-none/release/packets-bpf
@@ -0,0 +1,35 @@
+//! Loads every program of the object file given as argument, so that the kernel verifier checks
+//! each of them, and exits with an error if any is rejected. Needs root or `CAP_BPF`.
+
+use aya::programs::Program;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: packets-bpf-verify <object>");
+    let mut ebpf = aya::Ebpf::load_file(&path).expect("failed to read the object file");
+    let mut rejected = 0;
+    for (name, program) in ebpf.programs_mut() {
+        let result = match program {
+            Program::Xdp(program) => program.load(),
+            Program::SchedClassifier(program) => program.load(),
+            _ => panic!("{name}: unexpected program type"),
+        };
+        match result {
+            Ok(()) => println!("{name}: ok"),
+            Err(e) => {
+                rejected += 1;
+                println!("{name}: {e}");
+                let mut source = std::error::Error::source(&e);
+                while let Some(e) = source {
+                    println!("  {e}");
+                    source = e.source();
+                }
+            }
+        }
+    }
+    if rejected > 0 {
+        eprintln!("{rejected} program(s) rejected");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,53 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use packets_fuzz::{walk, Frame};
+
+// The input starts at the ARP header, as seen by a program that has already matched the
+// EtherType.
+fuzz_target!(|data: &[u8]| {
+    let mut frame = Frame::new(data);
+    let range = frame.range();
+    let Some(mut var) = packets::meta::from_range::<packets::arp::VarHeader>(range.clone()) else {
+        return;
+    };
+    let fixed = *var;
+    match var.addresses(range.clone()) {
+        Ok(addrs) => {
+            assert!(fixed.packet_len() <= data.len());
+            assert_eq!(fixed.h_len as usize, addrs.sha.len());
+            assert_eq!(fixed.p_len as usize, addrs.tpa.len());
+        }
+        Err(()) => assert!(fixed.packet_len() > data.len()),
+    }
+    let Ok(mut arp) = var.ethernet_ipv4(range.clone()) else {
+        assert!(!var.is_ethernet_ipv4() || data.len() < packets::arp::Header::LEN);
+        return;
+    };
+    assert!(arp.var().is_ethernet_ipv4());
+
+    // Reply, then build the frame the reply would go out in and parse it back.
+    let request = *arp;
+    let mac = [2, 0, 0, 0, 0, 1];
+    let mut eth = packets::eth::Header {
+        dst_addr: [0; 6],
+        src_addr: request.sha,
+        ether_type: packets::eth::Type::ARP,
+    };
+    if packets::arp::reply(&mut eth, &mut arp, mac).is_err() {
+        assert_ne!(packets::arp::Operation::REQUEST, request.oper);
+        assert_eq!({ request.sha }, arp.sha);
+        return;
+    }
+    assert_eq!(request.spa(), arp.tpa());
+    assert_eq!(request.tpa(), arp.spa());
+    assert_eq!({ request.sha }, eth.dst_addr);
+
+    let mut out = [0u8; 42];
+    let len = packets::arp::write_frame(&mut out, &arp).unwrap();
+    let mut frame = Frame::new(&out[..len]);
+    assert_eq!(2, walk(frame.range()));
+    let back: packets::arp::Header = packets::read_header(&out, packets::eth::Header::LEN).unwrap();
+    assert_eq!(arp.spa(), back.spa());
+    assert_eq!({ arp.tha }, back.tha);
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use core::fmt::Write;
+
+use libfuzzer_sys::fuzz_target;
+use packets_fuzz::{walk, Frame};
+
+/// Discards formatted output.
+struct Sink;
+
+impl Write for Sink {
+    fn write_str(&mut self, _: &str) -> core::fmt::Result {
+        Ok(())
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut frame = Frame::new(data);
+    walk(frame.range());
+
+    // Each layer covers its header and everything it encloses, so the ranges nest.
+    let mut outer = 0..data.len();
+    for (range, _) in packets::dissect::layers(data) {
+        assert!(outer.start <= range.start && range.end <= outer.end);
+        outer = range;
+    }
+    write!(Sink, "{}", packets::dissect::summary(data)).unwrap();
+    write!(Sink, "{}", packets::dissect::tree(data)).unwrap();
+});
@@ -0,0 +1,34 @@
+#![no_main]
+
+use core::ffi::c_void;
+
+use libfuzzer_sys::fuzz_target;
+use packets::AutoNextHeader;
+use packets_fuzz::{walk, Frame};
+
+// The input starts at the VXLAN header, as seen by a program that has already matched the UDP
+// port.
+fuzz_target!(|data: &[u8]| {
+    let mut frame = Frame::new(data);
+    let range = frame.range();
+    let Some(mut vxlan) = packets::meta::from_range::<packets::vxlan::Header>(range.clone()) else {
+        return;
+    };
+    vxlan.source_port(range.clone());
+    assert_eq!(
+        data.len() >= packets::vxlan::Header::LEN + packets::eth::Header::LEN,
+        vxlan.next(range.clone()).is_ok()
+    );
+    if let Ok(eth) = vxlan.ethernet(range.clone()) {
+        let offset = eth.offset(range.clone()) as usize;
+        let start = unsafe { (range.start as *mut u8).add(offset) } as *mut c_void;
+        walk(start..range.end);
+    }
+
+    if let Some(vni) = vxlan.vni() {
+        vxlan.clear_vni();
+        assert_eq!(None, vxlan.vni());
+        vxlan.set_vni(vni);
+        assert_eq!(Some(vni), vxlan.vni());
+    }
+});
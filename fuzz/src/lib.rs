@@ -0,0 +1,113 @@
+//! Helpers shared by the fuzz targets. Run a target with `cargo +nightly fuzz run eth` from this
+//! directory; the targets are `eth`, `vxlan` and `arp`.
+
+use core::ffi::c_void;
+use core::ops::Range;
+
+use packets::{AutoNextHeader, HeaderPtr};
+
+/// Bounds the walk through nested headers, which can't loop but can nest VXLAN deeply.
+const MAX_DEPTH: usize = 32;
+
+/// A copy of the fuzz input with the alignment the packed headers need, as an XDP or TC frame
+/// would have.
+pub struct Frame {
+    buf: Vec<u64>,
+    len: usize,
+}
+
+impl Frame {
+    pub fn new(data: &[u8]) -> Self {
+        let mut buf = vec![0u64; data.len().div_ceil(8)];
+        let bytes =
+            unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, data.len()) };
+        bytes.copy_from_slice(data);
+        Self {
+            buf,
+            len: data.len(),
+        }
+    }
+
+    /// Returns the frame as the pointer range the header accessors take. Nothing past its end is
+    /// allocated, so an out-of-bounds access is caught by the sanitizer.
+    pub fn range(&mut self) -> Range<*mut c_void> {
+        let start = self.buf.as_mut_ptr() as *mut u8;
+        let end = unsafe { start.add(self.len) };
+        start as *mut c_void..end as *mut c_void
+    }
+}
+
+/// Follows [`AutoNextHeader`] from an Ethernet header at the start of `frame` as far as it goes,
+/// reading every header on the way. Returns the number of headers seen.
+pub fn walk(frame: Range<*mut c_void>) -> usize {
+    let Ok(eth) = packets::eth::Header::from_frame(frame.clone()) else {
+        return 0;
+    };
+    let mut next = Ok(HeaderPtr::Eth(eth));
+    let mut depth = 0;
+    while depth < MAX_DEPTH {
+        depth += 1;
+        next = match next {
+            Ok(HeaderPtr::Eth(mut eth)) => {
+                core::hint::black_box(*eth);
+                eth.next(frame.clone())
+            }
+            Ok(HeaderPtr::Arp(arp)) => {
+                core::hint::black_box((arp.var().is_ethernet_ipv4(), arp.spa(), arp.tpa()));
+                break;
+            }
+            Ok(HeaderPtr::ArpVar(mut arp)) => {
+                if let Ok(addrs) = arp.addresses(frame.clone()) {
+                    core::hint::black_box(addrs);
+                }
+                break;
+            }
+            Ok(HeaderPtr::Ipv4(mut ip)) => {
+                core::hint::black_box((
+                    ip.header_len(),
+                    ip.fragment_offset(),
+                    ip.compute_checksum(),
+                    ip.source(),
+                ));
+                ip.next(frame.clone())
+            }
+            Ok(HeaderPtr::Ipv6(mut ip)) => {
+                core::hint::black_box((ip.priority(), ip.flow(), ip.destination()));
+                ip.next(frame.clone())
+            }
+            Ok(HeaderPtr::Ipv6Fragment(frag)) => {
+                // `Ptr::offset` shadows the header's own.
+                let frag = *frag;
+                core::hint::black_box((frag.offset(), frag.more_fragments()));
+                break;
+            }
+            Ok(HeaderPtr::Tcp(tcp)) => {
+                core::hint::black_box((tcp.header_len(), tcp.flags()));
+                break;
+            }
+            Ok(HeaderPtr::Udp(mut udp)) => udp.next(frame.clone()),
+            Ok(HeaderPtr::Vxlan(mut vxlan)) => {
+                core::hint::black_box((vxlan.vni(), vxlan.source_port(frame.clone())));
+                vxlan.next(frame.clone())
+            }
+            Ok(HeaderPtr::Gtp(mut gtp)) => {
+                core::hint::black_box((gtp.teid(), gtp.extensions(frame.clone()).count()));
+                gtp.next(frame.clone())
+            }
+            Ok(HeaderPtr::Sctp(sctp)) => {
+                core::hint::black_box((sctp.vtag, sctp.checksum()));
+                break;
+            }
+            Ok(HeaderPtr::Icmp(icmp)) => {
+                core::hint::black_box((icmp.id(), icmp.mtu()));
+                break;
+            }
+            Ok(HeaderPtr::Icmpv6(icmp)) => {
+                core::hint::black_box((icmp.id(), icmp.mtu()));
+                break;
+            }
+            Ok(HeaderPtr::Unhandled()) | Err(()) => break,
+        };
+    }
+    depth
+}
@@ -1,6 +1,12 @@
 use core::mem;
 
+#[repr(transparent)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct HardwareType {
     val: crate::be16,
 }
@@ -12,10 +18,21 @@ impl HardwareType {
         }
     }
 
+    #[inline(always)]
+    pub const fn get(&self) -> u16 {
+        self.val.get()
+    }
+
     pub const ETHERNET: HardwareType = HardwareType::new(1);
 }
 
+#[repr(transparent)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Operation {
     val: crate::be16,
 }
@@ -27,15 +44,134 @@ impl Operation {
         }
     }
 
+    #[inline(always)]
+    pub const fn get(&self) -> u16 {
+        self.val.get()
+    }
+
     pub const REQUEST: Operation = Operation::new(1);
     pub const REPLY: Operation = Operation::new(2);
 }
 
 pub type ProtocolType = super::eth::Type;
 
-// NOTE This struct is only useful if hardware length is 6 and protocol length is 4.
+/// The fixed part of an ARP packet, common to every hardware and protocol type. The four addresses
+/// follow it and their sizes are given by `h_len` and `p_len`.
 #[repr(C, packed(2))]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct VarHeader {
+    /// Hardware Type
+    pub h_type: HardwareType,
+    /// Protocol Type
+    pub p_type: ProtocolType,
+    // Hardware Address Length
+    pub h_len: u8,
+    // Protocol Address Length
+    pub p_len: u8,
+    /// Operation
+    pub oper: Operation,
+}
+
+const _: () = assert!(mem::size_of::<VarHeader>() == 8 && mem::align_of::<VarHeader>() == 2);
+
+impl super::NextHeader for VarHeader {}
+impl super::Header for VarHeader {
+    const PROTOCOL: super::Protocol = super::Protocol::Arp;
+
+    /// Includes the four addresses, so the next header is whatever follows the packet.
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.packet_len()
+    }
+}
+
+impl VarHeader {
+    pub const LEN: usize = mem::size_of::<VarHeader>();
+
+    /// Returns true if this packet maps Ethernet addresses to IPv4 addresses, which means that
+    /// [`Header`] can be used to access it.
+    #[inline(always)]
+    pub fn is_ethernet_ipv4(&self) -> bool {
+        self.h_type == HardwareType::ETHERNET
+            && self.p_type == ProtocolType::IPV4
+            && self.h_len == 6
+            && self.p_len == 4
+    }
+
+    /// Returns the total length of the packet, including the four addresses.
+    #[inline(always)]
+    pub fn packet_len(&self) -> usize {
+        Self::LEN + 2 * (self.h_len as usize + self.p_len as usize)
+    }
+
+    /// Returns the fixed Ethernet/IPv4 view of this packet after checking the address types and
+    /// lengths.
+    #[inline(always)]
+    pub fn ethernet_ipv4(
+        &mut self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<super::Ptr<Header>, ()> {
+        if !self.is_ethernet_ipv4() {
+            return Err(());
+        }
+        let pointer = self as *mut VarHeader as *mut Header;
+        unsafe {
+            if pointer.offset(1) > (frame.end as *mut Header) {
+                return Err(());
+            }
+        }
+        Ok(super::Ptr::new(pointer))
+    }
+
+    /// Returns the four addresses (sha, spa, tha, tpa) following the fixed part.
+    #[inline(always)]
+    pub fn addresses(
+        &mut self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<Addresses<'_>, ()> {
+        let h_len = self.h_len as usize;
+        let p_len = self.p_len as usize;
+        let start = unsafe { (self as *mut VarHeader as *mut u8).add(Self::LEN) };
+        let len = 2 * (h_len + p_len);
+        if start as usize + len > frame.end as usize {
+            return Err(());
+        }
+        let bytes = unsafe { core::slice::from_raw_parts_mut(start, len) };
+        let (sha, rest) = bytes.split_at_mut(h_len);
+        let (spa, rest) = rest.split_at_mut(p_len);
+        let (tha, tpa) = rest.split_at_mut(h_len);
+        Ok(Addresses { sha, spa, tha, tpa })
+    }
+}
+
+/// The variable-length addresses of an ARP packet.
+#[derive(Debug)]
+pub struct Addresses<'a> {
+    /// Sender hardware address.
+    pub sha: &'a mut [u8],
+    /// Sender protocol address.
+    pub spa: &'a mut [u8],
+    /// Target hardware address.
+    pub tha: &'a mut [u8],
+    /// Target protocol address.
+    pub tpa: &'a mut [u8],
+}
+
+/// ARP packet for Ethernet hardware addresses and IPv4 protocol addresses. Use
+/// [`VarHeader::ethernet_ipv4`] or [`VarHeader::is_ethernet_ipv4`] on [`Header::var`] to check
+/// that a received packet has this layout.
+#[repr(C, packed(2))]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Header {
     /// Hardware Type
     pub h_type: HardwareType,
@@ -57,11 +193,89 @@ pub struct Header {
     pub tpa: [u8; 4],
 }
 
+const _: () = assert!(mem::size_of::<Header>() == 28 && mem::align_of::<Header>() == 2);
+
 impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Arp;
+
+    /// Checks that the type and length fields match the layout of this struct.
+    #[inline(always)]
+    fn validate(&self, remaining: usize) -> Result<(), ()> {
+        match self.var().is_ethernet_ipv4() && Self::LEN <= remaining {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
 
 impl Header {
     pub const LEN: usize = mem::size_of::<Header>();
 
+    /// Returns an Ethernet/IPv4 ARP packet with the given operation and addresses.
+    #[inline(always)]
+    pub fn new(
+        oper: Operation,
+        sha: [u8; 6],
+        spa: core::net::Ipv4Addr,
+        tha: [u8; 6],
+        tpa: core::net::Ipv4Addr,
+    ) -> Self {
+        Self {
+            h_type: HardwareType::ETHERNET,
+            p_type: ProtocolType::IPV4,
+            h_len: 6,
+            p_len: 4,
+            oper,
+            sha,
+            spa: spa.octets(),
+            tha,
+            tpa: tpa.octets(),
+        }
+    }
+
+    /// Returns a gratuitous ARP (an ARP announcement, RFC 5227) claiming `ip` for `mac`.
+    #[inline(always)]
+    pub fn gratuitous(mac: [u8; 6], ip: core::net::Ipv4Addr) -> Self {
+        Self::new(Operation::REQUEST, mac, ip, [0; 6], ip)
+    }
+
+    /// Returns an ARP probe (RFC 5227) checking whether `ip` is already in use.
+    #[inline(always)]
+    pub fn probe(mac: [u8; 6], ip: core::net::Ipv4Addr) -> Self {
+        Self::new(
+            Operation::REQUEST,
+            mac,
+            core::net::Ipv4Addr::UNSPECIFIED,
+            [0; 6],
+            ip,
+        )
+    }
+
+    /// Returns the type, length and operation fields, which this layout shares with
+    /// [`VarHeader`].
+    #[inline(always)]
+    pub fn var(&self) -> &VarHeader {
+        unsafe { &*(self as *const Header as *const VarHeader) }
+    }
+
+    /// Turns a request into a reply in place: the sender becomes the target and `mac` is filled
+    /// in as the sender hardware address for the requested protocol address. Returns `Err` and
+    /// leaves the packet as it is unless it is a request.
+    #[inline(always)]
+    pub fn make_reply(&mut self, mac: [u8; 6]) -> Result<(), ()> {
+        if self.oper != Operation::REQUEST {
+            return Err(());
+        }
+        let spa = self.spa;
+        self.oper = Operation::REPLY;
+        self.tha = self.sha;
+        self.spa = self.tpa;
+        self.tpa = spa;
+        self.sha = mac;
+        Ok(())
+    }
+
     /// Returns the source address field.
     #[inline(always)]
     pub fn spa(&self) -> core::net::Ipv4Addr {
@@ -87,17 +301,233 @@ impl Header {
     }
 }
 
+/// Turns an ARP request frame into a reply in place, addressing the Ethernet header back to the
+/// requester. Returns `Err` and leaves both headers as they are unless `arp` is a request.
+#[inline(always)]
+pub fn reply(eth: &mut super::eth::Header, arp: &mut Header, mac: [u8; 6]) -> Result<(), ()> {
+    let requester = arp.sha;
+    arp.make_reply(mac)?;
+    eth.dst_addr = requester;
+    eth.src_addr = mac;
+    Ok(())
+}
+
+/// Writes a broadcast Ethernet frame carrying `arp` to the start of `buf`, returning the number of
+/// bytes written.
+#[inline(always)]
+pub fn write_frame(buf: &mut [u8], arp: &Header) -> Result<usize, ()> {
+    let eth = super::eth::Header {
+        dst_addr: super::eth::BROADCAST,
+        src_addr: arp.sha,
+        ether_type: super::eth::Type::ARP,
+    };
+    super::write_header(buf, super::eth::Header::LEN, arp)?;
+    super::write_header(buf, 0, &eth)?;
+    Ok(super::eth::Header::LEN + Header::LEN)
+}
+
+impl core::fmt::Display for Operation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Operation::REQUEST => f.write_str("Request"),
+            Operation::REPLY => f.write_str("Reply"),
+            _ => write!(f, "Operation {}", self.val),
+        }
+    }
+}
+
+impl core::fmt::Display for VarHeader {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (h_type, p_type, oper) = (self.h_type, self.p_type, self.oper);
+        write!(
+            f,
+            "ARP, {}, hardware type {} ({} bytes), protocol 0x{:04x} ({} bytes)",
+            oper,
+            h_type.val,
+            self.h_len,
+            p_type.get(),
+            self.p_len
+        )
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let spa = core::net::Ipv4Addr::from(self.spa);
+        let tpa = core::net::Ipv4Addr::from(self.tpa);
+        match self.oper {
+            Operation::REQUEST => write!(f, "ARP, Request who-has {tpa} tell {spa}"),
+            Operation::REPLY => {
+                let sha = super::eth::MacAddr(self.sha);
+                write!(f, "ARP, Reply {spa} is-at {sha}")
+            }
+            oper => write!(f, "ARP, {oper} {spa} > {tpa}"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::net::Ipv4Addr;
+    use proptest::prelude::*;
+
+    const MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const PEER: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+    // "who-has 10.0.0.1 tell 10.0.0.2" request.
+    const REQUEST: [u8; 42] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0, 0, 0, 0, 2, 0x08, 0x06, // eth
+        0, 1, 0x08, 0, 6, 4, 0, 1, // fixed
+        0x02, 0, 0, 0, 0, 2, 10, 0, 0, 2, // sender
+        0, 0, 0, 0, 0, 0, 10, 0, 0, 1, // target
+    ];
+
+    #[test]
+    fn validate() {
+        let mut frame = crate::Aligned(REQUEST);
+        let r = frame.range(REQUEST.len());
+        let mut eth = crate::eth::Header::from_frame(r.clone()).unwrap();
+        let mut var = crate::NextHeader::next_t::<VarHeader>(&mut *eth, r.clone()).unwrap();
+        assert!(var.is_ethernet_ipv4());
+        assert_eq!(28, var.packet_len());
+        let arp = var.ethernet_ipv4(r.clone()).unwrap();
+        assert!(arp.var().is_ethernet_ipv4());
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 1), arp.tpa());
+
+        assert_eq!(28, crate::Header::len(&*var));
+        assert!(crate::Header::validate(&*var, 28).is_ok());
+        assert!(crate::Header::validate(&*arp, 28).is_ok());
+
+        var.h_len = 8;
+        assert!(var.ethernet_ipv4(r.clone()).is_err());
+        assert!(crate::Header::validate(&*var, 28).is_err());
+        assert!(crate::Header::validate(&*arp, 28).is_err());
+        match crate::AutoNextHeader::next(&mut *eth, r).unwrap() {
+            crate::HeaderPtr::ArpVar(_) => {}
+            _ => panic!("expected variable-length ARP"),
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let mut frame = crate::Aligned(REQUEST);
+        let r = frame.range(40);
+        let mut eth = crate::eth::Header::from_frame(r.clone()).unwrap();
+        let mut var = crate::NextHeader::next_t::<VarHeader>(&mut *eth, r.clone()).unwrap();
+        assert!(var.ethernet_ipv4(r.clone()).is_err());
+        assert!(var.addresses(r.clone()).is_err());
+        assert!(crate::AutoNextHeader::next(&mut *eth, r).is_err());
+    }
+
+    #[test]
+    fn addresses() {
+        // Hardware length 8 and IPv6-sized protocol addresses.
+        let mut pkt = crate::Aligned([0u8; 8 + 2 * (8 + 16)]);
+        pkt.0[4] = 8;
+        pkt.0[5] = 16;
+        pkt.0[8..16].copy_from_slice(&[1; 8]);
+        pkt.0[40..56].copy_from_slice(&[4; 16]);
+        let r = pkt.range(56);
+        let mut var = crate::meta::from_range::<VarHeader>(r.clone()).unwrap();
+        assert_eq!(56, var.packet_len());
+        let a = var.addresses(r).unwrap();
+        assert_eq!(&[1; 8], a.sha);
+        assert_eq!(&[0; 16], a.spa);
+        assert_eq!(&[0; 8], a.tha);
+        assert_eq!(&[4; 16], a.tpa);
+    }
+
+    #[test]
+    fn make_reply() {
+        let mut frame = crate::Aligned(REQUEST);
+        let r = frame.range(REQUEST.len());
+        let mut eth = crate::eth::Header::from_frame(r.clone()).unwrap();
+        let mut arp = crate::NextHeader::next_t::<Header>(&mut *eth, r).unwrap();
+        reply(&mut eth, &mut arp, MAC).unwrap();
+
+        assert_eq!(Operation::REPLY, arp.oper);
+        assert_eq!(MAC, arp.sha);
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 1), arp.spa());
+        assert_eq!(PEER, arp.tha);
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 2), arp.tpa());
+        assert_eq!(PEER, eth.dst_addr);
+        assert_eq!(MAC, eth.src_addr);
+
+        // A reply isn't answered, and neither header changes.
+        let (before_eth, before_arp) = (*eth, *arp);
+        assert!(reply(&mut eth, &mut arp, PEER).is_err());
+        assert_eq!(before_eth.dst_addr, eth.dst_addr);
+        assert_eq!(before_arp.sha, arp.sha);
+        assert_eq!(Operation::REPLY, arp.oper);
+    }
 
     #[test]
-    fn size() {
-        assert_eq!(28, Header::LEN);
+    fn gratuitous() {
+        let mut buf = [0u8; 64];
+        let ip = Ipv4Addr::new(192, 168, 1, 10);
+        assert_eq!(Ok(42), write_frame(&mut buf, &Header::gratuitous(MAC, ip)));
+        assert_eq!([0xff; 6], buf[0..6]);
+        assert_eq!(MAC, buf[6..12]);
+        assert_eq!([0x08, 0x06], buf[12..14]);
+        assert_eq!([0, 1, 0x08, 0, 6, 4, 0, 1], buf[14..22]);
+        assert_eq!([192, 168, 1, 10], buf[28..32]);
+        assert_eq!([192, 168, 1, 10], buf[38..42]);
+        assert!(write_frame(&mut buf[..41], &Header::gratuitous(MAC, ip)).is_err());
     }
 
     #[test]
-    fn alignment() {
-        assert_eq!(2, mem::align_of::<Header>());
+    fn probe() {
+        let ip = Ipv4Addr::new(192, 168, 1, 10);
+        let p = Header::probe(MAC, ip);
+        assert_eq!(Ipv4Addr::UNSPECIFIED, p.spa());
+        assert_eq!(ip, p.tpa());
+        assert_eq!([0; 6], p.tha);
+        assert_eq!(Operation::REQUEST, p.oper);
+    }
+
+    proptest! {
+        #[test]
+        fn address_accessors(spa: [u8; 4], tpa: [u8; 4]) {
+            let mut arp = Header::probe(MAC, Ipv4Addr::UNSPECIFIED);
+            arp.set_spa(spa.into());
+            arp.set_tpa(tpa.into());
+            prop_assert_eq!(Ipv4Addr::from(spa), arp.spa());
+            prop_assert_eq!(Ipv4Addr::from(tpa), arp.tpa());
+        }
+
+        #[test]
+        fn build_parse_reply(
+            oper: u16,
+            sha: [u8; 6],
+            spa: [u8; 4],
+            tha: [u8; 6],
+            tpa: [u8; 4],
+            short in 0usize..42,
+        ) {
+            let arp = Header::new(Operation::new(oper), sha, spa.into(), tha, tpa.into());
+            let mut frame = crate::Aligned([0u8; 42]);
+            prop_assert!(write_frame(&mut frame.0[..short], &arp).is_err());
+            prop_assert_eq!(Ok(42), write_frame(&mut frame.0, &arp));
+
+            let r = frame.range(42);
+            let mut eth = crate::eth::Header::from_frame(r.clone()).unwrap();
+            let Ok(crate::HeaderPtr::Arp(mut parsed)) = crate::AutoNextHeader::next(&mut *eth, r)
+            else {
+                return Err(TestCaseError::fail("expected Ethernet/IPv4 ARP"));
+            };
+            prop_assert!(parsed.var().is_ethernet_ipv4());
+            prop_assert_eq!(Operation::new(oper), parsed.oper);
+            prop_assert_eq!((sha, spa, tha, tpa), (parsed.sha, parsed.spa, parsed.tha, parsed.tpa));
+
+            if Operation::new(oper) != Operation::REQUEST {
+                prop_assert!(reply(&mut eth, &mut parsed, MAC).is_err());
+                return Ok(());
+            }
+            prop_assert!(reply(&mut eth, &mut parsed, MAC).is_ok());
+            prop_assert_eq!(Operation::REPLY, parsed.oper);
+            prop_assert_eq!((MAC, tpa, sha, spa), (parsed.sha, parsed.spa, parsed.tha, parsed.tpa));
+            prop_assert_eq!((sha, MAC), (eth.dst_addr, eth.src_addr));
+        }
     }
 }
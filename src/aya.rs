@@ -0,0 +1,115 @@
+//! Adapters for [aya-ebpf](https://docs.rs/aya-ebpf) program contexts, enabled by the `aya`
+//! feature.
+//!
+//! A [`Frame`] is the packet data of an XDP or TC context as the `Range<*mut c_void>` the rest of
+//! the crate takes. It mutably borrows the context, and the helpers that move the start of the
+//! packet take the context mutably too and return a new frame. The verifier invalidates every
+//! packet pointer after such a call, so the borrow checker rejects a header reference from
+//! [`Frame::header`] that is still used after it.
+//!
+//! [`Ptr`](crate::Ptr) values carry no lifetime, so a pointer made from [`Frame::range`] must not
+//! be kept across a head adjustment either.
+
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use aya_ebpf::cty::c_long;
+use aya_ebpf::programs::{TcContext, XdpContext};
+
+/// The packet data of an XDP or TC context.
+pub struct Frame<'a> {
+    range: Range<*mut c_void>,
+    _ctx: PhantomData<&'a mut ()>,
+}
+
+impl Frame<'_> {
+    #[inline(always)]
+    fn new(data: usize, data_end: usize) -> Self {
+        Self {
+            range: data as *mut c_void..data_end as *mut c_void,
+            _ctx: PhantomData,
+        }
+    }
+
+    /// Returns the packet data as the range taken by the header accessors, such as
+    /// [`eth::Header::from_frame`](crate::eth::Header::from_frame).
+    #[inline(always)]
+    pub fn range(&self) -> Range<*mut c_void> {
+        self.range.clone()
+    }
+
+    /// Returns the length of the packet data in bytes.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.range.end as usize - self.range.start as usize
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the header of type `T` at `offset` bytes into the packet, or `Err` if it doesn't
+    /// fit. The bounds check compares against the end of the packet, as the verifier requires.
+    #[inline(always)]
+    pub fn header<T: crate::Plain>(&mut self, offset: usize) -> Result<&mut T, ()> {
+        let start = (self.range.start as usize).checked_add(offset).ok_or(())?;
+        let end = start.checked_add(core::mem::size_of::<T>()).ok_or(())?;
+        if end > self.range.end as usize {
+            return Err(());
+        }
+        Ok(unsafe { &mut *(start as *mut T) })
+    }
+}
+
+impl<'a> From<&'a mut XdpContext> for Frame<'a> {
+    #[inline(always)]
+    fn from(ctx: &'a mut XdpContext) -> Self {
+        Frame::new(ctx.data(), ctx.data_end())
+    }
+}
+
+impl<'a> From<&'a mut TcContext> for Frame<'a> {
+    #[inline(always)]
+    fn from(ctx: &'a mut TcContext) -> Self {
+        Frame::new(ctx.data(), ctx.data_end())
+    }
+}
+
+/// Moves the start of the packet by `delta` bytes with `bpf_xdp_adjust_head`, a negative `delta`
+/// making room for a new header, and returns the frame that results. On failure the packet is
+/// unchanged and the helper's error code is returned.
+#[inline(always)]
+pub fn xdp_adjust_head(ctx: &mut XdpContext, delta: i32) -> Result<Frame<'_>, c_long> {
+    let ret = unsafe { aya_ebpf::helpers::bpf_xdp_adjust_head(ctx.ctx, delta) };
+    match ret {
+        0 => Ok(Frame::from(ctx)),
+        err => Err(err),
+    }
+}
+
+/// Makes the first `len` bytes of a non-linear skb directly accessible with `bpf_skb_pull_data`,
+/// or the whole packet if `len` is 0, and returns the frame that results. On failure the helper's
+/// error code is returned.
+#[inline(always)]
+pub fn tc_pull_data(ctx: &mut TcContext, len: u32) -> Result<Frame<'_>, c_long> {
+    ctx.pull_data(len)?;
+    Ok(Frame::from(ctx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_bounds() {
+        let mut buf = crate::Aligned([0u8; 20]);
+        let r = buf.range(20);
+        let mut frame = Frame::new(r.start as usize, r.end as usize);
+        assert!(frame.header::<crate::eth::Header>(6).is_ok());
+        assert!(frame.header::<crate::eth::Header>(7).is_err());
+        // An offset that wraps the address space.
+        assert!(frame.header::<crate::eth::Header>(usize::MAX - 4).is_err());
+    }
+}
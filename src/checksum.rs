@@ -0,0 +1,189 @@
+//! Internet checksum (RFC 1071) and incremental update (RFC 1624) helpers, and the CRC32c used
+//! by SCTP (RFC 9260, appendix A).
+//!
+//! Sums are carried as `u32` accumulators in host order and folded to 16 bits at the end, so the
+//! pieces of a checksum (pseudo header, header, payload) can be summed separately and combined.
+
+use crate::{be16, be32};
+
+/// Folds a 32 bit accumulator into 16 bits of one's complement sum.
+#[inline(always)]
+pub const fn fold(sum: u32) -> u16 {
+    let sum = (sum & 0xffff) + (sum >> 16);
+    let sum = (sum & 0xffff) + (sum >> 16);
+    sum as u16
+}
+
+/// Adds `data`, taken as a sequence of big-endian 16 bit words, to `initial`. An odd trailing
+/// byte is padded with zero as required by RFC 1071.
+#[inline(always)]
+pub fn sum(data: &[u8], initial: u32) -> u32 {
+    let mut acc = initial as u64;
+    let mut chunks = data.chunks_exact(2);
+    for c in &mut chunks {
+        acc += u16::from_be_bytes([c[0], c[1]]) as u64;
+    }
+    if let [last] = chunks.remainder() {
+        acc += (*last as u64) << 8;
+    }
+    let acc = (acc & 0xffff_ffff) + (acc >> 32);
+    ((acc & 0xffff_ffff) + (acc >> 32)) as u32
+}
+
+/// Returns the checksum field value for an accumulated sum.
+#[inline(always)]
+pub const fn finish(sum: u32) -> be16 {
+    be16::new(!fold(sum))
+}
+
+/// Computes the Internet checksum of `data`.
+#[inline(always)]
+pub fn checksum(data: &[u8]) -> be16 {
+    finish(sum(data, 0))
+}
+
+/// Returns true if `data`, including its checksum field, sums to zero.
+#[inline(always)]
+pub fn verify(data: &[u8]) -> bool {
+    fold(sum(data, 0)) == 0xffff
+}
+
+/// Returns the updated checksum after a 16 bit word covered by `check` changes from `old` to
+/// `new` (RFC 1624, equation 3).
+#[inline(always)]
+pub const fn update16(check: be16, old: be16, new: be16) -> be16 {
+    let sum = (!check.get()) as u32 + (!old.get()) as u32 + new.get() as u32;
+    be16::new(!fold(sum))
+}
+
+/// Same as [`update16`] for a 32 bit field such as an IPv4 address or TCP sequence number.
+#[inline(always)]
+pub const fn update32(check: be16, old: be32, new: be32) -> be16 {
+    let (old, new) = (old.get(), new.get());
+    let sum = (!check.get()) as u32
+        + (!(old >> 16) & 0xffff)
+        + (!old & 0xffff)
+        + (new >> 16)
+        + (new & 0xffff);
+    be16::new(!fold(sum))
+}
+
+/// Same as [`update16`] for an arbitrary run of bytes at an even offset, such as an IPv6 address.
+/// `old` and `new` must have the same length.
+#[inline(always)]
+pub fn update(check: be16, old: &[u8], new: &[u8]) -> be16 {
+    let sum = (!check.get()) as u32 + !fold(sum(old, 0)) as u32;
+    finish(self::sum(new, sum))
+}
+
+/// Returns the sum of the IPv4 pseudo header used by TCP, UDP and other transport checksums.
+#[inline(always)]
+pub fn pseudo_ipv4(src: [u8; 4], dst: [u8; 4], proto: u8, len: u16) -> u32 {
+    let acc = sum(&src, 0);
+    let acc = sum(&dst, acc);
+    acc + proto as u32 + len as u32
+}
+
+/// Returns the sum of the IPv6 pseudo header used by TCP, UDP, ICMPv6 and other transport
+/// checksums.
+#[inline(always)]
+pub fn pseudo_ipv6(src: [u8; 16], dst: [u8; 16], next_header: u8, len: u32) -> u32 {
+    let acc = sum(&src, 0);
+    let acc = sum(&dst, acc);
+    acc + (len >> 16) + (len & 0xffff) + next_header as u32
+}
+
+/// Table for the reflected Castagnoli polynomial, one entry per byte value.
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => crc >> 1 ^ 0x82f6_3b78,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Returns the CRC32c of `data` following a run of bytes whose CRC32c is `initial`, which is 0
+/// for the first run.
+#[inline(always)]
+pub fn crc32c(data: &[u8], initial: u32) -> u32 {
+    let crc = data.iter().fold(!initial, |crc, &b| {
+        crc >> 8 ^ CRC32C_TABLE[(crc as u8 ^ b) as usize]
+    });
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc1071_example() {
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(0xddf2, fold(sum(&data, 0)));
+        assert_eq!(be16::new(0x220d), checksum(&data));
+    }
+
+    #[test]
+    fn crc32c_vectors() {
+        assert_eq!(0, crc32c(&[], 0));
+        assert_eq!(0xe306_9283, crc32c(b"123456789", 0));
+        // RFC 3720, appendix B.4.
+        assert_eq!(0x8a91_36aa, crc32c(&[0; 32], 0));
+        assert_eq!(0x62a8_ab43, crc32c(&[0xff; 32], 0));
+        assert_eq!(
+            crc32c(b"123456789", 0),
+            crc32c(b"6789", crc32c(b"12345", 0))
+        );
+    }
+
+    #[test]
+    fn odd_length() {
+        assert_eq!(
+            fold(sum(&[0xab, 0xcd, 0xef, 0x00], 0)),
+            fold(sum(&[0xab, 0xcd, 0xef], 0))
+        );
+    }
+
+    #[test]
+    fn ipv4_header() {
+        let mut hdr = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(be16::new(0xb861), checksum(&hdr));
+        hdr[10..12].copy_from_slice(&[0xb8, 0x61]);
+        assert!(verify(&hdr));
+    }
+
+    #[test]
+    fn incremental() {
+        let mut hdr = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xb8, 0x61, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        let check = be16::new(0xb861);
+
+        let c = update16(check, be16::new(0x4011), be16::new(0x3f11));
+        hdr[8] = 0x3f;
+        hdr[10..12].copy_from_slice(&c.to_be_bytes());
+        assert!(verify(&hdr));
+
+        let c2 = update32(c, be32::new(0xc0a800c7), be32::new(0x0a000001));
+        let c3 = update(c, &hdr[16..20], &[10, 0, 0, 1]);
+        assert_eq!(c2, c3);
+        hdr[16..20].copy_from_slice(&[10, 0, 0, 1]);
+        hdr[10..12].copy_from_slice(&c2.to_be_bytes());
+        assert!(verify(&hdr));
+    }
+}
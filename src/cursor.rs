@@ -0,0 +1,240 @@
+//! Header access at offsets the eBPF verifier can bound.
+//!
+//! The verifier tracks the range of every scalar added to a packet pointer and rejects an access
+//! whose offset it can't bound, even when a comparison against the end of the packet follows. A
+//! length read from the packet, such as the IPv4 IHL, is bounded by its field width, but the sum
+//! of several of them often isn't by the time LLVM has reordered or spilled it. A [`Cursor`] keeps
+//! its offset at most `MAX`, and masks it again before every access. A mask right after the check
+//! against `MAX` is a no-op that LLVM removes, leaving only a branch it is free to turn into a
+//! select after the pointer arithmetic, so the offset is first reloaded through a volatile read,
+//! which the compiler can't see through. Each access is then checked against the end of the frame
+//! on the pointer that is dereferenced.
+
+use core::ffi::c_void;
+use core::ops::Range;
+
+use super::Ptr;
+
+/// A position in a frame that never moves past `MAX` bytes from its start.
+#[derive(Debug, Clone)]
+pub struct Cursor<const MAX: usize> {
+    frame: Range<*mut c_void>,
+    offset: usize,
+}
+
+impl<const MAX: usize> Cursor<MAX> {
+    /// The smallest all-ones mask that keeps `MAX`.
+    const MASK: usize = (MAX + 1).next_power_of_two() - 1;
+
+    /// Returns a cursor at the start of `frame`.
+    #[inline(always)]
+    pub fn new(frame: Range<*mut c_void>) -> Self {
+        Self { frame, offset: 0 }
+    }
+
+    /// Returns a cursor `offset` bytes into `frame`, or `Err` if that is past `MAX`.
+    #[inline(always)]
+    pub fn at(frame: Range<*mut c_void>, offset: usize) -> Result<Self, ()> {
+        Ok(Self {
+            frame,
+            offset: Self::bound(offset)?,
+        })
+    }
+
+    /// Returns the offset of the cursor from the start of the frame.
+    #[inline(always)]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the frame the cursor walks.
+    #[inline(always)]
+    pub fn frame(&self) -> Range<*mut c_void> {
+        self.frame.clone()
+    }
+
+    #[inline(always)]
+    fn bound(offset: usize) -> Result<usize, ()> {
+        match offset <= MAX {
+            true => Ok(offset),
+            false => Err(()),
+        }
+    }
+
+    /// Returns `offset`, at most `MAX`, masked with `MASK` in a way the compiler can't fold.
+    #[inline(always)]
+    fn mask(offset: usize) -> usize {
+        unsafe { core::ptr::read_volatile(&offset) & Self::MASK }
+    }
+
+    /// Returns the header of type `T` at the cursor without moving it, or `Err` if it doesn't fit
+    /// in the frame.
+    #[inline(always)]
+    pub fn peek<T>(&self) -> Result<Ptr<T>, ()> {
+        let offset = Self::mask(self.offset);
+        let pointer = unsafe { (self.frame.start as *mut u8).add(offset) } as *mut T;
+        unsafe {
+            if pointer.offset(1) > (self.frame.end as *mut T) {
+                return Err(());
+            }
+        }
+        Ok(Ptr::new(pointer))
+    }
+
+    /// Moves the cursor forward by `len` bytes, or returns `Err` without moving it if that would
+    /// take it past `MAX`. The cursor may end up past the end of the frame, in which case the next
+    /// header access fails.
+    #[inline(always)]
+    pub fn advance(&mut self, len: usize) -> Result<(), ()> {
+        let offset = self.offset.checked_add(len).ok_or(())?;
+        self.offset = Self::bound(offset)?;
+        Ok(())
+    }
+
+    /// Returns the fixed-size header of type `T` at the cursor and moves past it.
+    #[inline(always)]
+    pub fn next_t<T>(&mut self) -> Result<Ptr<T>, ()> {
+        let header = self.peek::<T>()?;
+        self.advance(core::mem::size_of::<T>())?;
+        Ok(header)
+    }
+
+    /// Returns the header of type `T` at the cursor and moves past the `len` bytes it gives for
+    /// itself, such as its length including options. Returns `Err` if that length is shorter than
+    /// `T`.
+    #[inline(always)]
+    pub fn next_with<T>(&mut self, len: impl FnOnce(&T) -> usize) -> Result<Ptr<T>, ()> {
+        let header = self.peek::<T>()?;
+        let len = len(&header);
+        if len < core::mem::size_of::<T>() {
+            return Err(());
+        }
+        self.advance(len)?;
+        Ok(header)
+    }
+
+    /// Returns the IPv4 header at the cursor and moves past it and its options.
+    #[inline(always)]
+    pub fn next_ipv4(&mut self) -> Result<Ptr<super::ipv4::Header>, ()> {
+        self.next_with(super::ipv4::Header::header_len)
+    }
+
+    /// Returns the IPv6 Hop-by-Hop, Routing or Destination Options header at the cursor and moves
+    /// past it.
+    #[inline(always)]
+    pub fn next_ipv6_ext(&mut self) -> Result<Ptr<super::ipv6::ExtHeader>, ()> {
+        self.next_with(super::ipv6::ExtHeader::header_len)
+    }
+
+    /// Returns the TCP header at the cursor and moves past it and its options.
+    #[inline(always)]
+    pub fn next_tcp(&mut self) -> Result<Ptr<super::tcp::Header>, ()> {
+        self.next_with(super::tcp::Header::header_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ip::Proto;
+
+    #[test]
+    fn mask() {
+        assert_eq!(0, Cursor::<0>::MASK);
+        assert_eq!(0x3f, Cursor::<60>::MASK);
+        assert_eq!(0x3f, Cursor::<63>::MASK);
+        assert_eq!(0x7f, Cursor::<64>::MASK);
+        assert_eq!(0x7ff, Cursor::<1500>::MASK);
+    }
+
+    #[test]
+    fn bounds() {
+        let mut frame = crate::Aligned([0u8; 64]);
+        let r = frame.range(64);
+        let mut c = Cursor::<40>::new(r.clone());
+        assert!(c.next_t::<crate::eth::Header>().is_ok());
+        assert_eq!(14, c.offset());
+        assert!(c.advance(27).is_err());
+        assert_eq!(14, c.offset());
+        assert!(c.advance(26).is_ok());
+        assert!(c.peek::<u8>().is_ok());
+        assert!(c.next_t::<u8>().is_err());
+        assert!(Cursor::<40>::at(r.clone(), 41).is_err());
+
+        // The frame ends before MAX.
+        let mut c = Cursor::<1500>::at(frame.range(20), 16).unwrap();
+        assert!(c.peek::<[u8; 4]>().is_ok());
+        assert!(c.peek::<[u8; 5]>().is_err());
+        c.advance(100).unwrap();
+        assert!(c.peek::<u8>().is_err());
+    }
+
+    #[test]
+    fn ipv4_options_tcp() {
+        let mut buf = crate::Aligned([0u8; 14 + 24 + 24]);
+        let mut ip = crate::ipv4::Header {
+            proto: Proto::TCP,
+            ..Default::default()
+        };
+        ip.set_ihl(6);
+        crate::write_header(&mut buf.0, 14, &ip).unwrap();
+        let mut tcp = crate::tcp::Header {
+            dest: 80.into(),
+            ..Default::default()
+        };
+        tcp.set_data_offset(6);
+        crate::write_header(&mut buf.0, 38, &tcp).unwrap();
+
+        let mut c = Cursor::<128>::new(buf.range(62));
+        c.next_t::<crate::eth::Header>().unwrap();
+        assert_eq!(Proto::TCP, c.next_ipv4().unwrap().proto);
+        assert_eq!(38, c.offset());
+        assert_eq!(80, c.next_tcp().unwrap().dest.get());
+        assert_eq!(62, c.offset());
+
+        // Options longer than MAX allows.
+        let mut c = Cursor::<60>::at(buf.range(62), 14).unwrap();
+        assert!(c.next_ipv4().is_ok());
+        assert!(c.next_tcp().is_err());
+        assert_eq!(38, c.offset());
+
+        // An IHL below 5 is malformed.
+        ip.set_ihl(4);
+        crate::write_header(&mut buf.0, 14, &ip).unwrap();
+        let mut c = Cursor::<128>::at(buf.range(62), 14).unwrap();
+        assert!(c.next_ipv4().is_err());
+        assert_eq!(14, c.offset());
+    }
+
+    #[test]
+    fn ipv6_extensions() {
+        let mut buf = crate::Aligned([0u8; 40 + 8 + 16 + 8]);
+        let ip = crate::ipv6::Header {
+            proto: Proto::HOPOPTS,
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 0, &ip).unwrap();
+        let hop = crate::ipv6::ExtHeader {
+            next_header: Proto::DSTOPTS,
+            hdr_ext_len: 0,
+        };
+        crate::write_header(&mut buf.0, 40, &hop).unwrap();
+        let dst = crate::ipv6::ExtHeader {
+            next_header: Proto::UDP,
+            hdr_ext_len: 1,
+        };
+        crate::write_header(&mut buf.0, 48, &dst).unwrap();
+
+        let mut c = Cursor::<256>::new(buf.range(72));
+        let mut proto = c.next_t::<crate::ipv6::Header>().unwrap().proto;
+        for _ in 0..4 {
+            if !matches!(proto, Proto::HOPOPTS | Proto::ROUTING | Proto::DSTOPTS) {
+                break;
+            }
+            proto = c.next_ipv6_ext().unwrap().next_header;
+        }
+        assert_eq!(Proto::UDP, proto);
+        assert_eq!(64, c.offset());
+        assert!(c.next_t::<crate::udp::Header>().is_ok());
+    }
+}
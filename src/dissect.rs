@@ -0,0 +1,952 @@
+//! Human-readable dissection of whole frames.
+//!
+//! [`layers`] walks the headers of an Ethernet frame held in a byte slice, and [`summary`] and
+//! [`tree`] format them as a tcpdump-like line or a Wireshark-like tree. Both are plain
+//! [`core::fmt::Display`] values, so they can be written to any [`core::fmt::Write`] without
+//! allocating.
+
+use core::fmt;
+use core::net::IpAddr;
+use core::ops::Range;
+
+use super::eth::MacAddr;
+use super::ip::Proto;
+
+/// A header found in a frame.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(::serde::Serialize),
+    serde(tag = "type", content = "header", rename_all = "snake_case")
+)]
+pub enum Layer {
+    Eth(super::eth::Header),
+    Arp(super::arp::Header),
+    ArpVar(super::arp::VarHeader),
+    Ipv4(super::ipv4::Header),
+    Ipv6(super::ipv6::Header),
+    /// A Hop-by-Hop, Routing or Destination Options header of the given type.
+    Ipv6Ext(Proto, super::ipv6::ExtHeader),
+    Ipv6Fragment(super::ipv6::FragmentHeader),
+    Tcp(super::tcp::Header),
+    Udp(super::udp::Header),
+    Icmp(super::icmp::Header),
+    Icmpv6(super::icmpv6::Header),
+    Vxlan(super::vxlan::Header),
+    Gtp(super::gtp::Header),
+    /// Bytes that aren't decoded.
+    Payload,
+    /// A header of the named protocol that doesn't fit in what's left of the frame.
+    Truncated(&'static str),
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Next {
+    Eth,
+    Arp,
+    Ipv4,
+    Ipv6,
+    L4(Proto),
+    Vxlan,
+    Gtp,
+    Payload,
+    Done,
+}
+
+/// Iterator over the headers of a frame, yielding each with the range of the frame it covers,
+/// from its start to the end of its payload.
+#[derive(Debug, Clone)]
+pub struct Layers<'a> {
+    frame: &'a [u8],
+    offset: usize,
+    end: usize,
+    next: Next,
+}
+
+/// Returns the headers of the Ethernet frame `frame`.
+pub fn layers(frame: &[u8]) -> Layers<'_> {
+    Layers {
+        frame,
+        offset: 0,
+        end: frame.len(),
+        next: Next::Eth,
+    }
+}
+
+impl Layers<'_> {
+    fn truncated(&mut self, name: &'static str) -> Option<(Range<usize>, Layer)> {
+        self.next = Next::Done;
+        Some((self.offset..self.end, Layer::Truncated(name)))
+    }
+
+    fn header<T: super::Plain>(&self) -> Option<T> {
+        super::read_header(&self.frame[..self.end], self.offset).ok()
+    }
+
+    /// Yields a header of `len` bytes that covers the rest of the packet.
+    fn advance(&mut self, len: usize, next: Next, layer: Layer) -> Option<(Range<usize>, Layer)> {
+        let range = self.offset..self.end;
+        self.offset = (self.offset + len).min(self.end);
+        self.next = next;
+        Some((range, layer))
+    }
+}
+
+impl Iterator for Layers<'_> {
+    type Item = (Range<usize>, Layer);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next {
+            Next::Eth => {
+                let Some(eth) = self.header::<super::eth::Header>() else {
+                    return self.truncated("ethernet");
+                };
+                let next = match eth.ether_type {
+                    super::eth::Type::ARP => Next::Arp,
+                    super::eth::Type::IPV4 => Next::Ipv4,
+                    super::eth::Type::IPV6 => Next::Ipv6,
+                    _ => Next::Payload,
+                };
+                self.advance(super::eth::Header::LEN, next, Layer::Eth(eth))
+            }
+            Next::Arp => {
+                let Some(var) = self.header::<super::arp::VarHeader>() else {
+                    return self.truncated("arp");
+                };
+                let len = var.packet_len();
+                if !var.is_ethernet_ipv4() {
+                    return self.advance(len, Next::Payload, Layer::ArpVar(var));
+                }
+                match self.header::<super::arp::Header>() {
+                    Some(arp) => self.advance(len, Next::Payload, Layer::Arp(arp)),
+                    None => self.truncated("arp"),
+                }
+            }
+            Next::Ipv4 => {
+                let Some(ip) = self.header::<super::ipv4::Header>() else {
+                    return self.truncated("ip");
+                };
+                let len = ip.header_len();
+                let tot_len = ip.tot_len.get() as usize;
+                if len < super::ipv4::Header::LEN || tot_len < len || self.offset + len > self.end {
+                    return self.truncated("ip");
+                }
+                self.end = self.end.min(self.offset + tot_len);
+                // Extension headers only exist in IPv6.
+                let next = match ip.fragment_offset() {
+                    0 if !ip.proto.is_ipv6_ext() && ip.proto != Proto::FRAGMENT => {
+                        Next::L4(ip.proto)
+                    }
+                    _ => Next::Payload,
+                };
+                self.advance(len, next, Layer::Ipv4(ip))
+            }
+            Next::Ipv6 => {
+                let Some(ip) = self.header::<super::ipv6::Header>() else {
+                    return self.truncated("ip6");
+                };
+                let len = super::ipv6::Header::LEN;
+                self.end = self
+                    .end
+                    .min(self.offset + len + ip.payload_len.get() as usize);
+                self.advance(len, Next::L4(ip.proto), Layer::Ipv6(ip))
+            }
+            Next::L4(proto) => match proto {
+                Proto::TCP => {
+                    let Some(tcp) = self.header::<super::tcp::Header>() else {
+                        return self.truncated("tcp");
+                    };
+                    let len = tcp.header_len().max(super::tcp::Header::LEN);
+                    self.advance(len, Next::Payload, Layer::Tcp(tcp))
+                }
+                Proto::UDP => {
+                    let Some(udp) = self.header::<super::udp::Header>() else {
+                        return self.truncated("udp");
+                    };
+                    let next = match udp.destination.get() {
+                        super::vxlan::PORT => Next::Vxlan,
+                        super::gtp::PORT => Next::Gtp,
+                        _ => Next::Payload,
+                    };
+                    self.advance(super::udp::Header::LEN, next, Layer::Udp(udp))
+                }
+                Proto::ICMP => match self.header::<super::icmp::Header>() {
+                    Some(icmp) => {
+                        self.advance(super::icmp::Header::LEN, Next::Payload, Layer::Icmp(icmp))
+                    }
+                    None => self.truncated("icmp"),
+                },
+                Proto::ICMPV6 => match self.header::<super::icmpv6::Header>() {
+                    Some(icmp) => self.advance(
+                        super::icmpv6::Header::LEN,
+                        Next::Payload,
+                        Layer::Icmpv6(icmp),
+                    ),
+                    None => self.truncated("icmp6"),
+                },
+                Proto::FRAGMENT => {
+                    let Some(frag) = self.header::<super::ipv6::FragmentHeader>() else {
+                        return self.truncated("frag");
+                    };
+                    let next = match frag.offset() {
+                        0 => Next::L4(frag.next_header),
+                        _ => Next::Payload,
+                    };
+                    let len = super::ipv6::FragmentHeader::LEN;
+                    self.advance(len, next, Layer::Ipv6Fragment(frag))
+                }
+                Proto::NONE => {
+                    self.next = Next::Done;
+                    None
+                }
+                proto if proto.is_ipv6_ext() => {
+                    let Some(ext) = self.header::<super::ipv6::ExtHeader>() else {
+                        return self.truncated("ip6 ext");
+                    };
+                    if self.offset + ext.header_len() > self.end {
+                        return self.truncated("ip6 ext");
+                    }
+                    let next = Next::L4(ext.next_header);
+                    self.advance(ext.header_len(), next, Layer::Ipv6Ext(proto, ext))
+                }
+                _ => {
+                    self.next = Next::Payload;
+                    self.next()
+                }
+            },
+            Next::Vxlan => match self.header::<super::vxlan::Header>() {
+                Some(vxlan) => {
+                    self.advance(super::vxlan::Header::LEN, Next::Eth, Layer::Vxlan(vxlan))
+                }
+                None => self.truncated("vxlan"),
+            },
+            Next::Gtp => {
+                let Some(gtp) = self.header::<super::gtp::Header>() else {
+                    return self.truncated("gtp");
+                };
+                self.end = self
+                    .end
+                    .min(self.offset + super::gtp::Header::LEN + gtp.length.get() as usize);
+                let packet = &self.frame[self.offset..self.end];
+                let Ok(len) = super::gtp::payload_offset(packet) else {
+                    return self.truncated("gtp");
+                };
+                let next = match (gtp.msg_type, packet.get(len).map(|b| b >> 4)) {
+                    (super::gtp::MsgType::G_PDU, Some(4)) => Next::Ipv4,
+                    (super::gtp::MsgType::G_PDU, Some(6)) => Next::Ipv6,
+                    _ => Next::Payload,
+                };
+                self.advance(len, next, Layer::Gtp(gtp))
+            }
+            Next::Payload if self.offset < self.end => {
+                self.advance(self.end - self.offset, Next::Done, Layer::Payload)
+            }
+            Next::Payload | Next::Done => {
+                self.next = Next::Done;
+                None
+            }
+        }
+    }
+}
+
+/// A tcpdump-like one-line summary of a frame.
+#[derive(Debug, Copy, Clone)]
+pub struct Summary<'a> {
+    frame: &'a [u8],
+}
+
+/// Returns a one-line summary of the Ethernet frame `frame`, such as
+/// `02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype IPv4 (0x0800), length 54: 10.0.0.1.40000 >
+/// 10.0.0.2.80: Flags [S], seq 1000, win 100, length 0`.
+pub fn summary(frame: &[u8]) -> Summary<'_> {
+    Summary { frame }
+}
+
+/// An IP header whose summary waits for the transport header.
+struct PendingIp {
+    source: IpAddr,
+    destination: IpAddr,
+    proto: Proto,
+}
+
+impl PendingIp {
+    fn flush(self, f: &mut fmt::Formatter<'_>, sep: &str, len: usize) -> fmt::Result {
+        write!(
+            f,
+            "{sep}{} > {}: {}, length {len}",
+            self.source, self.destination, self.proto
+        )
+    }
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ip: Option<(PendingIp, usize)> = None;
+        let mut sep = "";
+        for (range, layer) in layers(self.frame) {
+            let len = range.len();
+            // Headers that are shown as part of the IP packet they belong to.
+            match layer {
+                Layer::Ipv6Ext(..) => continue,
+                Layer::Ipv6Fragment(frag) => {
+                    if let Some((pending, ip_len)) = ip.take() {
+                        pending.flush(f, sep, ip_len)?;
+                    }
+                    write!(f, " {frag}")?;
+                    sep = ": ";
+                    continue;
+                }
+                Layer::Tcp(_) | Layer::Udp(_) | Layer::Icmp(_) | Layer::Icmpv6(_) => {}
+                _ => {
+                    if let Some((pending, ip_len)) = ip.take() {
+                        pending.flush(f, sep, ip_len)?;
+                        sep = ": ";
+                    }
+                }
+            }
+            let payload = |header_len: usize| len.saturating_sub(header_len);
+            let addresses = ip.take().map(|(ip, _)| (ip.source, ip.destination));
+            match layer {
+                Layer::Eth(eth) => {
+                    let ether_type = eth.ether_type;
+                    write!(
+                        f,
+                        "{sep}{} > {}, ethertype {} (0x{:04x}), length {len}",
+                        MacAddr(eth.src_addr),
+                        MacAddr(eth.dst_addr),
+                        ether_type,
+                        ether_type.get()
+                    )?;
+                }
+                Layer::Arp(arp) => write!(f, "{sep}{arp}, length {len}")?,
+                Layer::ArpVar(arp) => write!(f, "{sep}{arp}, length {len}")?,
+                Layer::Ipv4(h) => {
+                    let pending = PendingIp {
+                        source: h.source().into(),
+                        destination: h.destination().into(),
+                        proto: h.proto,
+                    };
+                    ip = Some((pending, len));
+                    continue;
+                }
+                Layer::Ipv6(h) => {
+                    let pending = PendingIp {
+                        source: h.source().into(),
+                        destination: h.destination().into(),
+                        proto: h.proto,
+                    };
+                    ip = Some((pending, len));
+                    continue;
+                }
+                Layer::Tcp(tcp) => {
+                    let (source, dest) = (tcp.source, tcp.dest);
+                    match addresses {
+                        Some((s, d)) => write!(f, "{sep}{s}.{source} > {d}.{dest}: ")?,
+                        None => write!(f, "{sep}{source} > {dest}: ")?,
+                    }
+                    let (seq, window) = (tcp.seq, tcp.window);
+                    write!(f, "Flags [{}], seq {seq}", tcp.flags())?;
+                    if tcp.flags().contains(super::tcp::Flags::ACK) {
+                        let ack_seq = tcp.ack_seq;
+                        write!(f, ", ack {ack_seq}")?;
+                    }
+                    write!(f, ", win {window}, length {}", payload(tcp.header_len()))?;
+                }
+                Layer::Udp(udp) => {
+                    let (source, dest) = (udp.source, udp.destination);
+                    match addresses {
+                        Some((s, d)) => write!(f, "{sep}{s}.{source} > {d}.{dest}: ")?,
+                        None => write!(f, "{sep}{source} > {dest}: ")?,
+                    }
+                    write!(f, "UDP, length {}", payload(super::udp::Header::LEN))?;
+                }
+                Layer::Icmp(icmp) => {
+                    if let Some((s, d)) = addresses {
+                        write!(f, "{sep}{s} > {d}: ")?;
+                    }
+                    write!(f, "{icmp}, length {}", payload(super::icmp::Header::LEN))?;
+                }
+                Layer::Icmpv6(icmp) => {
+                    if let Some((s, d)) = addresses {
+                        write!(f, "{sep}{s} > {d}: ")?;
+                    }
+                    write!(f, "{icmp}, length {}", payload(super::icmpv6::Header::LEN))?;
+                }
+                Layer::Vxlan(vxlan) => write!(f, "{sep}{vxlan}")?,
+                Layer::Gtp(gtp) => write!(f, "{sep}{gtp}")?,
+                Layer::Truncated(name) => write!(f, " [|{name}]")?,
+                Layer::Ipv6Ext(..) | Layer::Ipv6Fragment(_) | Layer::Payload => continue,
+            }
+            sep = ": ";
+        }
+        if let Some((pending, ip_len)) = ip {
+            pending.flush(f, sep, ip_len)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes the frame as a list of its layers, each with its `offset`, `length` and `layer`.
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for Summary<'_> {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use ::serde::ser::{SerializeSeq, SerializeStruct};
+
+        struct Entry(Range<usize>, Layer);
+
+        impl ::serde::Serialize for Entry {
+            fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                let mut entry = s.serialize_struct("Layer", 3)?;
+                entry.serialize_field("offset", &self.0.start)?;
+                entry.serialize_field("length", &self.0.len())?;
+                entry.serialize_field("layer", &self.1)?;
+                entry.end()
+            }
+        }
+
+        let mut seq = s.serialize_seq(Some(layers(self.frame).count()))?;
+        for (range, layer) in layers(self.frame) {
+            seq.serialize_element(&Entry(range, layer))?;
+        }
+        seq.end()
+    }
+}
+
+/// A Wireshark-like tree of the headers of a frame, one line per field.
+#[derive(Debug, Copy, Clone)]
+pub struct Tree<'a> {
+    frame: &'a [u8],
+}
+
+/// Returns a multi-line tree of the headers of the Ethernet frame `frame`, with a line for each
+/// header followed by an indented line for each of its fields.
+pub fn tree(frame: &[u8]) -> Tree<'_> {
+    Tree { frame }
+}
+
+impl fmt::Display for Tree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const I: &str = "    ";
+        for (range, layer) in layers(self.frame) {
+            let len = range.len();
+            match layer {
+                Layer::Eth(eth) => {
+                    let (src, dst) = (MacAddr(eth.src_addr), MacAddr(eth.dst_addr));
+                    let ether_type = eth.ether_type;
+                    writeln!(f, "Ethernet II, Src: {src}, Dst: {dst}")?;
+                    writeln!(f, "{I}Destination: {dst}")?;
+                    writeln!(f, "{I}Source: {src}")?;
+                    writeln!(f, "{I}Type: {} (0x{:04x})", ether_type, ether_type.get())?;
+                }
+                Layer::Arp(arp) => {
+                    let oper = arp.oper;
+                    writeln!(f, "Address Resolution Protocol ({oper})")?;
+                    writeln!(f, "{I}Hardware size: {}", arp.h_len)?;
+                    writeln!(f, "{I}Protocol size: {}", arp.p_len)?;
+                    writeln!(f, "{I}Opcode: {oper}")?;
+                    writeln!(f, "{I}Sender MAC address: {}", MacAddr(arp.sha))?;
+                    let spa = core::net::Ipv4Addr::from(arp.spa);
+                    writeln!(f, "{I}Sender IP address: {spa}")?;
+                    writeln!(f, "{I}Target MAC address: {}", MacAddr(arp.tha))?;
+                    let tpa = core::net::Ipv4Addr::from(arp.tpa);
+                    writeln!(f, "{I}Target IP address: {tpa}")?;
+                }
+                Layer::ArpVar(arp) => {
+                    let oper = arp.oper;
+                    writeln!(f, "Address Resolution Protocol ({oper})")?;
+                    writeln!(f, "{I}Hardware size: {}", arp.h_len)?;
+                    writeln!(f, "{I}Protocol size: {}", arp.p_len)?;
+                    writeln!(f, "{I}Opcode: {oper}")?;
+                }
+                Layer::Ipv4(ip) => {
+                    let (tot_len, id, frag_off, check) = (ip.tot_len, ip.id, ip.frag_off, ip.check);
+                    writeln!(
+                        f,
+                        "Internet Protocol Version 4, Src: {}, Dst: {}",
+                        ip.source(),
+                        ip.destination()
+                    )?;
+                    writeln!(f, "{I}Version: {}", ip.version())?;
+                    writeln!(f, "{I}Header Length: {} bytes", ip.header_len())?;
+                    writeln!(f, "{I}Type of Service: 0x{:02x}", ip.tos)?;
+                    writeln!(f, "{I}Total Length: {tot_len}")?;
+                    writeln!(f, "{I}Identification: 0x{id:04x} ({id})")?;
+                    writeln!(
+                        f,
+                        "{I}Flags: 0x{:x}{}{}",
+                        frag_off.get() >> 13,
+                        if ip.dont_fragment() {
+                            ", Don't fragment"
+                        } else {
+                            ""
+                        },
+                        if ip.more_fragments() {
+                            ", More fragments"
+                        } else {
+                            ""
+                        }
+                    )?;
+                    writeln!(f, "{I}Fragment Offset: {}", ip.fragment_offset())?;
+                    writeln!(f, "{I}Time to Live: {}", ip.ttl)?;
+                    writeln!(f, "{I}Protocol: {} ({})", ip.proto, u8::from(ip.proto))?;
+                    writeln!(f, "{I}Header Checksum: 0x{check:04x}")?;
+                }
+                Layer::Ipv6(ip) => {
+                    let payload_len = ip.payload_len;
+                    writeln!(
+                        f,
+                        "Internet Protocol Version 6, Src: {}, Dst: {}",
+                        ip.source(),
+                        ip.destination()
+                    )?;
+                    writeln!(f, "{I}Version: {}", ip.version())?;
+                    writeln!(f, "{I}Traffic Class: 0x{:02x}", ip.priority())?;
+                    writeln!(f, "{I}Flow Label: 0x{:05x}", ip.flow())?;
+                    writeln!(f, "{I}Payload Length: {payload_len}")?;
+                    writeln!(f, "{I}Next Header: {} ({})", ip.proto, u8::from(ip.proto))?;
+                    writeln!(f, "{I}Hop Limit: {}", ip.hop_limit)?;
+                }
+                Layer::Ipv6Ext(proto, ext) => {
+                    writeln!(f, "IPv6 {proto} Header")?;
+                    let next = ext.next_header;
+                    writeln!(f, "{I}Next Header: {next} ({})", u8::from(next))?;
+                    writeln!(f, "{I}Length: {} bytes", ext.header_len())?;
+                }
+                Layer::Ipv6Fragment(frag) => {
+                    let (next, identification) = (frag.next_header, frag.identification);
+                    writeln!(f, "IPv6 Fragment Header")?;
+                    writeln!(f, "{I}Next Header: {next} ({})", u8::from(next))?;
+                    writeln!(f, "{I}Offset: {}", frag.offset())?;
+                    writeln!(f, "{I}More Fragments: {}", frag.more_fragments())?;
+                    writeln!(f, "{I}Identification: 0x{identification:08x}")?;
+                }
+                Layer::Tcp(tcp) => {
+                    let (source, dest, seq, ack_seq) = (tcp.source, tcp.dest, tcp.seq, tcp.ack_seq);
+                    let (window, check, urg_ptr) = (tcp.window, tcp.check, tcp.urg_ptr);
+                    let payload = len.saturating_sub(tcp.header_len());
+                    writeln!(
+                        f,
+                        "Transmission Control Protocol, Src Port: {source}, Dst Port: {dest}, \
+                         Seq: {seq}, Len: {payload}"
+                    )?;
+                    writeln!(f, "{I}Source Port: {source}")?;
+                    writeln!(f, "{I}Destination Port: {dest}")?;
+                    writeln!(f, "{I}Sequence Number: {seq}")?;
+                    writeln!(f, "{I}Acknowledgment Number: {ack_seq}")?;
+                    writeln!(f, "{I}Header Length: {} bytes", tcp.header_len())?;
+                    let flags = tcp.flags();
+                    writeln!(f, "{I}Flags: 0x{:02x} [{flags}]", flags.bits())?;
+                    writeln!(f, "{I}Window: {window}")?;
+                    writeln!(f, "{I}Checksum: 0x{check:04x}")?;
+                    writeln!(f, "{I}Urgent Pointer: {urg_ptr}")?;
+                }
+                Layer::Udp(udp) => {
+                    let (source, dest) = (udp.source, udp.destination);
+                    let (udp_len, check) = (udp.len, udp.check);
+                    writeln!(
+                        f,
+                        "User Datagram Protocol, Src Port: {source}, Dst Port: {dest}"
+                    )?;
+                    writeln!(f, "{I}Source Port: {source}")?;
+                    writeln!(f, "{I}Destination Port: {dest}")?;
+                    writeln!(f, "{I}Length: {udp_len}")?;
+                    writeln!(f, "{I}Checksum: 0x{check:04x}")?;
+                }
+                Layer::Icmp(icmp) => {
+                    let check = icmp.check;
+                    writeln!(f, "Internet Control Message Protocol")?;
+                    writeln!(f, "{I}Type: {} ({})", icmp.msg_type, icmp.msg_type.get())?;
+                    writeln!(f, "{I}Code: {}", icmp.code)?;
+                    writeln!(f, "{I}Checksum: 0x{check:04x}")?;
+                    if matches!(
+                        icmp.msg_type,
+                        super::icmp::Type::ECHO_REQUEST | super::icmp::Type::ECHO_REPLY
+                    ) {
+                        writeln!(f, "{I}Identifier: {}", icmp.id())?;
+                        writeln!(f, "{I}Sequence Number: {}", icmp.seq())?;
+                    }
+                }
+                Layer::Icmpv6(icmp) => {
+                    let check = icmp.check;
+                    writeln!(f, "Internet Control Message Protocol v6")?;
+                    writeln!(f, "{I}Type: {} ({})", icmp.msg_type, icmp.msg_type.get())?;
+                    writeln!(f, "{I}Code: {}", icmp.code)?;
+                    writeln!(f, "{I}Checksum: 0x{check:04x}")?;
+                    if matches!(
+                        icmp.msg_type,
+                        super::icmpv6::Type::ECHO_REQUEST | super::icmpv6::Type::ECHO_REPLY
+                    ) {
+                        writeln!(f, "{I}Identifier: {}", icmp.id())?;
+                        writeln!(f, "{I}Sequence Number: {}", icmp.seq())?;
+                    }
+                }
+                Layer::Vxlan(vxlan) => {
+                    writeln!(f, "Virtual eXtensible Local Area Network")?;
+                    match vxlan.vni() {
+                        Some(vni) => writeln!(f, "{I}VXLAN Network Identifier (VNI): {vni}")?,
+                        None => writeln!(f, "{I}VXLAN Network Identifier (VNI): none")?,
+                    }
+                }
+                Layer::Gtp(gtp) => {
+                    writeln!(f, "GPRS Tunneling Protocol")?;
+                    writeln!(f, "{I}Flags: 0x{:02x}", gtp.flags)?;
+                    writeln!(f, "{I}Version: {}", gtp.version())?;
+                    writeln!(
+                        f,
+                        "{I}Message Type: {} ({})",
+                        gtp.msg_type,
+                        gtp.msg_type.get()
+                    )?;
+                    writeln!(f, "{I}Length: {}", gtp.length.get())?;
+                    writeln!(f, "{I}TEID: 0x{:08x}", gtp.teid())?;
+                }
+                Layer::Payload => writeln!(f, "Data ({len} bytes)")?,
+                Layer::Truncated(name) => writeln!(f, "[Truncated {name} header]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::Flags;
+    use proptest::prelude::*;
+
+    /// Collects formatted output in a fixed buffer, as a `no_std` user would.
+    struct Buf {
+        data: [u8; 2048],
+        len: usize,
+    }
+
+    impl Buf {
+        fn new() -> Self {
+            Self {
+                data: [0; 2048],
+                len: 0,
+            }
+        }
+
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.data[..self.len]).unwrap()
+        }
+    }
+
+    impl fmt::Write for Buf {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.data
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    fn show(v: impl fmt::Display) -> Buf {
+        let mut buf = Buf::new();
+        fmt::Write::write_fmt(&mut buf, format_args!("{v}")).unwrap();
+        buf
+    }
+
+    const ETH: usize = crate::eth::Header::LEN;
+
+    fn eth(buf: &mut [u8], ether_type: crate::eth::Type) {
+        let eth = crate::eth::Header {
+            dst_addr: [2, 0, 0, 0, 0, 2],
+            src_addr: [2, 0, 0, 0, 0, 1],
+            ether_type,
+        };
+        crate::write_header(buf, 0, &eth).unwrap();
+    }
+
+    fn tcp_frame() -> [u8; 58] {
+        let mut buf = [0u8; 58];
+        eth(&mut buf, crate::eth::Type::IPV4);
+        let mut ip = crate::ipv4::Header {
+            tot_len: 44.into(),
+            id: 1.into(),
+            ttl: 64,
+            proto: crate::ip::Proto::TCP,
+            source: [10, 0, 0, 1],
+            destination: [10, 0, 0, 2],
+            ..Default::default()
+        };
+        ip.set_dont_fragment(true);
+        crate::write_header(&mut buf, ETH, &ip).unwrap();
+        let mut tcp = crate::tcp::Header {
+            source: 40000.into(),
+            dest: 80.into(),
+            seq: 1000.into(),
+            ack_seq: 7.into(),
+            window: 100.into(),
+            ..Default::default()
+        };
+        tcp.set_data_offset(5);
+        tcp.set_flags(Flags::SYN | Flags::ACK);
+        crate::write_header(&mut buf, ETH + 20, &tcp).unwrap();
+        buf
+    }
+
+    #[test]
+    fn headers() {
+        let frame = tcp_frame();
+        let eth: crate::eth::Header = crate::read_header(&frame, 0).unwrap();
+        assert_eq!(
+            "02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype IPv4 (0x0800)",
+            show(eth).as_str()
+        );
+        let ip: crate::ipv4::Header = crate::read_header(&frame, ETH).unwrap();
+        assert_eq!(
+            "10.0.0.1 > 10.0.0.2: TCP, tos 0x0, ttl 64, id 1, offset 0, flags [DF], length 44",
+            show(ip).as_str()
+        );
+        let tcp: crate::tcp::Header = crate::read_header(&frame, ETH + 20).unwrap();
+        assert_eq!(
+            "40000 > 80: Flags [S.], seq 1000, ack 7, win 100",
+            show(tcp).as_str()
+        );
+        let arp = crate::arp::Header::new(
+            crate::arp::Operation::REQUEST,
+            [2, 0, 0, 0, 0, 1],
+            [10, 0, 0, 1].into(),
+            [0; 6],
+            [10, 0, 0, 2].into(),
+        );
+        assert_eq!(
+            "ARP, Request who-has 10.0.0.2 tell 10.0.0.1",
+            show(arp).as_str()
+        );
+        let mut vxlan = crate::vxlan::Header::default();
+        vxlan.set_vni(42.into());
+        assert_eq!("VXLAN, flags [I] (0x08), vni 42", show(vxlan).as_str());
+        let ip6 = crate::ipv6::Header {
+            payload_len: 8.into(),
+            proto: crate::ip::Proto::UDP,
+            source: core::net::Ipv6Addr::LOCALHOST.octets(),
+            destination: core::net::Ipv6Addr::LOCALHOST.octets(),
+            ..Default::default()
+        };
+        assert_eq!(
+            "::1 > ::1: UDP, class 0x00, flowlabel 0x00000, hlim 64, payload length 8",
+            show(ip6).as_str()
+        );
+        let udp = crate::udp::Header {
+            source: 1.into(),
+            destination: 2.into(),
+            len: 8.into(),
+            check: 0.into(),
+        };
+        assert_eq!("1 > 2: UDP, length 8", show(udp).as_str());
+    }
+
+    #[test]
+    fn tcp_summary() {
+        let frame = tcp_frame();
+        assert_eq!(
+            "02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype IPv4 (0x0800), length 58: \
+             10.0.0.1.40000 > 10.0.0.2.80: Flags [S.], seq 1000, ack 7, win 100, length 4",
+            show(summary(&frame)).as_str()
+        );
+        // Cut in the middle of the TCP header.
+        assert_eq!(
+            "02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype IPv4 (0x0800), length 40: \
+             10.0.0.1 > 10.0.0.2: TCP, length 26 [|tcp]",
+            show(summary(&frame[..40])).as_str()
+        );
+    }
+
+    #[test]
+    fn tcp_tree() {
+        let frame = tcp_frame();
+        let tree = show(tree(&frame));
+        let lines: [&str; 4] = [
+            "Ethernet II, Src: 02:00:00:00:00:01, Dst: 02:00:00:00:00:02",
+            "Internet Protocol Version 4, Src: 10.0.0.1, Dst: 10.0.0.2",
+            "Transmission Control Protocol, Src Port: 40000, Dst Port: 80, Seq: 1000, Len: 4",
+            "Data (4 bytes)",
+        ];
+        let mut headers = tree.as_str().lines().filter(|l| !l.starts_with(' '));
+        for line in lines {
+            assert_eq!(Some(line), headers.next());
+        }
+        assert_eq!(None, headers.next());
+        assert!(tree.as_str().contains("\n    Flags: 0x12 [S.]\n"));
+        assert!(tree.as_str().contains("\n    Protocol: TCP (6)\n"));
+    }
+
+    #[test]
+    fn vxlan_icmpv6() {
+        // Outer IPv4/UDP/VXLAN carrying an inner IPv6 ICMPv6 echo request.
+        let mut buf = [0u8; 14 + 20 + 8 + 8 + 14 + 40 + 8];
+        eth(&mut buf, crate::eth::Type::IPV4);
+        let ip = crate::ipv4::Header {
+            tot_len: ((buf.len() - ETH) as u16).into(),
+            ttl: 64,
+            proto: crate::ip::Proto::UDP,
+            source: [192, 168, 0, 1],
+            destination: [192, 168, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut buf, ETH, &ip).unwrap();
+        let udp = crate::udp::Header {
+            source: 50000.into(),
+            destination: crate::vxlan::PORT.into(),
+            len: ((buf.len() - ETH - 20) as u16).into(),
+            check: 0.into(),
+        };
+        crate::write_header(&mut buf, ETH + 20, &udp).unwrap();
+        let mut vxlan = crate::vxlan::Header::default();
+        vxlan.set_vni(7.into());
+        crate::write_header(&mut buf, ETH + 28, &vxlan).unwrap();
+        let inner = ETH + 36;
+        eth(&mut buf[inner..], crate::eth::Type::IPV6);
+        let ip6 = crate::ipv6::Header {
+            payload_len: 8.into(),
+            proto: crate::ip::Proto::ICMPV6,
+            source: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            destination: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut buf, inner + ETH, &ip6).unwrap();
+        let icmp = crate::icmpv6::Header {
+            msg_type: crate::icmpv6::Type::ECHO_REQUEST,
+            rest: [0, 1, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut buf, inner + ETH + 40, &icmp).unwrap();
+
+        assert_eq!(
+            "02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype IPv4 (0x0800), length 112: \
+             192.168.0.1.50000 > 192.168.0.2.4789: UDP, length 70: \
+             VXLAN, flags [I] (0x08), vni 7: \
+             02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype IPv6 (0x86dd), length 62: \
+             fd00::1 > fd00::2: ICMP6 echo request, id 1, seq 2, length 0",
+            show(summary(&buf)).as_str()
+        );
+        let kinds = layers(&buf).map(|(_, layer)| core::mem::discriminant(&layer));
+        assert_eq!(7, kinds.count());
+    }
+
+    #[test]
+    fn gtp_icmp() {
+        // Outer IPv4/UDP/GTP-U with a PDU Session Container, carrying an inner ICMP echo request.
+        let mut buf = [0u8; 14 + 20 + 8 + 12 + 8 + 20 + 8];
+        eth(&mut buf, crate::eth::Type::IPV4);
+        let ip = crate::ipv4::Header {
+            tot_len: ((buf.len() - ETH) as u16).into(),
+            ttl: 64,
+            proto: crate::ip::Proto::UDP,
+            source: [192, 168, 0, 1],
+            destination: [192, 168, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut buf, ETH, &ip).unwrap();
+        let udp = crate::udp::Header {
+            source: crate::gtp::PORT.into(),
+            destination: crate::gtp::PORT.into(),
+            len: ((buf.len() - ETH - 20) as u16).into(),
+            check: 0.into(),
+        };
+        crate::write_header(&mut buf, ETH + 20, &udp).unwrap();
+        let mut gtp = crate::gtp::Header::new(crate::gtp::MsgType::G_PDU, 0x1234);
+        gtp.flags |= crate::gtp::Header::E;
+        gtp.length = ((buf.len() - ETH - 36) as u16).into();
+        crate::write_header(&mut buf, ETH + 28, &gtp).unwrap();
+        buf[ETH + 39] = crate::gtp::ExtType::PDU_SESSION_CONTAINER.get();
+        buf[ETH + 40..ETH + 44].copy_from_slice(&[1, 0, 9, 0]);
+        let inner = ETH + 44;
+        let ip = crate::ipv4::Header {
+            tot_len: 28.into(),
+            ttl: 64,
+            proto: crate::ip::Proto::ICMP,
+            source: [10, 0, 0, 1],
+            destination: [10, 0, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut buf, inner, &ip).unwrap();
+        let icmp = crate::icmp::Header {
+            msg_type: crate::icmp::Type::ECHO_REQUEST,
+            rest: [0, 1, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut buf, inner + 20, &icmp).unwrap();
+
+        assert_eq!(
+            "02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype IPv4 (0x0800), length 90: \
+             192.168.0.1.2152 > 192.168.0.2.2152: UDP, length 48: \
+             GTPv1 G-PDU, teid 0x00001234, length 40: \
+             10.0.0.1 > 10.0.0.2: ICMP echo request, id 1, seq 2, length 0",
+            show(summary(&buf)).as_str()
+        );
+        let kinds = layers(&buf).map(|(_, layer)| core::mem::discriminant(&layer));
+        assert_eq!(6, kinds.count());
+        assert!(show(tree(&buf)).as_str().contains("TEID: 0x00001234"));
+    }
+
+    #[test]
+    fn arp_summary() {
+        let mut buf = [0u8; 42];
+        eth(&mut buf, crate::eth::Type::ARP);
+        let arp = crate::arp::Header::new(
+            crate::arp::Operation::REPLY,
+            [2, 0, 0, 0, 0, 1],
+            [10, 0, 0, 1].into(),
+            [2, 0, 0, 0, 0, 2],
+            [10, 0, 0, 2].into(),
+        );
+        crate::write_header(&mut buf, ETH, &arp).unwrap();
+        assert_eq!(
+            "02:00:00:00:00:01 > 02:00:00:00:00:02, ethertype ARP (0x0806), length 42: \
+             ARP, Reply 10.0.0.1 is-at 02:00:00:00:00:01, length 28",
+            show(summary(&buf)).as_str()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_summary() {
+        let frame = tcp_frame();
+        let json = serde_json::to_value(summary(&frame)).unwrap();
+        let layers = json.as_array().unwrap();
+        assert_eq!(4, layers.len());
+        assert_eq!(14, layers[1]["offset"]);
+        assert_eq!(44, layers[1]["length"]);
+        assert_eq!("ipv4", layers[1]["layer"]["type"]);
+        assert_eq!("TCP", layers[1]["layer"]["header"]["protocol"]);
+        assert_eq!(
+            serde_json::json!(["SYN", "ACK"]),
+            layers[2]["layer"]["header"]["flags"]
+        );
+        assert_eq!(serde_json::json!({"type": "payload"}), layers[3]["layer"]);
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_frames(frame in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let mut outer = 0..frame.len();
+            for (range, _) in layers(&frame) {
+                prop_assert!(outer.start <= range.start && range.end <= outer.end);
+                outer = range;
+            }
+            // Output may not fit in the buffer; this only checks that formatting doesn't panic.
+            let _ = fmt::Write::write_fmt(&mut Buf::new(), format_args!("{}", summary(&frame)));
+            let _ = fmt::Write::write_fmt(&mut Buf::new(), format_args!("{}", tree(&frame)));
+        }
+
+        #[test]
+        fn truncated_tcp(len in 0usize..58) {
+            let frame = tcp_frame();
+            let (range, layer) = layers(&frame[..len]).last().unwrap();
+            prop_assert_eq!(len, range.end);
+            let expected = match len {
+                ..54 => matches!(layer, Layer::Truncated(_)),
+                54 => matches!(layer, Layer::Tcp(_)),
+                _ => matches!(layer, Layer::Payload),
+            };
+            prop_assert!(expected, "{:?} at length {}", layer, len);
+        }
+    }
+}
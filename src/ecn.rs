@@ -0,0 +1,255 @@
+//! ECN propagation between the outer and inner IP headers of a tunnel (RFC 6040).
+//!
+//! An encapsulator copies the ECN field of the inner header to the outer one so that a queue on
+//! the tunnel path can mark congestion on the outer header, and a decapsulator folds that mark
+//! back into the inner header with [`decap`]. [`vxlan_encap`] and [`vxlan_decap`] apply both
+//! rules to a VXLAN packet, whose inner IP header follows the inner Ethernet header.
+
+use super::ip::{Ecn, IpHeader};
+
+/// How an encapsulator sets the ECN field of the outer header.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Copies the ECN field of the inner header, which every RFC 6040 tunnel should do.
+    #[default]
+    Normal,
+    /// Clears the ECN field of the outer header, for a decapsulator that predates RFC 6040 and
+    /// might otherwise discard a congestion mark.
+    Compatibility,
+}
+
+/// Returns the ECN field of the outer header of a packet whose inner header has `inner`.
+#[inline(always)]
+pub fn encap(inner: Ecn, mode: Mode) -> Ecn {
+    match mode {
+        Mode::Normal => inner,
+        Mode::Compatibility => Ecn::NOT_ECT,
+    }
+}
+
+/// Returns the ECN field the inner header leaves the tunnel with, given the ones that arrived in
+/// the `outer` and `inner` headers, or `Err` if the packet must be dropped because congestion was
+/// marked on a transport that isn't ECN-capable (RFC 6040, section 4.2).
+#[inline(always)]
+pub fn decap(outer: Ecn, inner: Ecn) -> Result<Ecn, ()> {
+    match (inner, outer) {
+        (Ecn::NOT_ECT, Ecn::CE) => Err(()),
+        (Ecn::NOT_ECT, _) => Ok(Ecn::NOT_ECT),
+        (Ecn::CE, _) | (_, Ecn::CE) => Ok(Ecn::CE),
+        (Ecn::ECT_0, Ecn::ECT_1) => Ok(Ecn::ECT_1),
+        (inner, _) => Ok(inner),
+    }
+}
+
+/// Returns the IP header inside a VXLAN packet, or `None` if the inner frame isn't IP, which
+/// counts as Not-ECT.
+#[inline(always)]
+fn vxlan_inner(
+    vxlan: &mut super::vxlan::Header,
+    frame: core::ops::Range<*mut core::ffi::c_void>,
+) -> Result<Option<IpHeader>, ()> {
+    let mut eth = vxlan.ethernet(frame.clone())?;
+    match eth.ether_type {
+        super::eth::Type::IPV4 | super::eth::Type::IPV6 => {
+            Ok(Some(IpHeader::after_eth(&mut eth, frame)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Sets the ECN field of the `outer` header of a VXLAN packet from its inner IP header, patching
+/// the checksum of an outer IPv4 header. Returns `Err` if the inner headers don't fit in `frame`.
+#[inline(always)]
+pub fn vxlan_encap(
+    outer: &mut IpHeader,
+    vxlan: &mut super::vxlan::Header,
+    frame: core::ops::Range<*mut core::ffi::c_void>,
+    mode: Mode,
+) -> Result<(), ()> {
+    let inner = match vxlan_inner(vxlan, frame)? {
+        Some(ip) => ip.ecn(),
+        None => Ecn::NOT_ECT,
+    };
+    outer.set_ecn(encap(inner, mode));
+    Ok(())
+}
+
+/// Folds the ECN field of the `outer` header of a VXLAN packet into its inner IP header before
+/// the outer headers are stripped, patching the checksum of an inner IPv4 header. Returns `Err`
+/// if the packet must be dropped, either because [`decap`] says so or because the inner headers
+/// don't fit in `frame`.
+#[inline(always)]
+pub fn vxlan_decap(
+    outer: &IpHeader,
+    vxlan: &mut super::vxlan::Header,
+    frame: core::ops::Range<*mut core::ffi::c_void>,
+) -> Result<(), ()> {
+    match vxlan_inner(vxlan, frame)? {
+        Some(mut inner) => {
+            let ecn = decap(outer.ecn(), inner.ecn())?;
+            if ecn != inner.ecn() {
+                inner.set_ecn(ecn);
+            }
+            Ok(())
+        }
+        None => decap(outer.ecn(), Ecn::NOT_ECT).map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ip::Proto;
+    use crate::Ptr;
+
+    const CODEPOINTS: [Ecn; 4] = [Ecn::NOT_ECT, Ecn::ECT_0, Ecn::ECT_1, Ecn::CE];
+
+    #[test]
+    fn decap_table() {
+        // Rows are the inner header, columns the outer one, as in RFC 6040, figure 4.
+        let expected = [
+            [
+                Ok(Ecn::NOT_ECT),
+                Ok(Ecn::NOT_ECT),
+                Ok(Ecn::NOT_ECT),
+                Err(()),
+            ],
+            [Ok(Ecn::ECT_0), Ok(Ecn::ECT_0), Ok(Ecn::ECT_1), Ok(Ecn::CE)],
+            [Ok(Ecn::ECT_1), Ok(Ecn::ECT_1), Ok(Ecn::ECT_1), Ok(Ecn::CE)],
+            [Ok(Ecn::CE), Ok(Ecn::CE), Ok(Ecn::CE), Ok(Ecn::CE)],
+        ];
+        for (inner, row) in CODEPOINTS.iter().zip(expected) {
+            for (outer, expected) in CODEPOINTS.iter().zip(row) {
+                assert_eq!(
+                    expected,
+                    decap(*outer, *inner),
+                    "inner {inner}, outer {outer}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encap_modes() {
+        for ecn in CODEPOINTS {
+            assert_eq!(ecn, encap(ecn, Mode::Normal));
+            assert_eq!(Ecn::NOT_ECT, encap(ecn, Mode::Compatibility));
+            // Nothing marked on the way is lost by a tunnel in normal mode.
+            assert_eq!(Ok(ecn), decap(encap(ecn, Mode::Normal), ecn));
+        }
+    }
+
+    // Outer IPv4, UDP and VXLAN headers, then an inner Ethernet header and an inner header of the
+    // given EtherType.
+    const INNER_ETH: usize = 20 + 8 + 8;
+    const INNER_IP: usize = INNER_ETH + 14;
+
+    fn frame(ether_type: crate::eth::Type) -> crate::Aligned<{ INNER_IP + 40 }> {
+        let mut buf = crate::Aligned([0u8; INNER_IP + 40]);
+        let mut outer = crate::ipv4::Header {
+            proto: Proto::UDP,
+            ttl: 64,
+            ..Default::default()
+        };
+        outer.update_checksum();
+        crate::write_header(&mut buf.0, 0, &outer).unwrap();
+        let mut vxlan = crate::vxlan::Header::default();
+        vxlan.set_vni(7.into());
+        crate::write_header(&mut buf.0, 28, &vxlan).unwrap();
+        let eth = crate::eth::Header {
+            dst_addr: [2, 0, 0, 0, 0, 1],
+            src_addr: [2, 0, 0, 0, 0, 2],
+            ether_type,
+        };
+        crate::write_header(&mut buf.0, INNER_ETH, &eth).unwrap();
+        match ether_type {
+            crate::eth::Type::IPV4 => {
+                let mut ip = crate::ipv4::Header {
+                    proto: Proto::TCP,
+                    ttl: 64,
+                    ..Default::default()
+                };
+                ip.update_checksum();
+                crate::write_header(&mut buf.0, INNER_IP, &ip).unwrap()
+            }
+            crate::eth::Type::IPV6 => {
+                crate::write_header(&mut buf.0, INNER_IP, &crate::ipv6::Header::default()).unwrap()
+            }
+            _ => {}
+        }
+        buf
+    }
+
+    fn headers(
+        buf: &mut crate::Aligned<{ INNER_IP + 40 }>,
+    ) -> (IpHeader, Ptr<crate::vxlan::Header>, IpHeader) {
+        let r = buf.range(INNER_IP + 40);
+        let outer = IpHeader::V4(Ptr::new(r.start as *mut crate::ipv4::Header));
+        let vxlan = Ptr::new(unsafe { (r.start as *mut u8).add(28) } as *mut crate::vxlan::Header);
+        let mut eth = Ptr::new(unsafe { (r.start as *mut u8).add(INNER_ETH) } as *mut _);
+        let inner = IpHeader::after_eth(&mut eth, r).unwrap();
+        (outer, vxlan, inner)
+    }
+
+    #[test]
+    fn vxlan_ipv4() {
+        let mut buf = frame(crate::eth::Type::IPV4);
+        let r = buf.range(INNER_IP + 40);
+        let (mut outer, mut vxlan, mut inner) = headers(&mut buf);
+        inner.set_ecn(Ecn::ECT_0);
+
+        vxlan_encap(&mut outer, &mut vxlan, r.clone(), Mode::Normal).unwrap();
+        assert_eq!(Ecn::ECT_0, outer.ecn());
+        let IpHeader::V4(ip) = &outer else {
+            unreachable!()
+        };
+        assert_eq!(ip.compute_checksum(), ip.check);
+
+        // A queue on the tunnel path marks the outer header.
+        assert!(outer.mark_ce());
+        vxlan_decap(&outer, &mut vxlan, r.clone()).unwrap();
+        assert_eq!(Ecn::CE, inner.ecn());
+        let IpHeader::V4(ip) = &inner else {
+            unreachable!()
+        };
+        assert_eq!(ip.compute_checksum(), ip.check);
+
+        vxlan_encap(&mut outer, &mut vxlan, r.clone(), Mode::Compatibility).unwrap();
+        assert_eq!(Ecn::NOT_ECT, outer.ecn());
+
+        // A mark on a Not-ECT inner packet can't be passed on.
+        inner.set_ecn(Ecn::NOT_ECT);
+        outer.set_ecn(Ecn::CE);
+        assert!(vxlan_decap(&outer, &mut vxlan, r).is_err());
+    }
+
+    #[test]
+    fn vxlan_ipv6() {
+        let mut buf = frame(crate::eth::Type::IPV6);
+        let r = buf.range(INNER_IP + 40);
+        let (mut outer, mut vxlan, mut inner) = headers(&mut buf);
+        inner.set_ecn(Ecn::ECT_1);
+
+        vxlan_encap(&mut outer, &mut vxlan, r.clone(), Mode::Normal).unwrap();
+        assert_eq!(Ecn::ECT_1, outer.ecn());
+        outer.set_ecn(Ecn::CE);
+        vxlan_decap(&outer, &mut vxlan, r).unwrap();
+        assert_eq!(Ecn::CE, inner.ecn());
+    }
+
+    #[test]
+    fn vxlan_not_ip() {
+        let mut buf = frame(crate::eth::Type::ARP);
+        let r = buf.range(INNER_IP + 40);
+        let mut outer = IpHeader::V4(Ptr::new(r.start as *mut crate::ipv4::Header));
+        let mut vxlan =
+            Ptr::new(unsafe { (r.start as *mut u8).add(28) } as *mut crate::vxlan::Header);
+        outer.set_ecn(Ecn::ECT_0);
+
+        vxlan_encap(&mut outer, &mut vxlan, r.clone(), Mode::Normal).unwrap();
+        assert_eq!(Ecn::NOT_ECT, outer.ecn());
+        assert!(vxlan_decap(&outer, &mut vxlan, r.clone()).is_ok());
+        outer.set_ecn(Ecn::CE);
+        assert!(vxlan_decap(&outer, &mut vxlan, r).is_err());
+    }
+}
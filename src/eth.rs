@@ -1,6 +1,12 @@
 use core::mem;
 
+#[repr(transparent)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Type {
     val: crate::be16,
 }
@@ -17,8 +23,16 @@ impl Type {
     pub const IPV6: Type = Type::new(0x86DD);
 }
 
+/// The broadcast MAC address.
+pub const BROADCAST: [u8; 6] = [0xff; 6];
+
 #[repr(C, packed(2))]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Header {
     /// Destination MAC address.
     pub dst_addr: [u8; 6],
@@ -28,6 +42,8 @@ pub struct Header {
     pub ether_type: Type,
 }
 
+const _: () = assert!(mem::size_of::<Header>() == 14 && mem::align_of::<Header>() == 2);
+
 impl Header {
     pub const LEN: usize = mem::size_of::<Header>();
 
@@ -41,12 +57,15 @@ impl Header {
                 return Err(());
             }
 
-            Ok(super::Ptr::new(pointer as *mut Header))
+            Ok(super::Ptr::new(pointer))
         }
     }
 }
 
 impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Ethernet;
+}
 impl super::AutoNextHeader for Header {
     #[inline(always)]
     fn next(
@@ -56,9 +75,13 @@ impl super::AutoNextHeader for Header {
         use super::NextHeader;
 
         match self.ether_type {
-            Type::ARP => Ok(super::HeaderPtr::Arp(
-                self.next_t::<super::arp::Header>(frame)?,
-            )),
+            Type::ARP => {
+                let mut arp = self.next_t::<super::arp::VarHeader>(frame.clone())?;
+                match arp.is_ethernet_ipv4() {
+                    true => Ok(super::HeaderPtr::Arp(arp.ethernet_ipv4(frame)?)),
+                    false => Ok(super::HeaderPtr::ArpVar(arp)),
+                }
+            }
             Type::IPV4 => Ok(super::HeaderPtr::Ipv4(
                 self.next_t::<super::ipv4::Header>(frame)?,
             )),
@@ -70,17 +93,92 @@ impl super::AutoNextHeader for Header {
     }
 }
 
+/// A MAC address, displayed as six colon-separated hex bytes.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl core::fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let m = &self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            m[0], m[1], m[2], m[3], m[4], m[5]
+        )
+    }
+}
+
+/// Parses six colon-separated hex bytes, such as `02:00:5e:00:53:01`.
+impl core::str::FromStr for MacAddr {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut mac = [0u8; 6];
+        let mut parts = s.split(':');
+        for b in &mut mac {
+            match parts.next() {
+                Some(p) if p.len() == 2 => *b = u8::from_str_radix(p, 16).map_err(|_| ())?,
+                _ => return Err(()),
+            }
+        }
+        match parts.next() {
+            None => Ok(MacAddr(mac)),
+            Some(_) => Err(()),
+        }
+    }
+}
+
+impl core::fmt::Display for Type {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Type::IPV4 => f.write_str("IPv4"),
+            Type::ARP => f.write_str("ARP"),
+            Type::IPV6 => f.write_str("IPv6"),
+            _ => f.write_str("Unknown"),
+        }
+    }
+}
+
+impl Type {
+    /// Returns the EtherType in host byte order.
+    #[inline(always)]
+    pub const fn get(&self) -> u16 {
+        self.val.get()
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let ether_type = self.ether_type;
+        write!(
+            f,
+            "{} > {}, ethertype {} (0x{:04x})",
+            MacAddr(self.src_addr),
+            MacAddr(self.dst_addr),
+            ether_type,
+            ether_type.get()
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
-    #[test]
-    fn size() {
-        assert_eq!(14, Header::LEN);
-    }
+    proptest! {
+        #[test]
+        fn mac_addr(mac: [u8; 6]) {
+            let text = std::format!("{}", MacAddr(mac));
+            prop_assert_eq!(Ok(MacAddr(mac)), text.parse());
+            prop_assert_eq!(Ok(MacAddr(mac)), text.to_uppercase().parse());
+        }
 
-    #[test]
-    fn alignment() {
-        assert_eq!(2, mem::align_of::<Header>());
+        #[test]
+        fn mac_addr_garbage(text in "[0-9a-fA-F:]{0,20}") {
+            if let Ok(mac) = text.parse::<MacAddr>() {
+                prop_assert_eq!(text.to_lowercase(), std::format!("{mac}"));
+            }
+        }
     }
 }
@@ -0,0 +1,480 @@
+//! Fragmentation of IPv4 and IPv6 packets that exceed the MTU of the egress link.
+//!
+//! A fragmenter is created over the whole packet and then writes one fragment at a time into a
+//! buffer supplied by the caller, so nothing is allocated and each fragment can be written
+//! straight into a transmit descriptor.
+
+use crate::{be16, be32};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The packet is truncated or its headers are inconsistent.
+    Malformed,
+    /// The IPv4 packet exceeds the MTU and has DF set. The sender should get an ICMP
+    /// Fragmentation Needed message.
+    DontFragment,
+    /// The MTU leaves no room for payload after the headers that every fragment repeats.
+    MtuTooSmall,
+    /// The output buffer is too small for the next fragment.
+    BufferTooSmall,
+}
+
+/// Longest IPv4 header, with options.
+const MAX_IPV4_HEADER: usize = 60;
+
+/// Splits an IPv4 packet into fragments no larger than the MTU.
+pub struct Ipv4Fragmenter<'a> {
+    header: super::ipv4::Header,
+    /// Options of the first fragment.
+    options: &'a [u8],
+    /// Options copied into later fragments, padded to a multiple of 4 bytes.
+    copied: [u8; MAX_IPV4_HEADER - super::ipv4::Header::LEN],
+    copied_len: usize,
+    payload: &'a [u8],
+    mtu: usize,
+    offset: usize,
+}
+
+impl<'a> Ipv4Fragmenter<'a> {
+    /// Prepares to fragment `packet`, which starts with the IPv4 header, for a link with the
+    /// given MTU. A packet that already fits is produced as a single unmodified fragment.
+    pub fn new(packet: &'a [u8], mtu: usize) -> Result<Self, Error> {
+        let header: super::ipv4::Header =
+            super::read_header(packet, 0).map_err(|_| Error::Malformed)?;
+        let ihl = header.header_len();
+        let len = header.tot_len.get() as usize;
+        if header.version() != 4
+            || ihl < super::ipv4::Header::LEN
+            || len < ihl
+            || len > packet.len()
+        {
+            return Err(Error::Malformed);
+        }
+        if len > mtu && header.dont_fragment() {
+            return Err(Error::DontFragment);
+        }
+
+        let options = &packet[super::ipv4::Header::LEN..ihl];
+        let (copied, copied_len) = copied_options(options)?;
+        // Every fragment but the last must carry a multiple of 8 bytes of payload.
+        if len > mtu && mtu < ihl.max(super::ipv4::Header::LEN + copied_len) + 8 {
+            return Err(Error::MtuTooSmall);
+        }
+        Ok(Self {
+            header,
+            options,
+            copied,
+            copied_len,
+            payload: &packet[ihl..len],
+            mtu,
+            offset: 0,
+        })
+    }
+
+    /// Writes the next fragment into `out` and returns its length, or returns `None` once the
+    /// whole packet has been written.
+    pub fn next_into(&mut self, out: &mut [u8]) -> Option<Result<usize, Error>> {
+        if self.offset == usize::MAX {
+            return None;
+        }
+        let options = match self.offset {
+            0 => self.options,
+            _ => &self.copied[..self.copied_len],
+        };
+        let ihl = super::ipv4::Header::LEN + options.len();
+        let remaining = self.payload.len() - self.offset;
+        let last = ihl + remaining <= self.mtu;
+        let chunk = match last {
+            true => remaining,
+            false => (self.mtu - ihl) & !7,
+        };
+        let len = ihl + chunk;
+        if out.len() < len {
+            return Some(Err(Error::BufferTooSmall));
+        }
+
+        // Offsets are relative to the original packet, which may itself be a fragment.
+        let offset = u16::try_from(self.offset)
+            .ok()
+            .and_then(|offset| self.header.fragment_offset().checked_add(offset));
+        let Some(offset) = offset else {
+            self.offset = usize::MAX;
+            return Some(Err(Error::Malformed));
+        };
+        let mut ip = self.header;
+        ip.set_ihl((ihl / 4) as u8);
+        ip.tot_len = (len as u16).into();
+        ip.set_fragment_offset(offset);
+        ip.set_more_fragments(!last || self.header.more_fragments());
+        ip.check = be16::ZERO;
+        let _ = super::write_header(out, 0, &ip);
+        out[super::ipv4::Header::LEN..ihl].copy_from_slice(options);
+        out[ihl..len].copy_from_slice(&self.payload[self.offset..self.offset + chunk]);
+        ip.check = super::checksum::checksum(&out[..ihl]);
+        let _ = super::write_header(out, 0, &ip);
+
+        self.offset = match last {
+            true => usize::MAX,
+            false => self.offset + chunk,
+        };
+        Some(Ok(len))
+    }
+}
+
+/// Returns the options that must be copied into every fragment (those with the copied flag set,
+/// RFC 791), padded with End of Option List to a multiple of 4 bytes.
+fn copied_options(
+    options: &[u8],
+) -> Result<([u8; MAX_IPV4_HEADER - super::ipv4::Header::LEN], usize), Error> {
+    let mut copied = [0u8; MAX_IPV4_HEADER - super::ipv4::Header::LEN];
+    let mut len = 0;
+    let mut i = 0;
+    while i < options.len() {
+        let kind = options[i];
+        let size = match kind {
+            0 => break,
+            1 => 1,
+            _ => *options.get(i + 1).ok_or(Error::Malformed)? as usize,
+        };
+        if size == 0 || i + size > options.len() {
+            return Err(Error::Malformed);
+        }
+        if kind & 0x80 != 0 {
+            copied[len..len + size].copy_from_slice(&options[i..i + size]);
+            len += size;
+        }
+        i += size;
+    }
+    Ok((copied, (len + 3) & !3))
+}
+
+/// Most extension headers walked in the unfragmentable part of an IPv6 packet.
+const MAX_EXT_HEADERS: usize = 8;
+
+/// Splits an IPv6 packet into fragments no larger than the MTU by inserting Fragment headers.
+pub struct Ipv6Fragmenter<'a> {
+    /// The whole packet, produced as is if it fits.
+    packet: &'a [u8],
+    /// The IPv6 header and the extension headers that are repeated in every fragment.
+    unfragmentable: &'a [u8],
+    /// Offset in `unfragmentable` of the next header field to point at the Fragment header.
+    next_header_at: usize,
+    next_header: super::ip::Proto,
+    identification: be32,
+    payload: &'a [u8],
+    mtu: usize,
+    offset: usize,
+}
+
+impl<'a> Ipv6Fragmenter<'a> {
+    /// Prepares to fragment `packet`, which starts with the IPv6 header, for a link with the
+    /// given MTU. Every fragment carries a Fragment header with `identification`. A packet that
+    /// already fits is produced unchanged, without a Fragment header, rather than as an atomic
+    /// fragment (RFC 8021).
+    pub fn new(packet: &'a [u8], mtu: usize, identification: be32) -> Result<Self, Error> {
+        use super::ipv6::{ExtHeader, FragmentHeader, Header};
+
+        let ip: Header = super::read_header(packet, 0).map_err(|_| Error::Malformed)?;
+        let len = Header::LEN + ip.payload_len.get() as usize;
+        if ip.version() != 6 || len > packet.len() {
+            return Err(Error::Malformed);
+        }
+
+        // The unfragmentable part ends after the Routing header if there is one, otherwise after
+        // the Hop-by-Hop Options header (RFC 8200, section 4.5).
+        let mut next_header_at = 6;
+        let mut offset = Header::LEN;
+        let mut proto = ip.proto;
+        let mut walk = Header::LEN;
+        for _ in 0..MAX_EXT_HEADERS {
+            if !proto.is_ipv6_ext() {
+                break;
+            }
+            let ext: ExtHeader =
+                super::read_header(&packet[..len], walk).map_err(|_| Error::Malformed)?;
+            if proto != super::ip::Proto::DSTOPTS {
+                next_header_at = walk;
+                offset = walk + ext.header_len();
+            }
+            walk += ext.header_len();
+            proto = ext.next_header;
+        }
+        let next_header = match packet.get(next_header_at) {
+            Some(&val) => super::ip::Proto::new(val),
+            None => return Err(Error::Malformed),
+        };
+        if offset > len {
+            return Err(Error::Malformed);
+        }
+        if len > mtu && mtu < offset + FragmentHeader::LEN + 8 {
+            return Err(Error::MtuTooSmall);
+        }
+
+        Ok(Self {
+            packet: &packet[..len],
+            unfragmentable: &packet[..offset],
+            next_header_at,
+            next_header,
+            identification,
+            payload: &packet[offset..len],
+            mtu,
+            offset: 0,
+        })
+    }
+
+    /// Writes the next fragment into `out` and returns its length, or returns `None` once the
+    /// whole packet has been written.
+    pub fn next_into(&mut self, out: &mut [u8]) -> Option<Result<usize, Error>> {
+        use super::ipv6::FragmentHeader;
+
+        if self.offset == usize::MAX {
+            return None;
+        }
+        if self.packet.len() <= self.mtu {
+            let len = self.packet.len();
+            if out.len() < len {
+                return Some(Err(Error::BufferTooSmall));
+            }
+            out[..len].copy_from_slice(self.packet);
+            self.offset = usize::MAX;
+            return Some(Ok(len));
+        }
+        let headers = self.unfragmentable.len() + FragmentHeader::LEN;
+        let remaining = self.payload.len() - self.offset;
+        let last = headers + remaining <= self.mtu;
+        let chunk = match last {
+            true => remaining,
+            false => (self.mtu - headers) & !7,
+        };
+        let len = headers + chunk;
+        if out.len() < len {
+            return Some(Err(Error::BufferTooSmall));
+        }
+
+        out[..self.unfragmentable.len()].copy_from_slice(self.unfragmentable);
+        out[self.next_header_at] = super::ip::Proto::FRAGMENT.into();
+        let payload_len = (len - super::ipv6::Header::LEN) as u16;
+        out[4..6].copy_from_slice(&payload_len.to_be_bytes());
+        let mut frag = FragmentHeader {
+            next_header: self.next_header,
+            identification: self.identification,
+            ..Default::default()
+        };
+        frag.set_offset(self.offset as u16);
+        frag.set_more_fragments(!last);
+        let _ = super::write_header(out, self.unfragmentable.len(), &frag);
+        out[headers..len].copy_from_slice(&self.payload[self.offset..self.offset + chunk]);
+
+        self.offset = match last {
+            true => usize::MAX,
+            false => self.offset + chunk,
+        };
+        Some(Ok(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reassembly::{Ipv4Key, Ipv6Key, Reassembler};
+
+    const PAYLOAD_LEN: usize = 300;
+
+    fn ipv4_packet(options: &[u8], df: bool) -> ([u8; 400], usize) {
+        let ihl = 20 + options.len();
+        let mut ip = crate::ipv4::Header {
+            tot_len: ((ihl + PAYLOAD_LEN) as u16).into(),
+            id: 0x1234.into(),
+            ttl: 64,
+            proto: crate::ip::Proto::UDP,
+            source: [10, 0, 0, 1],
+            destination: [10, 0, 0, 2],
+            ..Default::default()
+        };
+        ip.set_ihl((ihl / 4) as u8);
+        ip.set_dont_fragment(df);
+        let mut buf = [0u8; 400];
+        crate::write_header(&mut buf, 0, &ip).unwrap();
+        buf[20..ihl].copy_from_slice(options);
+        let check = crate::checksum::checksum(&buf[..ihl]);
+        buf[10..12].copy_from_slice(&check.to_be_bytes());
+        for (i, b) in buf[ihl..ihl + PAYLOAD_LEN].iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        (buf, ihl + PAYLOAD_LEN)
+    }
+
+    #[test]
+    fn ipv4_round_trip() {
+        let (packet, len) = ipv4_packet(&[], false);
+        let mut f = Ipv4Fragmenter::new(&packet[..len], 128).unwrap();
+        let mut t = Reassembler::<Ipv4Key, 1, 512>::new(30);
+        let mut out = [0u8; 128];
+        let mut lens = [0; 4];
+        let mut n = 0;
+        while let Some(res) = f.next_into(&mut out) {
+            let flen = res.unwrap();
+            lens[n] = flen;
+            n += 1;
+            assert!(crate::checksum::verify(&out[..20]));
+            if let Some(whole) = t.insert_ipv4(0, &out[..flen]).unwrap() {
+                assert_eq!(&packet[..len], whole);
+            }
+        }
+        assert_eq!([124, 124, 112, 0], lens);
+        assert!(f.next_into(&mut out).is_none());
+    }
+
+    #[test]
+    fn ipv4_fits() {
+        let (packet, len) = ipv4_packet(&[], true);
+        let mut f = Ipv4Fragmenter::new(&packet[..len], 1500).unwrap();
+        let mut out = [0u8; 1500];
+        assert_eq!(Some(Ok(len)), f.next_into(&mut out));
+        assert_eq!(packet[..len], out[..len]);
+        assert_eq!(None, f.next_into(&mut out));
+    }
+
+    #[test]
+    fn ipv4_errors() {
+        let (packet, len) = ipv4_packet(&[], true);
+        assert!(matches!(
+            Ipv4Fragmenter::new(&packet[..len], 128),
+            Err(Error::DontFragment)
+        ));
+        let (packet, len) = ipv4_packet(&[], false);
+        assert!(matches!(
+            Ipv4Fragmenter::new(&packet[..len], 27),
+            Err(Error::MtuTooSmall)
+        ));
+        assert!(matches!(
+            Ipv4Fragmenter::new(&packet[..len - 1], 128),
+            Err(Error::Malformed)
+        ));
+        let mut f = Ipv4Fragmenter::new(&packet[..len], 128).unwrap();
+        assert_eq!(
+            Some(Err(Error::BufferTooSmall)),
+            f.next_into(&mut [0u8; 100])
+        );
+
+        // Refragmenting a fragment whose offset leaves no room for the rest of its payload.
+        let (mut packet, len) = ipv4_packet(&[], false);
+        let mut ip: crate::ipv4::Header = crate::read_header(&packet, 0).unwrap();
+        ip.set_fragment_offset(65528);
+        crate::write_header(&mut packet, 0, &ip).unwrap();
+        let mut f = Ipv4Fragmenter::new(&packet[..len], 128).unwrap();
+        let mut out = [0u8; 128];
+        assert_eq!(Some(Ok(124)), f.next_into(&mut out));
+        assert_eq!(Some(Err(Error::Malformed)), f.next_into(&mut out));
+        assert_eq!(None, f.next_into(&mut out));
+    }
+
+    #[test]
+    fn ipv4_options() {
+        // Record Route (not copied), NOP, and a copied option (type 0x94, Router Alert).
+        let options = [7, 7, 4, 0, 0, 0, 0, 1, 0x94, 4, 0, 0];
+        let (packet, len) = ipv4_packet(&options, false);
+        let mut f = Ipv4Fragmenter::new(&packet[..len], 128).unwrap();
+        let mut out = [0u8; 128];
+
+        let first = f.next_into(&mut out).unwrap().unwrap();
+        assert_eq!(32 + 96, first);
+        assert_eq!(0x48, out[0]);
+        assert_eq!(options, out[20..32]);
+        assert!(crate::checksum::verify(&out[..32]));
+
+        let second = f.next_into(&mut out).unwrap().unwrap();
+        assert_eq!(24 + 104, second);
+        assert_eq!(0x46, out[0]);
+        assert_eq!([0x94, 4, 0, 0], out[20..24]);
+        let ip: crate::ipv4::Header = crate::read_header(&out, 0).unwrap();
+        assert_eq!(96, ip.fragment_offset());
+        assert!(ip.more_fragments());
+        assert!(crate::checksum::verify(&out[..24]));
+    }
+
+    fn ipv6_packet(hbh: bool) -> ([u8; 400], usize) {
+        let ext = if hbh { 8 } else { 0 };
+        let ip = crate::ipv6::Header {
+            payload_len: ((ext + PAYLOAD_LEN) as u16).into(),
+            proto: match hbh {
+                true => crate::ip::Proto::HOPOPTS,
+                false => crate::ip::Proto::UDP,
+            },
+            source: [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            destination: [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            ..Default::default()
+        };
+        let mut buf = [0u8; 400];
+        crate::write_header(&mut buf, 0, &ip).unwrap();
+        buf[40..48].copy_from_slice(&[17, 0, 1, 4, 0, 0, 0, 0]);
+        for (i, b) in buf[40 + ext..40 + ext + PAYLOAD_LEN].iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        (buf, 40 + ext + PAYLOAD_LEN)
+    }
+
+    #[test]
+    fn ipv6_round_trip() {
+        for hbh in [false, true] {
+            let (packet, len) = ipv6_packet(hbh);
+            let mut f = Ipv6Fragmenter::new(&packet[..len], 160, be32::new(99)).unwrap();
+            let mut t = Reassembler::<Ipv6Key, 1, 512>::new(60);
+            let mut out = [0u8; 160];
+            let mut done = false;
+            let mut count = 0;
+            while let Some(res) = f.next_into(&mut out) {
+                let flen = res.unwrap();
+                count += 1;
+                assert!(flen <= 160);
+                let ip: crate::ipv6::Header = crate::read_header(&out, 0).unwrap();
+                assert_eq!(flen - 40, ip.payload_len.get() as usize);
+                if let Some(whole) = t.insert_ipv6(0, &out[..flen]).unwrap() {
+                    assert_eq!(&packet[..len], whole);
+                    done = true;
+                }
+            }
+            assert!(done);
+            assert_eq!(3, count);
+        }
+    }
+
+    #[test]
+    fn ipv6_fits() {
+        let (packet, len) = ipv6_packet(true);
+        let mut f = Ipv6Fragmenter::new(&packet[..len], 1280, be32::new(1)).unwrap();
+        let mut out = [0u8; 1280];
+        assert_eq!(
+            Some(Err(Error::BufferTooSmall)),
+            f.next_into(&mut out[..len - 1])
+        );
+        assert_eq!(Some(Ok(len)), f.next_into(&mut out));
+        assert_eq!(packet[..len], out[..len]);
+        assert_eq!(None, f.next_into(&mut out));
+
+        // An MTU too small to fragment for doesn't matter to a packet that fits it exactly.
+        let mut f = Ipv6Fragmenter::new(&packet[..len], len, be32::new(1)).unwrap();
+        assert_eq!(Some(Ok(len)), f.next_into(&mut out));
+        let ip = crate::ipv6::Header {
+            proto: crate::ip::Proto::NONE,
+            ..Default::default()
+        };
+        let mut packet = [0u8; 40];
+        crate::write_header(&mut packet, 0, &ip).unwrap();
+        let mut f = Ipv6Fragmenter::new(&packet, 40, be32::new(1)).unwrap();
+        assert_eq!(Some(Ok(40)), f.next_into(&mut out));
+    }
+
+    #[test]
+    fn ipv6_errors() {
+        let (packet, len) = ipv6_packet(true);
+        assert!(matches!(
+            Ipv6Fragmenter::new(&packet[..len], 63, be32::new(1)),
+            Err(Error::MtuTooSmall)
+        ));
+        assert!(matches!(
+            Ipv6Fragmenter::new(&packet[..len - 1], 1280, be32::new(1)),
+            Err(Error::Malformed)
+        ));
+    }
+}
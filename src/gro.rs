@@ -0,0 +1,617 @@
+//! Generic receive offload: coalescing of consecutive TCP segments.
+//!
+//! [`Gro`] holds up to `FLOWS` TCP flows, each in a buffer supplied by the caller. In-order
+//! segments of a held flow are appended to its buffer, and the coalesced packet is handed to a
+//! flush callback when the flow can't grow any further. The flush rules follow Linux
+//! (`tcp_gro_receive`): a segment is only merged when everything but the sequence number, the
+//! payload and the FIN/PSH flags matches, including the TCP options, so that a change of
+//! timestamp ends the coalesced packet.
+
+use crate::{be16, be32};
+
+/// Largest IP packet a coalesced packet may grow to.
+pub const MAX_IP_LEN: usize = 65535;
+
+/// What became of a packet passed to [`Gro::receive`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The packet was copied into a flow buffer and will be delivered as part of a coalesced
+    /// packet.
+    Held,
+    /// The packet can't be coalesced and must be delivered as is, after anything that was
+    /// flushed during the call.
+    Pass,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FlowKey {
+    ipv4: bool,
+    source: [u8; 16],
+    destination: [u8; 16],
+    ports: [be16; 2],
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Flow {
+    key: FlowKey,
+    l3: super::gso::L3,
+    /// Offset of the TCP payload.
+    payload: usize,
+    /// Bytes of the buffer in use.
+    len: usize,
+    /// Payload length of the first segment, which no later segment may exceed.
+    mss: usize,
+    next_seq: u32,
+    ack_seq: be32,
+    /// IPv4 identification of the last segment merged.
+    id: u16,
+    segments: usize,
+    last_used: u64,
+}
+
+/// A segment parsed far enough to decide whether it can be coalesced.
+struct Segment<'p> {
+    packet: &'p [u8],
+    key: FlowKey,
+    l3: super::gso::L3,
+    tcp: super::tcp::Header,
+    payload: usize,
+}
+
+impl<'p> Segment<'p> {
+    fn parse(packet: &'p [u8], l3_offset: usize) -> Option<Self> {
+        let l3 = super::gso::L3::parse(packet, l3_offset, super::ip::Proto::TCP).ok()?;
+        let packet = &packet[..l3.end];
+        let tcp: super::tcp::Header = super::read_header(packet, l3.l4()).ok()?;
+        let payload = l3.l4() + tcp.header_len();
+        if tcp.header_len() < super::tcp::Header::LEN || payload > l3.end {
+            return None;
+        }
+        let mut key = FlowKey {
+            ipv4: l3.ipv4,
+            source: [0; 16],
+            destination: [0; 16],
+            ports: [tcp.source, tcp.dest],
+        };
+        let (addr, size) = match l3.ipv4 {
+            true => (l3_offset + 12, 4),
+            false => (l3_offset + 8, 16),
+        };
+        key.source[..size].copy_from_slice(&packet[addr..addr + size]);
+        key.destination[..size].copy_from_slice(&packet[addr + size..addr + 2 * size]);
+        Some(Self {
+            packet,
+            key,
+            l3,
+            tcp,
+            payload,
+        })
+    }
+
+    fn payload_len(&self) -> usize {
+        self.l3.end - self.payload
+    }
+
+    /// Returns true if this segment may start or join a coalesced packet. Segments with control
+    /// flags other than ACK, PSH and FIN, IPv4 options or no payload are delivered on their own.
+    fn is_candidate(&self) -> bool {
+        use super::tcp::Flags;
+
+        let others = !(Flags::ACK | Flags::PSH | Flags::FIN);
+        let ipv4_options = self.l3.ipv4 && self.l3.len != super::ipv4::Header::LEN;
+        self.tcp.flags() & others == Flags::default()
+            && self.tcp.flags().contains(Flags::ACK)
+            && !ipv4_options
+            && self.payload_len() > 0
+    }
+
+    /// Returns true if the TCP checksum of this segment is correct.
+    fn checksum_ok(&self) -> bool {
+        let l4 = self.l3.l4();
+        let len = self.l3.end - l4;
+        let (src, dst) = (self.key.source, self.key.destination);
+        let proto = super::ip::Proto::TCP.into();
+        let pseudo = match self.l3.ipv4 {
+            true => {
+                let src = [src[0], src[1], src[2], src[3]];
+                let dst = [dst[0], dst[1], dst[2], dst[3]];
+                super::checksum::pseudo_ipv4(src, dst, proto, len as u16)
+            }
+            false => super::checksum::pseudo_ipv6(src, dst, proto, len as u32),
+        };
+        super::checksum::fold(super::checksum::sum(&self.packet[l4..], pseudo)) == 0xffff
+    }
+
+    /// Returns true if this segment ends a coalesced packet once merged.
+    fn is_final(&self, mss: usize) -> bool {
+        use super::tcp::Flags;
+
+        self.tcp.flags() & (Flags::PSH | Flags::FIN) != Flags::default() || self.payload_len() < mss
+    }
+}
+
+/// Coalesces in-order TCP segments of up to `FLOWS` flows into caller-supplied buffers.
+pub struct Gro<'a, const FLOWS: usize> {
+    buffers: [&'a mut [u8]; FLOWS],
+    flows: [Option<Flow>; FLOWS],
+    tick: u64,
+}
+
+impl<'a, const FLOWS: usize> Gro<'a, FLOWS> {
+    /// Returns an engine that coalesces each flow into one of `buffers`. A coalesced packet
+    /// grows up to the size of its buffer, but never beyond [`MAX_IP_LEN`] bytes of IP packet.
+    pub fn new(buffers: [&'a mut [u8]; FLOWS]) -> Self {
+        Self {
+            buffers,
+            flows: [None; FLOWS],
+            tick: 0,
+        }
+    }
+
+    /// Returns the number of flows being held.
+    pub fn held(&self) -> usize {
+        self.flows.iter().filter(|f| f.is_some()).count()
+    }
+
+    /// Offers `packet`, whose IP header starts at `l3_offset`, for coalescing. Held packets that
+    /// can no longer grow are passed to `flush`, always before the packet that caused it is
+    /// delivered, so per-flow order is preserved.
+    ///
+    /// The TCP checksum of the packet is verified first, because the coalesced packet gets a new
+    /// one. A packet with a bad checksum is passed on unmerged for the stack to drop.
+    pub fn receive<F: FnMut(&[u8])>(
+        &mut self,
+        packet: &[u8],
+        l3_offset: usize,
+        flush: F,
+    ) -> Verdict {
+        self.offer(packet, l3_offset, false, flush)
+    }
+
+    /// Same as [`Gro::receive`] for a packet whose TCP checksum was already verified, such as
+    /// one that a virtio-net device marked with [`super::virtio_net::Flags::DATA_VALID`].
+    pub fn receive_validated<F: FnMut(&[u8])>(
+        &mut self,
+        packet: &[u8],
+        l3_offset: usize,
+        flush: F,
+    ) -> Verdict {
+        self.offer(packet, l3_offset, true, flush)
+    }
+
+    fn offer<F: FnMut(&[u8])>(
+        &mut self,
+        packet: &[u8],
+        l3_offset: usize,
+        validated: bool,
+        mut flush: F,
+    ) -> Verdict {
+        let seg = match Segment::parse(packet, l3_offset) {
+            Some(seg) => seg,
+            None => return Verdict::Pass,
+        };
+        self.tick += 1;
+        let held = self
+            .flows
+            .iter()
+            .position(|f| matches!(f, Some(f) if f.key == seg.key));
+
+        if !seg.is_candidate() || !(validated || seg.checksum_ok()) {
+            if let Some(i) = held {
+                self.flush_flow(i, &mut flush);
+            }
+            return Verdict::Pass;
+        }
+
+        if let Some(i) = held {
+            if self.can_merge(i, &seg) {
+                self.merge(i, &seg);
+                if seg.is_final(self.flows[i].map_or(0, |f| f.mss)) {
+                    self.flush_flow(i, &mut flush);
+                }
+                return Verdict::Held;
+            }
+            self.flush_flow(i, &mut flush);
+        }
+
+        // A segment that already ends a burst gains nothing from being held.
+        if seg.is_final(seg.payload_len()) {
+            return Verdict::Pass;
+        }
+        let i = match self.flows.iter().position(|f| f.is_none()) {
+            Some(i) => i,
+            None => {
+                let i = self
+                    .flows
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, f)| f.map_or(0, |f| f.last_used))
+                    .map_or(0, |(i, _)| i);
+                self.flush_flow(i, &mut flush);
+                i
+            }
+        };
+        let len = seg.l3.end;
+        if len > self.buffers[i].len() {
+            return Verdict::Pass;
+        }
+        self.buffers[i][..len].copy_from_slice(seg.packet);
+        self.flows[i] = Some(Flow {
+            key: seg.key,
+            l3: seg.l3,
+            payload: seg.payload,
+            len,
+            mss: seg.payload_len(),
+            next_seq: seg.tcp.seq.get().wrapping_add(seg.payload_len() as u32),
+            ack_seq: seg.tcp.ack_seq,
+            id: ipv4_id(seg.packet, seg.l3.offset),
+            segments: 1,
+            last_used: self.tick,
+        });
+        Verdict::Held
+    }
+
+    /// Flushes every held flow, e.g. at the end of a batch of received packets.
+    pub fn flush_all<F: FnMut(&[u8])>(&mut self, mut flush: F) {
+        for i in 0..FLOWS {
+            self.flush_flow(i, &mut flush);
+        }
+    }
+
+    fn can_merge(&self, i: usize, seg: &Segment<'_>) -> bool {
+        use super::tcp::Flags;
+
+        let (flow, buf) = match &self.flows[i] {
+            Some(flow) => (flow, &self.buffers[i][..]),
+            None => return false,
+        };
+        let len = seg.payload_len();
+        if seg.tcp.seq.get() != flow.next_seq
+            || { seg.tcp.ack_seq } != flow.ack_seq
+            || len > flow.mss
+            || flow.len + len > buf.len()
+            || flow.len - flow.l3.offset + len > MAX_IP_LEN
+        {
+            return false;
+        }
+        let held: super::tcp::Header = match super::read_header(buf, flow.l3.l4()) {
+            Ok(tcp) => tcp,
+            Err(_) => return false,
+        };
+        let fin_psh = Flags::FIN | Flags::PSH;
+        if held.flags() & !fin_psh != seg.tcp.flags() & !fin_psh {
+            return false;
+        }
+        // Options, including timestamps, must be identical.
+        let options = flow.l3.l4() + super::tcp::Header::LEN..flow.payload;
+        if seg.payload != flow.payload || buf[options.clone()] != seg.packet[options] {
+            return false;
+        }
+        ip_matches(flow, buf, seg)
+    }
+
+    fn merge(&mut self, i: usize, seg: &Segment<'_>) {
+        let (flow, buf) = match &mut self.flows[i] {
+            Some(flow) => (flow, &mut *self.buffers[i]),
+            None => return,
+        };
+        let len = seg.payload_len();
+        buf[flow.len..flow.len + len].copy_from_slice(&seg.packet[seg.payload..]);
+        flow.len += len;
+        flow.next_seq = flow.next_seq.wrapping_add(len as u32);
+        flow.id = ipv4_id(seg.packet, seg.l3.offset);
+        flow.segments += 1;
+        flow.last_used = self.tick;
+
+        // The coalesced packet carries the latest window and the FIN/PSH of its last segment.
+        if let Ok(mut tcp) = super::read_header::<super::tcp::Header>(buf, flow.l3.l4()) {
+            tcp.window = seg.tcp.window;
+            tcp.set_flags(tcp.flags() | seg.tcp.flags());
+            let _ = super::write_header(buf, flow.l3.l4(), &tcp);
+        }
+    }
+
+    fn flush_flow<F: FnMut(&[u8])>(&mut self, i: usize, flush: &mut F) {
+        let flow = match self.flows[i].take() {
+            Some(flow) => flow,
+            None => return,
+        };
+        let buf = &mut *self.buffers[i];
+        if flow.segments > 1 {
+            let l4 = flow.l3.l4();
+            let pseudo = flow
+                .l3
+                .update(buf, 0, flow.len, super::ip::Proto::TCP.into());
+            if let Ok(mut tcp) = super::read_header::<super::tcp::Header>(buf, l4) {
+                tcp.check = be16::ZERO;
+                let _ = super::write_header(buf, l4, &tcp);
+                tcp.check =
+                    super::checksum::finish(super::checksum::sum(&buf[l4..flow.len], pseudo));
+                let _ = super::write_header(buf, l4, &tcp);
+            }
+        }
+        flush(&buf[..flow.len]);
+    }
+}
+
+/// Returns the IPv4 identification of a packet, or 0 for IPv6.
+fn ipv4_id(packet: &[u8], l3_offset: usize) -> u16 {
+    match super::read_header::<super::ipv4::Header>(packet, l3_offset) {
+        Ok(ip) if ip.version() == 4 => ip.id.get(),
+        _ => 0,
+    }
+}
+
+/// Returns true if the IP header of `seg` matches the held one in every field that must not
+/// change within a coalesced packet.
+fn ip_matches(flow: &Flow, buf: &[u8], seg: &Segment<'_>) -> bool {
+    let offset = flow.l3.offset;
+    if flow.l3.ipv4 {
+        let (held, new) = match (
+            super::read_header::<super::ipv4::Header>(buf, offset),
+            super::read_header::<super::ipv4::Header>(seg.packet, offset),
+        ) {
+            (Ok(held), Ok(new)) => (held, new),
+            _ => return false,
+        };
+        // Without DF the identification must increase by one per segment.
+        let id_ok = new.dont_fragment() || new.id.get() == flow.id.wrapping_add(1);
+        held.tos == new.tos
+            && held.ttl == new.ttl
+            && held.dont_fragment() == new.dont_fragment()
+            && id_ok
+    } else {
+        // Version, traffic class, flow label, and then the hop limit.
+        buf[offset..offset + 4] == seg.packet[offset..offset + 4]
+            && buf[offset + 7] == seg.packet[offset + 7]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::Flags;
+
+    const ETH: usize = crate::eth::Header::LEN;
+
+    /// Builds a TCP segment with 12 bytes of timestamp option carrying `tsval`.
+    fn segment(v6: bool, seq: u32, len: usize, flags: Flags, tsval: u8) -> ([u8; 512], usize) {
+        let mut buf = [0u8; 512];
+        let l3_len = if v6 { 40 } else { 20 };
+        let l4 = ETH + l3_len;
+        let tcp_len = 32;
+        let end = l4 + tcp_len + len;
+        if v6 {
+            let ip = crate::ipv6::Header {
+                payload_len: ((tcp_len + len) as u16).into(),
+                proto: crate::ip::Proto::TCP,
+                source: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+                destination: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+                ..Default::default()
+            };
+            crate::write_header(&mut buf, ETH, &ip).unwrap();
+        } else {
+            let mut ip = crate::ipv4::Header {
+                tot_len: ((end - ETH) as u16).into(),
+                ttl: 64,
+                proto: crate::ip::Proto::TCP,
+                source: [10, 0, 0, 1],
+                destination: [10, 0, 0, 2],
+                ..Default::default()
+            };
+            ip.set_dont_fragment(true);
+            ip.update_checksum();
+            crate::write_header(&mut buf, ETH, &ip).unwrap();
+        }
+        let mut tcp = crate::tcp::Header {
+            source: 40000.into(),
+            dest: 80.into(),
+            seq: seq.into(),
+            ack_seq: 1.into(),
+            window: 100.into(),
+            ..Default::default()
+        };
+        tcp.set_data_offset(8);
+        tcp.set_flags(flags);
+        crate::write_header(&mut buf, l4, &tcp).unwrap();
+        buf[l4 + 20..l4 + 32].copy_from_slice(&[1, 1, 8, 10, 0, 0, 0, tsval, 0, 0, 0, 0]);
+        for i in 0..len {
+            buf[l4 + tcp_len + i] = (seq as usize + i) as u8;
+        }
+        let pseudo = if v6 {
+            crate::checksum::pseudo_ipv6(
+                buf[ETH + 8..ETH + 24].try_into().unwrap(),
+                buf[ETH + 24..ETH + 40].try_into().unwrap(),
+                6,
+                (end - l4) as u32,
+            )
+        } else {
+            crate::checksum::pseudo_ipv4([10, 0, 0, 1], [10, 0, 0, 2], 6, (end - l4) as u16)
+        };
+        tcp.check = crate::checksum::finish(crate::checksum::sum(&buf[l4..end], pseudo));
+        crate::write_header(&mut buf, l4, &tcp).unwrap();
+        (buf, end)
+    }
+
+    struct Out {
+        packets: [[u8; 512]; 4],
+        lens: [usize; 4],
+        count: usize,
+    }
+
+    impl Out {
+        fn new() -> Self {
+            Self {
+                packets: [[0; 512]; 4],
+                lens: [0; 4],
+                count: 0,
+            }
+        }
+
+        fn push(&mut self, p: &[u8]) {
+            self.packets[self.count][..p.len()].copy_from_slice(p);
+            self.lens[self.count] = p.len();
+            self.count += 1;
+        }
+
+        fn get(&self, i: usize) -> &[u8] {
+            &self.packets[i][..self.lens[i]]
+        }
+    }
+
+    fn verify_tcp(packet: &[u8]) -> crate::tcp::Header {
+        let v6 = packet[ETH] >> 4 == 6;
+        let l4 = ETH + if v6 { 40 } else { 20 };
+        let pseudo = if v6 {
+            let ip: crate::ipv6::Header = crate::read_header(packet, ETH).unwrap();
+            assert_eq!(packet.len() - l4, ip.payload_len.get() as usize);
+            crate::checksum::pseudo_ipv6(ip.source, ip.destination, 6, (packet.len() - l4) as u32)
+        } else {
+            let ip: crate::ipv4::Header = crate::read_header(packet, ETH).unwrap();
+            assert_eq!(packet.len() - ETH, ip.tot_len.get() as usize);
+            assert!(crate::checksum::verify(&packet[ETH..l4]));
+            crate::checksum::pseudo_ipv4(ip.source, ip.destination, 6, (packet.len() - l4) as u16)
+        };
+        let sum = crate::checksum::sum(&packet[l4..], pseudo);
+        assert_eq!(0xffff, crate::checksum::fold(sum));
+        crate::read_header(packet, l4).unwrap()
+    }
+
+    #[test]
+    fn coalesce() {
+        for v6 in [false, true] {
+            let mut a = [0u8; 512];
+            let mut b = [0u8; 512];
+            let mut gro = Gro::new([&mut a[..], &mut b[..]]);
+            let mut out = Out::new();
+            let ack = Flags::ACK;
+            for (i, flags) in [ack, ack, ack | Flags::PSH].into_iter().enumerate() {
+                let (p, len) = segment(v6, 1000 + 40 * i as u32, 40, flags, 1);
+                assert_eq!(Verdict::Held, gro.receive(&p[..len], ETH, |p| out.push(p)));
+            }
+            assert_eq!(0, gro.held());
+            assert_eq!(1, out.count);
+            let merged = out.get(0);
+            let tcp = verify_tcp(merged);
+            assert_eq!(1000, tcp.seq.get());
+            assert_eq!(Flags::ACK | Flags::PSH, tcp.flags());
+            let payload = &merged[merged.len() - 120..];
+            for (i, b) in payload.iter().enumerate() {
+                assert_eq!((1000 + i) as u8, *b);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_with_gso() {
+        let (first, len) = segment(false, 5000, 200, Flags::ACK | Flags::PSH, 3);
+        let mut seg = crate::gso::TcpSegmenter::new(&first[..len], ETH, 50).unwrap();
+        let mut a = [0u8; 512];
+        let mut gro = Gro::new([&mut a[..]]);
+        let mut out = Out::new();
+        let mut buf = [0u8; 256];
+        while let Some(n) = seg.next_into(&mut buf) {
+            let n = n.unwrap();
+            assert_eq!(Verdict::Held, gro.receive(&buf[..n], ETH, |p| out.push(p)));
+        }
+        assert_eq!(1, out.count);
+        verify_tcp(out.get(0));
+        assert_eq!(first[..len], *out.get(0));
+    }
+
+    #[test]
+    fn flush_rules() {
+        let mut a = [0u8; 512];
+        let mut gro = Gro::new([&mut a[..]]);
+        let mut out = Out::new();
+        let ack = Flags::ACK;
+
+        let (p, len) = segment(false, 0, 40, ack, 1);
+        assert_eq!(Verdict::Held, gro.receive(&p[..len], ETH, |p| out.push(p)));
+        // A new timestamp starts a new packet.
+        let (p, len) = segment(false, 40, 40, ack, 2);
+        assert_eq!(Verdict::Held, gro.receive(&p[..len], ETH, |p| out.push(p)));
+        assert_eq!(1, out.count);
+        // So does a gap in the sequence.
+        let (p, len) = segment(false, 120, 40, ack, 2);
+        assert_eq!(Verdict::Held, gro.receive(&p[..len], ETH, |p| out.push(p)));
+        assert_eq!(2, out.count);
+        // A SYN, RST or URG is delivered on its own, after the held packet.
+        let (p, len) = segment(false, 160, 40, ack | Flags::URG, 2);
+        assert_eq!(Verdict::Pass, gro.receive(&p[..len], ETH, |p| out.push(p)));
+        assert_eq!(3, out.count);
+        assert_eq!(0, gro.held());
+        // A segment larger than the first one can't be appended.
+        let (p, len) = segment(false, 200, 40, ack, 2);
+        gro.receive(&p[..len], ETH, |p| out.push(p));
+        let (p, len) = segment(false, 240, 48, ack, 2);
+        assert_eq!(Verdict::Held, gro.receive(&p[..len], ETH, |p| out.push(p)));
+        assert_eq!(4, out.count);
+        for i in 0..4 {
+            verify_tcp(out.get(i));
+        }
+    }
+
+    #[test]
+    fn buffer_limit() {
+        let mut a = [0u8; 160];
+        let mut gro = Gro::new([&mut a[..]]);
+        let mut count = 0;
+        for i in 0..3 {
+            let (p, len) = segment(false, 40 * i, 40, Flags::ACK, 1);
+            gro.receive(&p[..len], ETH, |_| count += 1);
+        }
+        // Two segments fit in 160 bytes, the third starts over.
+        assert_eq!(1, count);
+        assert_eq!(1, gro.held());
+        gro.flush_all(|_| count += 1);
+        assert_eq!(2, count);
+        assert_eq!(0, gro.held());
+    }
+
+    #[test]
+    fn flows() {
+        let mut a = [0u8; 512];
+        let mut b = [0u8; 512];
+        let mut gro = Gro::new([&mut a[..], &mut b[..]]);
+        let mut out = Out::new();
+        let (p4, l4) = segment(false, 0, 40, Flags::ACK, 1);
+        let (p6, l6) = segment(true, 0, 40, Flags::ACK, 1);
+        assert_eq!(Verdict::Held, gro.receive(&p4[..l4], ETH, |p| out.push(p)));
+        assert_eq!(Verdict::Held, gro.receive(&p6[..l6], ETH, |p| out.push(p)));
+        assert_eq!(2, gro.held());
+        let (p4, l4) = segment(false, 40, 40, Flags::ACK | Flags::FIN, 1);
+        assert_eq!(Verdict::Held, gro.receive(&p4[..l4], ETH, |p| out.push(p)));
+        assert_eq!(1, out.count);
+        assert_eq!(1, gro.held());
+        assert_eq!(Verdict::Pass, gro.receive(&p4[..10], ETH, |p| out.push(p)));
+    }
+
+    #[test]
+    fn bad_checksum() {
+        for v6 in [false, true] {
+            let mut a = [0u8; 512];
+            let mut gro = Gro::new([&mut a[..]]);
+            let mut out = Out::new();
+            let (p, len) = segment(v6, 0, 40, Flags::ACK, 1);
+            assert_eq!(Verdict::Held, gro.receive(&p[..len], ETH, |p| out.push(p)));
+            // A corrupted segment flushes the held packet and is passed on unmerged.
+            let (mut p, len) = segment(v6, 40, 40, Flags::ACK, 1);
+            p[len - 1] ^= 0x10;
+            assert_eq!(Verdict::Pass, gro.receive(&p[..len], ETH, |p| out.push(p)));
+            assert_eq!(1, out.count);
+            assert_eq!(0, gro.held());
+            verify_tcp(out.get(0));
+            // Unless the caller vouches for it.
+            let (p, len) = segment(v6, 80, 40, Flags::ACK, 1);
+            gro.receive(&p[..len], ETH, |p| out.push(p));
+            let (mut p, len) = segment(v6, 120, 40, Flags::ACK, 1);
+            p[len - 1] ^= 0x10;
+            assert_eq!(
+                Verdict::Held,
+                gro.receive_validated(&p[..len], ETH, |p| out.push(p))
+            );
+            assert_eq!(1, gro.held());
+        }
+    }
+}
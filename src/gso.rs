@@ -0,0 +1,507 @@
+//! Software segmentation offload.
+//!
+//! Splits a large TCP or UDP "super-packet", as handed over by a TAP device or a socket with
+//! segmentation offload enabled, into packets that fit the wire. Like
+//! [`fragmentation`](super::fragmentation), each segment is written into a buffer supplied by the
+//! caller.
+
+use crate::be16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The packet is truncated or its headers are inconsistent.
+    Malformed,
+    /// The packet isn't IPv4 or IPv6 directly followed by the expected transport protocol.
+    Unsupported,
+    /// The segment size is zero.
+    InvalidSegmentSize,
+    /// The output buffer is too small for the next segment.
+    BufferTooSmall,
+    /// The packet can't be fragmented for UDP fragmentation offload.
+    Fragmentation(super::fragmentation::Error),
+}
+
+/// The network layer of a super-packet.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct L3 {
+    /// Offset of the IP header in the packet.
+    pub offset: usize,
+    /// Length of the IP header including options.
+    pub len: usize,
+    pub ipv4: bool,
+    /// Offset of the end of the IP packet.
+    pub end: usize,
+}
+
+impl L3 {
+    pub fn parse(packet: &[u8], offset: usize, proto: super::ip::Proto) -> Result<Self, Error> {
+        let version = packet.get(offset).ok_or(Error::Malformed)? >> 4;
+        let l3 = match version {
+            4 => {
+                let ip: super::ipv4::Header =
+                    super::read_header(packet, offset).map_err(|_| Error::Malformed)?;
+                if ip.proto != proto || ip.is_fragment() {
+                    return Err(Error::Unsupported);
+                }
+                L3 {
+                    offset,
+                    len: ip.header_len(),
+                    ipv4: true,
+                    end: offset + ip.tot_len.get() as usize,
+                }
+            }
+            6 => {
+                let ip: super::ipv6::Header =
+                    super::read_header(packet, offset).map_err(|_| Error::Malformed)?;
+                if ip.proto != proto {
+                    return Err(Error::Unsupported);
+                }
+                L3 {
+                    offset,
+                    len: super::ipv6::Header::LEN,
+                    ipv4: false,
+                    end: offset + super::ipv6::Header::LEN + ip.payload_len.get() as usize,
+                }
+            }
+            _ => return Err(Error::Unsupported),
+        };
+        if l3.len < super::ipv4::Header::LEN || l3.end > packet.len() || l3.end < l3.l4() {
+            return Err(Error::Malformed);
+        }
+        Ok(l3)
+    }
+
+    /// Offset of the transport header.
+    pub fn l4(&self) -> usize {
+        self.offset + self.len
+    }
+
+    /// Sets the length, identification and checksum of the IP header of the `index`th segment,
+    /// which ends at `end`, and returns the sum of its pseudo header.
+    pub fn update(&self, out: &mut [u8], index: usize, end: usize, proto: u8) -> u32 {
+        let l4_len = end - self.l4();
+        if self.ipv4 {
+            let mut ip: super::ipv4::Header = match super::read_header(out, self.offset) {
+                Ok(ip) => ip,
+                Err(_) => return 0,
+            };
+            ip.tot_len = ((end - self.offset) as u16).into();
+            ip.id = ip.id.wrapping_add(index as u16);
+            ip.check = be16::ZERO;
+            let _ = super::write_header(out, self.offset, &ip);
+            ip.check = super::checksum::checksum(&out[self.offset..self.l4()]);
+            let _ = super::write_header(out, self.offset, &ip);
+            super::checksum::pseudo_ipv4(ip.source, ip.destination, proto, l4_len as u16)
+        } else {
+            let mut ip: super::ipv6::Header = match super::read_header(out, self.offset) {
+                Ok(ip) => ip,
+                Err(_) => return 0,
+            };
+            ip.payload_len = (l4_len as u16).into();
+            let _ = super::write_header(out, self.offset, &ip);
+            super::checksum::pseudo_ipv6(ip.source, ip.destination, proto, l4_len as u32)
+        }
+    }
+}
+
+/// Splits a TCP super-packet into segments of at most `mss` bytes of payload.
+pub struct TcpSegmenter<'a> {
+    /// Everything up to the end of the TCP header, repeated in every segment.
+    headers: &'a [u8],
+    l3: L3,
+    tcp: super::tcp::Header,
+    payload: &'a [u8],
+    mss: usize,
+    offset: usize,
+    index: usize,
+}
+
+impl<'a> TcpSegmenter<'a> {
+    /// Prepares to segment `packet`, whose IP header starts at `l3_offset`. Anything before it,
+    /// such as an Ethernet header, is copied into every segment as is.
+    pub fn new(packet: &'a [u8], l3_offset: usize, mss: usize) -> Result<Self, Error> {
+        if mss == 0 {
+            return Err(Error::InvalidSegmentSize);
+        }
+        let l3 = L3::parse(packet, l3_offset, super::ip::Proto::TCP)?;
+        let tcp: super::tcp::Header =
+            super::read_header(&packet[..l3.end], l3.l4()).map_err(|_| Error::Malformed)?;
+        let headers_end = l3.l4() + tcp.header_len();
+        if tcp.header_len() < super::tcp::Header::LEN || headers_end > l3.end {
+            return Err(Error::Malformed);
+        }
+        Ok(Self {
+            headers: &packet[..headers_end],
+            l3,
+            tcp,
+            payload: &packet[headers_end..l3.end],
+            mss,
+            offset: 0,
+            index: 0,
+        })
+    }
+
+    /// Writes the next segment into `out` and returns its length, or returns `None` once the
+    /// whole payload has been written.
+    pub fn next_into(&mut self, out: &mut [u8]) -> Option<Result<usize, Error>> {
+        use super::tcp::Flags;
+
+        if self.offset == usize::MAX {
+            return None;
+        }
+        let chunk = self.mss.min(self.payload.len() - self.offset);
+        let last = self.offset + chunk == self.payload.len();
+        let headers = self.headers.len();
+        let len = headers + chunk;
+        if out.len() < len {
+            return Some(Err(Error::BufferTooSmall));
+        }
+        out[..headers].copy_from_slice(self.headers);
+        out[headers..len].copy_from_slice(&self.payload[self.offset..self.offset + chunk]);
+
+        // FIN and PSH belong to the end of the data, CWR to the first segment sent.
+        let mut tcp = self.tcp;
+        let mut flags = tcp.flags();
+        if !last {
+            flags = flags & !(Flags::FIN | Flags::PSH);
+        }
+        if self.index > 0 {
+            flags = flags & !Flags::CWR;
+        }
+        tcp.set_flags(flags);
+        tcp.seq = tcp.seq.wrapping_add(self.offset as u32);
+        tcp.check = be16::ZERO;
+        let l4 = self.l3.l4();
+        let _ = super::write_header(out, l4, &tcp);
+        let pseudo = self
+            .l3
+            .update(out, self.index, len, super::ip::Proto::TCP.into());
+        tcp.check = super::checksum::finish(super::checksum::sum(&out[l4..len], pseudo));
+        let _ = super::write_header(out, l4, &tcp);
+
+        self.index += 1;
+        self.offset = match last {
+            true => usize::MAX,
+            false => self.offset + chunk,
+        };
+        Some(Ok(len))
+    }
+}
+
+/// Splits a UDP super-packet into datagrams of at most `gso_size` bytes of payload, each with its
+/// own UDP header (UDP segmentation offload, as with `UDP_SEGMENT`).
+pub struct UdpSegmenter<'a> {
+    headers: &'a [u8],
+    l3: L3,
+    udp: super::udp::Header,
+    payload: &'a [u8],
+    gso_size: usize,
+    offset: usize,
+    index: usize,
+}
+
+impl<'a> UdpSegmenter<'a> {
+    /// Prepares to segment `packet`, whose IP header starts at `l3_offset`. Anything before it is
+    /// copied into every segment as is.
+    pub fn new(packet: &'a [u8], l3_offset: usize, gso_size: usize) -> Result<Self, Error> {
+        if gso_size == 0 {
+            return Err(Error::InvalidSegmentSize);
+        }
+        let l3 = L3::parse(packet, l3_offset, super::ip::Proto::UDP)?;
+        let udp: super::udp::Header =
+            super::read_header(&packet[..l3.end], l3.l4()).map_err(|_| Error::Malformed)?;
+        let headers_end = l3.l4() + super::udp::Header::LEN;
+        Ok(Self {
+            headers: &packet[..headers_end],
+            l3,
+            udp,
+            payload: &packet[headers_end..l3.end],
+            gso_size,
+            offset: 0,
+            index: 0,
+        })
+    }
+
+    /// Writes the next datagram into `out` and returns its length, or returns `None` once the
+    /// whole payload has been written.
+    pub fn next_into(&mut self, out: &mut [u8]) -> Option<Result<usize, Error>> {
+        if self.offset == usize::MAX {
+            return None;
+        }
+        let chunk = self.gso_size.min(self.payload.len() - self.offset);
+        let last = self.offset + chunk == self.payload.len();
+        let headers = self.headers.len();
+        let len = headers + chunk;
+        if out.len() < len {
+            return Some(Err(Error::BufferTooSmall));
+        }
+        out[..headers].copy_from_slice(self.headers);
+        out[headers..len].copy_from_slice(&self.payload[self.offset..self.offset + chunk]);
+
+        let l4 = self.l3.l4();
+        let mut udp = self.udp;
+        udp.len = ((len - l4) as u16).into();
+        udp.check = be16::ZERO;
+        let _ = super::write_header(out, l4, &udp);
+        let pseudo = self
+            .l3
+            .update(out, self.index, len, super::ip::Proto::UDP.into());
+        udp.check = match super::checksum::finish(super::checksum::sum(&out[l4..len], pseudo)) {
+            // Zero means no checksum, so a computed zero is sent as all ones (RFC 768).
+            be16::ZERO => be16::MAX,
+            check => check,
+        };
+        let _ = super::write_header(out, l4, &udp);
+
+        self.index += 1;
+        self.offset = match last {
+            true => usize::MAX,
+            false => self.offset + chunk,
+        };
+        Some(Ok(len))
+    }
+}
+
+/// Prepares a UDP super-packet for UDP fragmentation offload: fills in the checksum of the whole
+/// datagram, then returns a fragmenter that splits the IPv4 packet at `l3_offset` into IP
+/// fragments for the given MTU.
+pub fn ufo_ipv4(
+    packet: &mut [u8],
+    l3_offset: usize,
+    mtu: usize,
+) -> Result<super::fragmentation::Ipv4Fragmenter<'_>, Error> {
+    let l3 = L3::parse(packet, l3_offset, super::ip::Proto::UDP)?;
+    if !l3.ipv4 {
+        return Err(Error::Unsupported);
+    }
+    let mut udp: super::udp::Header =
+        super::read_header(&packet[..l3.end], l3.l4()).map_err(|_| Error::Malformed)?;
+    udp.len = ((l3.end - l3.l4()) as u16).into();
+    udp.check = be16::ZERO;
+    let _ = super::write_header(packet, l3.l4(), &udp);
+    let pseudo = l3.update(packet, 0, l3.end, super::ip::Proto::UDP.into());
+    udp.check =
+        match super::checksum::finish(super::checksum::sum(&packet[l3.l4()..l3.end], pseudo)) {
+            be16::ZERO => be16::MAX,
+            check => check,
+        };
+    let _ = super::write_header(packet, l3.l4(), &udp);
+    super::fragmentation::Ipv4Fragmenter::new(&packet[l3_offset..l3.end], mtu)
+        .map_err(Error::Fragmentation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::Flags;
+
+    const PAYLOAD_LEN: usize = 2500;
+    const ETH: usize = crate::eth::Header::LEN;
+
+    fn payload_byte(i: usize) -> u8 {
+        (i % 251) as u8
+    }
+
+    /// Builds an Ethernet frame with an IP header for `v6` and a transport header of `l4_len`
+    /// bytes, followed by `PAYLOAD_LEN` bytes of payload.
+    fn super_packet(v6: bool, proto: crate::ip::Proto, l4_len: usize) -> ([u8; 4096], usize) {
+        let mut buf = [0u8; 4096];
+        let l3_len = if v6 { 40 } else { 20 };
+        let eth = crate::eth::Header {
+            dst_addr: [2, 0, 0, 0, 0, 1],
+            src_addr: [2, 0, 0, 0, 0, 2],
+            ether_type: match v6 {
+                true => crate::eth::Type::IPV6,
+                false => crate::eth::Type::IPV4,
+            },
+        };
+        crate::write_header(&mut buf, 0, &eth).unwrap();
+        let l4 = l3_len + l4_len + PAYLOAD_LEN;
+        if v6 {
+            let ip = crate::ipv6::Header {
+                payload_len: ((l4_len + PAYLOAD_LEN) as u16).into(),
+                proto,
+                source: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+                destination: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+                ..Default::default()
+            };
+            crate::write_header(&mut buf, ETH, &ip).unwrap();
+        } else {
+            let mut ip = crate::ipv4::Header {
+                tot_len: (l4 as u16).into(),
+                id: 0xfffe.into(),
+                ttl: 64,
+                proto,
+                source: [10, 0, 0, 1],
+                destination: [10, 0, 0, 2],
+                ..Default::default()
+            };
+            ip.set_dont_fragment(proto == crate::ip::Proto::TCP);
+            crate::write_header(&mut buf, ETH, &ip).unwrap();
+        }
+        let start = ETH + l3_len + l4_len;
+        for i in 0..PAYLOAD_LEN {
+            buf[start + i] = payload_byte(i);
+        }
+        (buf, ETH + l4)
+    }
+
+    fn tcp_packet(v6: bool, flags: Flags) -> ([u8; 4096], usize) {
+        let (mut buf, len) = super_packet(v6, crate::ip::Proto::TCP, 32);
+        let mut tcp = crate::tcp::Header {
+            source: 40000.into(),
+            dest: 443.into(),
+            seq: 0xffff_fc00.into(),
+            ack_seq: 77.into(),
+            window: 512.into(),
+            ..Default::default()
+        };
+        tcp.set_data_offset(8);
+        tcp.set_flags(flags);
+        let l4 = ETH + if v6 { 40 } else { 20 };
+        crate::write_header(&mut buf, l4, &tcp).unwrap();
+        // NOP, NOP, timestamps
+        buf[l4 + 20..l4 + 32].copy_from_slice(&[1, 1, 8, 10, 0, 0, 0, 1, 0, 0, 0, 2]);
+        (buf, len)
+    }
+
+    /// Checks the transport checksum of a segment whose IP header is at `ETH`.
+    fn verify_l4(segment: &[u8], proto: crate::ip::Proto) {
+        let version = segment[ETH] >> 4;
+        let pseudo = if version == 4 {
+            let ip: crate::ipv4::Header = crate::read_header(segment, ETH).unwrap();
+            assert!(crate::checksum::verify(&segment[ETH..ETH + 20]));
+            let l4_len = ip.tot_len.get() as usize - 20;
+            assert_eq!(segment.len(), ETH + 20 + l4_len);
+            crate::checksum::pseudo_ipv4(ip.source, ip.destination, proto.into(), l4_len as u16)
+        } else {
+            let ip: crate::ipv6::Header = crate::read_header(segment, ETH).unwrap();
+            let l4_len = ip.payload_len.get() as usize;
+            assert_eq!(segment.len(), ETH + 40 + l4_len);
+            crate::checksum::pseudo_ipv6(ip.source, ip.destination, proto.into(), l4_len as u32)
+        };
+        let l4 = ETH + if version == 4 { 20 } else { 40 };
+        let sum = crate::checksum::sum(&segment[l4..], pseudo);
+        assert_eq!(0xffff, crate::checksum::fold(sum));
+    }
+
+    #[test]
+    fn tcp() {
+        for v6 in [false, true] {
+            let flags = Flags::ACK | Flags::PSH | Flags::FIN | Flags::CWR;
+            let (packet, len) = tcp_packet(v6, flags);
+            let mut s = TcpSegmenter::new(&packet[..len], ETH, 1000).unwrap();
+            let mut out = [0u8; 1500];
+            let mut offset = 0;
+            let mut index = 0;
+            let l4 = ETH + if v6 { 40 } else { 20 };
+            while let Some(res) = s.next_into(&mut out) {
+                let n = res.unwrap();
+                let segment = &out[..n];
+                verify_l4(segment, crate::ip::Proto::TCP);
+                assert_eq!(packet[..ETH], segment[..ETH]);
+
+                let tcp: crate::tcp::Header = crate::read_header(segment, l4).unwrap();
+                assert_eq!(0xffff_fc00u32.wrapping_add(offset as u32), tcp.seq.get());
+                assert_eq!(packet[l4 + 20..l4 + 32], segment[l4 + 20..l4 + 32]);
+                let expected = match index {
+                    0 => Flags::ACK | Flags::CWR,
+                    1 => Flags::ACK,
+                    _ => Flags::ACK | Flags::PSH | Flags::FIN,
+                };
+                assert_eq!(expected, tcp.flags());
+                if !v6 {
+                    let ip: crate::ipv4::Header = crate::read_header(segment, ETH).unwrap();
+                    assert_eq!(0xfffeu16.wrapping_add(index), ip.id.get());
+                }
+
+                let data = &segment[l4 + 32..];
+                for (i, b) in data.iter().enumerate() {
+                    assert_eq!(payload_byte(offset + i), *b);
+                }
+                offset += data.len();
+                index += 1;
+            }
+            assert_eq!(PAYLOAD_LEN, offset);
+            assert_eq!(3, index);
+        }
+    }
+
+    #[test]
+    fn tcp_errors() {
+        let (packet, len) = tcp_packet(false, Flags::ACK);
+        assert!(matches!(
+            TcpSegmenter::new(&packet[..len], ETH, 0),
+            Err(Error::InvalidSegmentSize)
+        ));
+        assert!(matches!(
+            TcpSegmenter::new(&packet[..len - 1], ETH, 1000),
+            Err(Error::Malformed)
+        ));
+        assert!(matches!(
+            UdpSegmenter::new(&packet[..len], ETH, 1000),
+            Err(Error::Unsupported)
+        ));
+        let mut s = TcpSegmenter::new(&packet[..len], ETH, 1000).unwrap();
+        assert_eq!(
+            Some(Err(Error::BufferTooSmall)),
+            s.next_into(&mut [0u8; 1000])
+        );
+    }
+
+    fn udp_packet(v6: bool) -> ([u8; 4096], usize) {
+        let (mut buf, len) = super_packet(v6, crate::ip::Proto::UDP, 8);
+        let udp = crate::udp::Header {
+            source: 5000.into(),
+            destination: 4789.into(),
+            ..Default::default()
+        };
+        let l4 = ETH + if v6 { 40 } else { 20 };
+        crate::write_header(&mut buf, l4, &udp).unwrap();
+        (buf, len)
+    }
+
+    #[test]
+    fn udp() {
+        for v6 in [false, true] {
+            let (packet, len) = udp_packet(v6);
+            let mut s = UdpSegmenter::new(&packet[..len], ETH, 1200).unwrap();
+            let mut out = [0u8; 1500];
+            let mut lens = [0; 3];
+            let l4 = ETH + if v6 { 40 } else { 20 };
+            for l in lens.iter_mut() {
+                let n = s.next_into(&mut out).unwrap().unwrap();
+                verify_l4(&out[..n], crate::ip::Proto::UDP);
+                let udp: crate::udp::Header = crate::read_header(&out, l4).unwrap();
+                assert_eq!((n - l4) as u16, udp.len.get());
+                assert_eq!(be16::new(4789), udp.destination);
+                *l = n - l4 - 8;
+            }
+            assert_eq!([1200, 1200, 100], lens);
+            assert_eq!(None, s.next_into(&mut out));
+        }
+    }
+
+    #[test]
+    fn ufo() {
+        use crate::reassembly::{Ipv4Key, Reassembler};
+
+        let (mut packet, len) = udp_packet(false);
+        let original = packet;
+        let mut f = ufo_ipv4(&mut packet[..len], ETH, 1500).unwrap();
+        let mut t = Reassembler::<Ipv4Key, 1, 4096>::new(30);
+        let mut out = [0u8; 1500];
+        let mut whole = None;
+        while let Some(res) = f.next_into(&mut out) {
+            let n = res.unwrap();
+            if let Some(p) = t.insert_ipv4(0, &out[..n]).unwrap() {
+                let mut frame = [0u8; 4096];
+                frame[..ETH].copy_from_slice(&original[..ETH]);
+                frame[ETH..ETH + p.len()].copy_from_slice(p);
+                whole = Some(ETH + p.len());
+                verify_l4(&frame[..ETH + p.len()], crate::ip::Proto::UDP);
+            }
+        }
+        assert_eq!(Some(len), whole);
+    }
+}
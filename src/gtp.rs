@@ -0,0 +1,561 @@
+//! GTPv1-U, the GPRS Tunnelling Protocol for user plane traffic (3GPP TS 29.281).
+//!
+//! The mandatory [`Header`] is followed by the [`Options`] when any of the E, S or PN flags is
+//! set, then by a chain of extension headers when E is set, each naming the type of the next one.
+//! A G-PDU carries the subscriber's IPv4 or IPv6 packet after the last extension header.
+
+use core::mem;
+
+use crate::{be16, be32};
+
+/// UDP port of GTP-U (3GPP TS 29.281).
+pub const PORT: u16 = 2152;
+
+#[repr(transparent)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct MsgType {
+    val: u8,
+}
+
+impl MsgType {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    pub const ECHO_REQUEST: MsgType = MsgType::new(1);
+    pub const ECHO_RESPONSE: MsgType = MsgType::new(2);
+    pub const ERROR_INDICATION: MsgType = MsgType::new(26);
+    pub const SUPPORTED_EXT_HEADERS: MsgType = MsgType::new(31);
+    pub const END_MARKER: MsgType = MsgType::new(254);
+    /// A tunnelled user packet (T-PDU).
+    pub const G_PDU: MsgType = MsgType::new(255);
+}
+
+impl core::fmt::Display for MsgType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            MsgType::ECHO_REQUEST => f.write_str("echo request"),
+            MsgType::ECHO_RESPONSE => f.write_str("echo response"),
+            MsgType::ERROR_INDICATION => f.write_str("error indication"),
+            MsgType::SUPPORTED_EXT_HEADERS => f.write_str("supported extension headers"),
+            MsgType::END_MARKER => f.write_str("end marker"),
+            MsgType::G_PDU => f.write_str("G-PDU"),
+            _ => write!(f, "message type {}", self.val),
+        }
+    }
+}
+
+/// Type of the next extension header, the last byte of the optional fields and of each
+/// extension header.
+#[repr(transparent)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct ExtType {
+    val: u8,
+}
+
+impl ExtType {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    /// No more extension headers.
+    pub const NONE: ExtType = ExtType::new(0);
+    pub const SERVICE_CLASS: ExtType = ExtType::new(0x20);
+    pub const UDP_PORT: ExtType = ExtType::new(0x40);
+    pub const RAN_CONTAINER: ExtType = ExtType::new(0x81);
+    pub const LONG_PDCP_PDU_NUMBER: ExtType = ExtType::new(0x82);
+    pub const XW_RAN_CONTAINER: ExtType = ExtType::new(0x83);
+    pub const NR_RAN_CONTAINER: ExtType = ExtType::new(0x84);
+    pub const PDU_SESSION_CONTAINER: ExtType = ExtType::new(0x85);
+    pub const PDCP_PDU_NUMBER: ExtType = ExtType::new(0xc0);
+}
+
+/// GTPv1-U mandatory header, which is present after the UDP header.
+#[repr(C, packed(2))]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Header {
+    /// Version in the top three bits, then the PT, E, S and PN flags.
+    pub flags: u8,
+    pub msg_type: MsgType,
+    /// Length of the packet after the mandatory header, including the optional fields and
+    /// extension headers.
+    pub length: be16,
+    /// Tunnel endpoint identifier.
+    pub teid: be32,
+}
+
+const _: () = assert!(mem::size_of::<Header>() == 8 && mem::align_of::<Header>() == 2);
+
+impl Default for Header {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(MsgType::G_PDU, 0)
+    }
+}
+
+/// The optional fields, present when any of the E, S or PN flags is set.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Options {
+    pub seq: be16,
+    pub npdu: u8,
+    pub next_ext: ExtType,
+}
+
+const _: () = assert!(mem::size_of::<Options>() == 4 && mem::align_of::<Options>() == 2);
+
+impl Options {
+    pub const LEN: usize = mem::size_of::<Options>();
+}
+
+impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Gtp;
+
+    /// Includes the optional fields but not the extension headers, whose length can only be
+    /// found by walking them with [`Header::payload_offset`].
+    #[inline(always)]
+    fn len(&self) -> usize {
+        match self.has_options() {
+            true => Self::LEN + Options::LEN,
+            false => Self::LEN,
+        }
+    }
+
+    /// Checks the version and protocol type.
+    #[inline(always)]
+    fn validate(&self, remaining: usize) -> Result<(), ()> {
+        match self.version() == 1 && self.flags & Self::PT != 0 && self.len() <= remaining {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
+
+impl Header {
+    pub const LEN: usize = mem::size_of::<Header>();
+
+    /// Protocol type flag, set for GTP and clear for GTP'.
+    pub const PT: u8 = 0x10;
+    /// Extension header flag.
+    pub const E: u8 = 0x04;
+    /// Sequence number flag.
+    pub const S: u8 = 0x02;
+    /// N-PDU number flag.
+    pub const PN: u8 = 0x01;
+
+    /// Returns a version 1 GTP header with no optional fields and a length of 0.
+    #[inline(always)]
+    pub fn new(msg_type: MsgType, teid: u32) -> Self {
+        Self {
+            flags: 1 << 5 | Self::PT,
+            msg_type,
+            length: be16::ZERO,
+            teid: teid.into(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn version(&self) -> u8 {
+        self.flags >> 5
+    }
+
+    #[inline(always)]
+    pub fn has_ext(&self) -> bool {
+        self.flags & Self::E != 0
+    }
+
+    #[inline(always)]
+    pub fn has_seq(&self) -> bool {
+        self.flags & Self::S != 0
+    }
+
+    #[inline(always)]
+    pub fn has_npdu(&self) -> bool {
+        self.flags & Self::PN != 0
+    }
+
+    /// Returns true if the optional fields follow the mandatory header.
+    #[inline(always)]
+    pub fn has_options(&self) -> bool {
+        self.flags & (Self::E | Self::S | Self::PN) != 0
+    }
+
+    #[inline(always)]
+    pub fn teid(&self) -> u32 {
+        self.teid.get()
+    }
+
+    #[inline(always)]
+    pub fn set_teid(&mut self, teid: u32) {
+        self.teid = teid.into();
+    }
+
+    /// Returns the bytes of the frame from this header on, or none if this header isn't in
+    /// `frame`.
+    #[inline(always)]
+    fn bytes(&self, frame: core::ops::Range<*mut core::ffi::c_void>) -> &[u8] {
+        super::frame_from(self, frame).unwrap_or(&[])
+    }
+
+    /// Returns the extension headers that follow this header, as [`extensions`] does. There are
+    /// none if this header isn't in `frame`, such as a copy of it.
+    #[inline(always)]
+    pub fn extensions(&self, frame: core::ops::Range<*mut core::ffi::c_void>) -> Extensions<'_> {
+        extensions(self.bytes(frame))
+    }
+
+    /// Returns the offset of the payload from this header, as [`payload_offset`] does, or `Err` if
+    /// this header isn't in `frame`.
+    #[inline(always)]
+    pub fn payload_offset(
+        &self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<usize, ()> {
+        payload_offset(self.bytes(frame))
+    }
+}
+
+/// Returns the optional fields of the GTP packet in `packet`, or `None` if it has none. Returns
+/// `Err` if they don't fit.
+#[inline(always)]
+pub fn options(packet: &[u8]) -> Result<Option<Options>, ()> {
+    let h = super::read_header::<Header>(packet, 0)?;
+    match h.has_options() {
+        true => super::read_header(packet, Header::LEN).map(Some),
+        false => Ok(None),
+    }
+}
+
+/// Most extension headers [`Extensions`] yields from one packet.
+pub const MAX_EXTENSIONS: usize = 16;
+
+/// An extension header.
+#[derive(Debug, Copy, Clone)]
+pub struct Extension<'a> {
+    pub ext_type: ExtType,
+    /// The contents between the length byte and the next extension header type.
+    pub content: &'a [u8],
+}
+
+/// The QoS fields of a PDU Session Container (3GPP TS 38.415).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PduSession {
+    /// [`PduSession::DL`] or [`PduSession::UL`].
+    pub pdu_type: u8,
+    /// QoS flow identifier.
+    pub qfi: u8,
+}
+
+impl PduSession {
+    /// Downlink PDU Session Information.
+    pub const DL: u8 = 0;
+    /// Uplink PDU Session Information.
+    pub const UL: u8 = 1;
+}
+
+impl Extension<'_> {
+    /// Returns the PDU type and QFI of a PDU Session Container.
+    #[inline(always)]
+    pub fn pdu_session(&self) -> Option<PduSession> {
+        if self.ext_type != ExtType::PDU_SESSION_CONTAINER {
+            return None;
+        }
+        match self.content {
+            [first, second, ..] => Some(PduSession {
+                pdu_type: first >> 4,
+                qfi: second & 0x3f,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over the extension headers of a GTP packet. It yields `Err` once and stops at an
+/// extension header whose length is 0 or runs past the packet, and stops after
+/// [`MAX_EXTENSIONS`].
+#[derive(Debug, Clone)]
+pub struct Extensions<'a> {
+    packet: &'a [u8],
+    offset: usize,
+    next: ExtType,
+    left: usize,
+}
+
+/// Returns an iterator over the extension headers of the GTP packet in `packet`, which is empty
+/// unless the E flag is set.
+#[inline(always)]
+pub fn extensions(packet: &[u8]) -> Extensions<'_> {
+    let next = match options(packet) {
+        Ok(Some(options)) if packet[0] & Header::E != 0 => options.next_ext,
+        _ => ExtType::NONE,
+    };
+    Extensions {
+        packet,
+        offset: Header::LEN + Options::LEN,
+        next,
+        left: MAX_EXTENSIONS,
+    }
+}
+
+impl<'a> Iterator for Extensions<'a> {
+    type Item = Result<Extension<'a>, ()>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == ExtType::NONE {
+            return None;
+        }
+        let ext_type = self.next;
+        let len = self.packet.get(self.offset).map(|len| *len as usize * 4);
+        let ext = match len {
+            Some(len) if len > 0 && self.left > 0 => {
+                self.packet.get(self.offset..self.offset + len)
+            }
+            _ => None,
+        };
+        let Some(ext) = ext else {
+            self.left = 0;
+            self.next = ExtType::NONE;
+            return Some(Err(()));
+        };
+        self.left -= 1;
+        self.offset += ext.len();
+        self.next = ExtType::new(ext[ext.len() - 1]);
+        Some(Ok(Extension {
+            ext_type,
+            content: &ext[1..ext.len() - 1],
+        }))
+    }
+}
+
+/// Returns the offset of the payload of the GTP packet in `packet`, after the optional fields and
+/// every extension header. Returns `Err` if any of them doesn't fit or if there are more than
+/// [`MAX_EXTENSIONS`] extension headers.
+#[inline(always)]
+pub fn payload_offset(packet: &[u8]) -> Result<usize, ()> {
+    let h = super::read_header::<Header>(packet, 0)?;
+    if !h.has_options() {
+        return Ok(Header::LEN);
+    }
+    let mut exts = extensions(packet);
+    for ext in exts.by_ref() {
+        ext?;
+    }
+    match exts.next == ExtType::NONE && exts.offset <= packet.len() {
+        true => Ok(exts.offset),
+        false => Err(()),
+    }
+}
+
+impl super::AutoNextHeader for Header {
+    /// Returns the IPv4 or IPv6 packet carried by a G-PDU, told apart by its version field.
+    #[inline(always)]
+    fn next(
+        &mut self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<super::HeaderPtr, ()> {
+        use super::Header as _;
+
+        let remaining = (frame.end as usize).saturating_sub(self as *const Header as usize);
+        self.validate(remaining)?;
+        if self.msg_type != MsgType::G_PDU {
+            return Ok(super::HeaderPtr::Unhandled());
+        }
+        let offset = self.payload_offset(frame.clone())?;
+        let inner = unsafe { (self as *mut Header as *mut u8).add(offset) };
+        let fits = |len: usize| match inner.wrapping_add(len) > frame.end as *mut u8 {
+            false => Ok(()),
+            true => Err(()),
+        };
+        fits(1)?;
+        match unsafe { *inner } >> 4 {
+            4 => {
+                fits(super::ipv4::Header::LEN)?;
+                Ok(super::HeaderPtr::Ipv4(super::Ptr::new(inner as *mut _)))
+            }
+            6 => {
+                fits(super::ipv6::Header::LEN)?;
+                Ok(super::HeaderPtr::Ipv6(super::Ptr::new(inner as *mut _)))
+            }
+            _ => Ok(super::HeaderPtr::Unhandled()),
+        }
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "GTPv{} {}, teid 0x{:08x}, length {}",
+            self.version(),
+            self.msg_type,
+            self.teid(),
+            self.length.get()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AutoNextHeader, HeaderPtr};
+
+    // A downlink G-PDU with a sequence number and a PDU Session Container for QFI 9, carrying
+    // the start of an IPv4 packet.
+    const PACKET: [u8; 12 + 8 + 20] = [
+        0x36, 0xff, 0, 32, 0, 0, 0x12, 0x34, // flags E and S, G-PDU, TEID 0x1234
+        0, 7, 0, 0x85, // sequence 7, PDU Session Container next
+        2, 0x00, 0x09, 0, 0, 0, 0, 0, // 8 bytes, DL, QFI 9, no more extensions
+        0x45, 0, 0, 20, 0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+    ];
+
+    #[test]
+    fn header() {
+        let h: Header = crate::read_header(&PACKET, 0).unwrap();
+        assert_eq!(1, h.version());
+        assert!(h.has_ext() && h.has_seq() && !h.has_npdu());
+        assert_eq!(0x1234, h.teid());
+        assert_eq!(12, crate::Header::len(&h));
+        assert_eq!(7, options(&PACKET).unwrap().unwrap().seq.get());
+        assert_eq!(
+            "GTPv1 G-PDU, teid 0x00001234, length 32",
+            std::format!("{h}")
+        );
+
+        let mut h = Header::new(MsgType::ECHO_REQUEST, 0);
+        h.set_teid(0xdeadbeef);
+        assert_eq!(be32::new(0xdeadbeef), { h.teid });
+        assert_eq!(0x30, h.flags);
+        assert_eq!(8, crate::Header::len(&h));
+        assert!(crate::Header::validate(&h, 8).is_ok());
+        h.flags = 0x50;
+        assert!(crate::Header::validate(&h, 8).is_err());
+    }
+
+    #[test]
+    fn pdu_session() {
+        let mut exts = extensions(&PACKET);
+        let ext = exts.next().unwrap().unwrap();
+        assert_eq!(ExtType::PDU_SESSION_CONTAINER, ext.ext_type);
+        assert_eq!(
+            Some(PduSession {
+                pdu_type: PduSession::DL,
+                qfi: 9
+            }),
+            ext.pdu_session()
+        );
+        assert!(exts.next().is_none());
+        assert_eq!(Ok(20), payload_offset(&PACKET));
+    }
+
+    #[test]
+    fn malformed_extensions() {
+        let mut packet = PACKET;
+        packet[12] = 0;
+        assert!(extensions(&packet).next().unwrap().is_err());
+        assert!(payload_offset(&packet).is_err());
+
+        packet[12] = 10;
+        assert!(payload_offset(&packet).is_err());
+        assert!(payload_offset(&PACKET[..19]).is_err());
+
+        // Without the E flag the extension type in the optional fields is ignored.
+        packet[0] = 0x32;
+        assert_eq!(0, extensions(&packet).count());
+        assert_eq!(Ok(12), payload_offset(&packet));
+
+        // A chain longer than MAX_EXTENSIONS.
+        let mut long = [0u8; 12 + 4 * (MAX_EXTENSIONS + 1)];
+        long[..12].copy_from_slice(&[0x34, 0xff, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0x40]);
+        for ext in long[12..].chunks_exact_mut(4) {
+            ext.copy_from_slice(&[1, 0, 0, 0x40]);
+        }
+        assert_eq!(
+            MAX_EXTENSIONS,
+            extensions(&long).filter(Result::is_ok).count()
+        );
+        assert!(payload_offset(&long).is_err());
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut buf = crate::Aligned([0u8; 20 + 8 + 40]);
+        let ip = crate::ipv4::Header {
+            proto: crate::ip::Proto::UDP,
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 0, &ip).unwrap();
+        let udp = crate::udp::Header {
+            destination: crate::gtp::PORT.into(),
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 20, &udp).unwrap();
+        buf.0[28..68].copy_from_slice(&PACKET);
+
+        let r = buf.range(68);
+        let Ok(HeaderPtr::Udp(mut udp)) =
+            crate::Ptr::new(r.start as *mut crate::ipv4::Header).next(r.clone())
+        else {
+            panic!("expected UDP");
+        };
+        let Ok(HeaderPtr::Gtp(mut gtp)) = udp.next(r.clone()) else {
+            panic!("expected GTP");
+        };
+        assert_eq!(0x1234, gtp.teid());
+        assert_eq!(
+            Some(9),
+            gtp.extensions(r.clone())
+                .next()
+                .unwrap()
+                .unwrap()
+                .pdu_session()
+                .map(|p| p.qfi)
+        );
+        // A copy isn't in the frame, so there is nothing after it to read.
+        let copy = *gtp;
+        assert!(copy.has_ext());
+        assert_eq!(0, copy.extensions(r.clone()).count());
+        assert!(copy.payload_offset(r.clone()).is_err());
+        match gtp.next(r) {
+            Ok(HeaderPtr::Ipv4(inner)) => assert_eq!([10, 0, 0, 2], inner.destination),
+            _ => panic!("expected IPv4"),
+        }
+        assert!(gtp.next(buf.range(67)).is_err());
+
+        // Signalling messages carry no user packet.
+        buf.0[29] = MsgType::ECHO_REQUEST.get();
+        let r = buf.range(68);
+        let mut gtp = crate::Ptr::new(unsafe { (r.start as *mut u8).add(28) } as *mut Header);
+        assert!(matches!(gtp.next(r), Ok(HeaderPtr::Unhandled())));
+    }
+}
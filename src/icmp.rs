@@ -0,0 +1,514 @@
+use core::mem;
+
+use crate::be16;
+
+#[repr(transparent)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Type {
+    val: u8,
+}
+
+impl Type {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    pub const ECHO_REPLY: Type = Type::new(0);
+    pub const DEST_UNREACH: Type = Type::new(3);
+    pub const SOURCE_QUENCH: Type = Type::new(4);
+    pub const REDIRECT: Type = Type::new(5);
+    pub const ECHO_REQUEST: Type = Type::new(8);
+    pub const TIME_EXCEEDED: Type = Type::new(11);
+    pub const PARAMETER_PROBLEM: Type = Type::new(12);
+
+    /// Returns true for error messages, which must never be answered with another error.
+    #[inline(always)]
+    pub fn is_error(&self) -> bool {
+        matches!(
+            *self,
+            Type::DEST_UNREACH
+                | Type::SOURCE_QUENCH
+                | Type::REDIRECT
+                | Type::TIME_EXCEEDED
+                | Type::PARAMETER_PROBLEM
+        )
+    }
+}
+
+/// Destination Unreachable codes.
+pub mod unreach {
+    pub const NET: u8 = 0;
+    pub const HOST: u8 = 1;
+    pub const PROTOCOL: u8 = 2;
+    pub const PORT: u8 = 3;
+    pub const FRAG_NEEDED: u8 = 4;
+    pub const ADMIN_PROHIBITED: u8 = 13;
+}
+
+/// ICMP header, which is present after the IPv4 header.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Header {
+    pub msg_type: Type,
+    pub code: u8,
+    pub check: be16,
+    /// Rest of the header, whose meaning depends on the type.
+    pub rest: [u8; 4],
+}
+
+const _: () = assert!(mem::size_of::<Header>() == 8 && mem::align_of::<Header>() == 2);
+
+impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Icmp;
+}
+
+impl Header {
+    pub const LEN: usize = mem::size_of::<Header>();
+
+    /// Returns the identifier of an echo request or reply.
+    #[inline(always)]
+    pub fn id(&self) -> be16 {
+        [self.rest[0], self.rest[1]].into()
+    }
+
+    /// Returns the sequence number of an echo request or reply.
+    #[inline(always)]
+    pub fn seq(&self) -> be16 {
+        [self.rest[2], self.rest[3]].into()
+    }
+
+    /// Returns the next-hop MTU of a Fragmentation Needed message (RFC 1191).
+    #[inline(always)]
+    pub fn mtu(&self) -> be16 {
+        self.seq()
+    }
+}
+
+/// Turns an ICMP echo request into an echo reply in place. The Ethernet and IP addresses are
+/// swapped, the TTL is reset to 64 and both checksums are patched incrementally. Returns `Err`
+/// without modifying anything if `icmp` is not an echo request, or if it was sent to a broadcast
+/// or multicast address, which the reply would otherwise come from. A directed broadcast to a
+/// subnet can't be told apart from a unicast address here and is left to the caller.
+#[inline(always)]
+pub fn echo_reply(
+    eth: &mut super::eth::Header,
+    ip: &mut super::ipv4::Header,
+    icmp: &mut Header,
+) -> Result<(), ()> {
+    if icmp.msg_type != Type::ECHO_REQUEST || icmp.code != 0 {
+        return Err(());
+    }
+    let dst = ip.destination();
+    if dst.is_broadcast() || dst.is_multicast() || eth.dst_addr[0] & 1 != 0 {
+        return Err(());
+    }
+
+    core::mem::swap(&mut eth.src_addr, &mut eth.dst_addr);
+
+    // Swapping the addresses leaves the IP checksum unchanged.
+    core::mem::swap(&mut ip.source, &mut ip.destination);
+    let old = be16::new((ip.ttl as u16) << 8);
+    ip.ttl = 64;
+    ip.check = super::checksum::update16(ip.check, old, be16::new(64 << 8));
+
+    icmp.msg_type = Type::ECHO_REPLY;
+    icmp.check = super::checksum::update16(
+        icmp.check,
+        be16::new((Type::ECHO_REQUEST.val as u16) << 8),
+        be16::new((Type::ECHO_REPLY.val as u16) << 8),
+    );
+    Ok(())
+}
+
+/// Same as [`echo_reply`] for the Ethernet frame in `frame`, which must hold an ICMP echo request
+/// over IPv4.
+#[inline(always)]
+pub fn echo_reply_frame(frame: core::ops::Range<*mut core::ffi::c_void>) -> Result<(), ()> {
+    use super::NextHeader;
+
+    let mut eth = super::eth::Header::from_frame(frame.clone())?;
+    if eth.ether_type != super::eth::Type::IPV4 {
+        return Err(());
+    }
+    let mut ip = eth.next_t::<super::ipv4::Header>(frame.clone())?;
+    if ip.proto != super::ip::Proto::ICMP || ip.header_len() != super::ipv4::Header::LEN {
+        return Err(());
+    }
+    let mut icmp = ip.next_t::<Header>(frame)?;
+    echo_reply(&mut eth, &mut ip, &mut icmp)
+}
+
+/// An ICMP error to send in response to a dropped IPv4 packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Time Exceeded in transit, sent when the TTL expires.
+    TimeExceeded,
+    /// Destination Unreachable with one of the [`unreach`] codes.
+    DestinationUnreachable(u8),
+    /// Destination Unreachable, Fragmentation Needed and DF set, with the next-hop MTU.
+    FragmentationNeeded { mtu: u16 },
+}
+
+impl Error {
+    #[inline(always)]
+    fn header(&self) -> Header {
+        let (msg_type, code, rest) = match *self {
+            Error::TimeExceeded => (Type::TIME_EXCEEDED, 0, [0; 4]),
+            Error::DestinationUnreachable(code) => (Type::DEST_UNREACH, code, [0; 4]),
+            Error::FragmentationNeeded { mtu } => {
+                let mtu = mtu.to_be_bytes();
+                (
+                    Type::DEST_UNREACH,
+                    unreach::FRAG_NEEDED,
+                    [0, 0, mtu[0], mtu[1]],
+                )
+            }
+        };
+        Header {
+            msg_type,
+            code,
+            check: be16::ZERO,
+            rest,
+        }
+    }
+}
+
+/// Largest datagram an error message may occupy (RFC 1812, section 4.3.2.3).
+pub const MAX_ERROR_LEN: usize = 576;
+
+const IP_OFFSET: usize = super::eth::Header::LEN;
+const QUOTE_OFFSET: usize = IP_OFFSET + super::ipv4::Header::LEN + Header::LEN;
+
+/// Checks that an ICMP error may be sent about the IPv4 packet in `frame` (RFC 1122, section
+/// 3.2.2) and returns its Ethernet and IP headers along with the number of bytes to quote.
+#[inline(always)]
+fn quotable(frame: &[u8]) -> Result<(super::eth::Header, super::ipv4::Header, usize), ()> {
+    let eth: super::eth::Header = super::read_header(frame, 0)?;
+    if eth.ether_type != super::eth::Type::IPV4 {
+        return Err(());
+    }
+    let ip: super::ipv4::Header = super::read_header(frame, IP_OFFSET)?;
+    let ihl = ip.header_len();
+    if ip.version() != 4 || ihl < super::ipv4::Header::LEN {
+        return Err(());
+    }
+    // The quote must hold at least the IP header and 64 bits of payload (RFC 792).
+    let available = frame.len() - IP_OFFSET;
+    if available < ihl + 8 {
+        return Err(());
+    }
+    if ip.fragment_offset() != 0 {
+        return Err(());
+    }
+    let (src, dst) = (ip.source(), ip.destination());
+    if src.is_broadcast() || src.is_multicast() || src.is_unspecified() || src.is_loopback() {
+        return Err(());
+    }
+    if dst.is_broadcast() || dst.is_multicast() || eth.dst_addr[0] & 1 != 0 {
+        return Err(());
+    }
+    if ip.proto == super::ip::Proto::ICMP {
+        let icmp: Header = super::read_header(frame, IP_OFFSET + ihl)?;
+        if icmp.msg_type.is_error() {
+            return Err(());
+        }
+    }
+
+    let total = (ip.tot_len.get() as usize).clamp(ihl + 8, available);
+    let max = MAX_ERROR_LEN - super::ipv4::Header::LEN - Header::LEN;
+    Ok((eth, ip, total.min(max)))
+}
+
+/// Writes the Ethernet, IPv4 and ICMP headers in front of a quote already at `QUOTE_OFFSET`.
+#[inline(always)]
+fn finish(
+    out: &mut [u8],
+    eth: &super::eth::Header,
+    ip: &super::ipv4::Header,
+    error: Error,
+    source: Option<core::net::Ipv4Addr>,
+    quote_len: usize,
+) -> Result<usize, ()> {
+    let len = QUOTE_OFFSET + quote_len;
+    if out.len() < len {
+        return Err(());
+    }
+
+    let reply_eth = super::eth::Header {
+        dst_addr: eth.src_addr,
+        src_addr: eth.dst_addr,
+        ether_type: super::eth::Type::IPV4,
+    };
+    let mut reply_ip = super::ipv4::Header {
+        tos: 0xc0,
+        tot_len: ((len - IP_OFFSET) as u16).into(),
+        ttl: 64,
+        proto: super::ip::Proto::ICMP,
+        source: source.map_or(ip.destination, |s| s.octets()),
+        destination: ip.source,
+        ..Default::default()
+    };
+    reply_ip.update_checksum();
+
+    let icmp_offset = IP_OFFSET + super::ipv4::Header::LEN;
+    let mut icmp = error.header();
+    super::write_header(out, icmp_offset, &icmp)?;
+    icmp.check = super::checksum::checksum(&out[icmp_offset..len]);
+    super::write_header(out, icmp_offset, &icmp)?;
+    super::write_header(out, IP_OFFSET, &reply_ip)?;
+    super::write_header(out, 0, &reply_eth)?;
+    Ok(len)
+}
+
+/// Builds the ICMP error for the Ethernet + IPv4 `frame` into `out`, returning the length of the
+/// new frame. The reply is addressed back to the sender, from `source` if given or from the
+/// original destination otherwise. Returns `Err` if no error may be sent about this packet (e.g.
+/// it is itself an ICMP error, a non-initial fragment or was broadcast) or `out` is too small.
+#[inline(always)]
+pub fn error_reply(
+    frame: &[u8],
+    out: &mut [u8],
+    error: Error,
+    source: Option<core::net::Ipv4Addr>,
+) -> Result<usize, ()> {
+    let (eth, ip, quote_len) = quotable(frame)?;
+    let quote_len = quote_len.min(out.len().saturating_sub(QUOTE_OFFSET));
+    if quote_len < ip.header_len() + 8 {
+        return Err(());
+    }
+    out[QUOTE_OFFSET..QUOTE_OFFSET + quote_len]
+        .copy_from_slice(&frame[IP_OFFSET..IP_OFFSET + quote_len]);
+    finish(out, &eth, &ip, error, source, quote_len)
+}
+
+/// Same as [`error_reply`] but transforms the frame held in the first `len` bytes of `buf` in
+/// place. The original packet is moved back to make room for the new headers, so `buf` needs
+/// spare room after the frame; the quote is truncated to fit.
+#[inline(always)]
+pub fn error_reply_in_place(
+    buf: &mut [u8],
+    len: usize,
+    error: Error,
+    source: Option<core::net::Ipv4Addr>,
+) -> Result<usize, ()> {
+    let (eth, ip, quote_len) = quotable(buf.get(..len).ok_or(())?)?;
+    let quote_len = quote_len.min(buf.len().saturating_sub(QUOTE_OFFSET));
+    if quote_len < ip.header_len() + 8 {
+        return Err(());
+    }
+    buf.copy_within(IP_OFFSET..IP_OFFSET + quote_len, QUOTE_OFFSET);
+    finish(buf, &eth, &ip, error, source, quote_len)
+}
+
+impl core::fmt::Display for Type {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Type::ECHO_REPLY => f.write_str("echo reply"),
+            Type::DEST_UNREACH => f.write_str("destination unreachable"),
+            Type::SOURCE_QUENCH => f.write_str("source quench"),
+            Type::REDIRECT => f.write_str("redirect"),
+            Type::ECHO_REQUEST => f.write_str("echo request"),
+            Type::TIME_EXCEEDED => f.write_str("time exceeded"),
+            Type::PARAMETER_PROBLEM => f.write_str("parameter problem"),
+            _ => write!(f, "type {}", self.val),
+        }
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.msg_type {
+            Type::ECHO_REQUEST | Type::ECHO_REPLY => write!(
+                f,
+                "ICMP {}, id {}, seq {}",
+                self.msg_type,
+                self.id(),
+                self.seq()
+            ),
+            _ => write!(f, "ICMP {}, code {}", self.msg_type, self.code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::net::Ipv4Addr;
+
+    // UDP datagram to port 33434 with TTL 1, as sent by traceroute.
+    const FRAME: [u8; 74] = [
+        0x52, 0x54, 0x00, 0x12, 0x34, 0x56, 0x52, 0x54, 0x00, 0xab, 0xcd, 0xef, 0x08,
+        0x00, // eth
+        0x45, 0x00, 0x00, 0x3c, 0x12, 0x34, 0x00, 0x00, 0x01, 0x11, 0xe4, 0x24, 0x0a, 0x00, 0x00,
+        0x02, 0x08, 0x08, 0x08, 0x08, // ip
+        0x82, 0x9b, 0x82, 0x9a, 0x00, 0x28, 0x00, 0x00, // udp
+        0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e,
+        0x4f, 0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x5b, 0x5c, 0x5d,
+        0x5e, 0x5f,
+    ];
+
+    const ROUTER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+    // Echo request as sent by iputils ping from 192.168.122.1 to 192.168.122.10.
+    const ECHO_REQUEST: [u8; 98] = [
+        0x52, 0x54, 0x00, 0xa1, 0xb2, 0xc3, 0xfe, 0x54, 0x00, 0x12, 0x34, 0x56, 0x08, 0x00, 0x45,
+        0x00, 0x00, 0x54, 0x9e, 0x4c, 0x40, 0x00, 0x40, 0x01, 0x27, 0x00, 0xc0, 0xa8, 0x7a, 0x01,
+        0xc0, 0xa8, 0x7a, 0x0a, 0x08, 0x00, 0xf1, 0xa9, 0x1c, 0x46, 0x00, 0x01, 0xc3, 0xa6, 0xf0,
+        0x66, 0x00, 0x00, 0x00, 0x00, 0x6c, 0x2e, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x11,
+        0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f, 0x20,
+        0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f,
+        0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37,
+    ];
+
+    #[test]
+    fn echo() {
+        let mut frame = crate::Aligned(ECHO_REQUEST);
+        // Lower the TTL so that resetting it exercises the checksum update.
+        frame.0[IP_OFFSET + 8] = 3;
+        frame.0[IP_OFFSET + 10..IP_OFFSET + 12].copy_from_slice(&[0x64, 0x00]);
+        assert!(crate::checksum::verify(&frame.0[IP_OFFSET..IP_OFFSET + 20]));
+        echo_reply_frame(frame.range(ECHO_REQUEST.len())).unwrap();
+        let reply = frame.0;
+
+        assert_eq!(ECHO_REQUEST[6..12], reply[0..6]);
+        assert_eq!(ECHO_REQUEST[0..6], reply[6..12]);
+        let ip: crate::ipv4::Header = crate::read_header(&reply, IP_OFFSET).unwrap();
+        assert_eq!(Ipv4Addr::new(192, 168, 122, 10), ip.source());
+        assert_eq!(Ipv4Addr::new(192, 168, 122, 1), ip.destination());
+        assert_eq!(64, ip.ttl);
+        assert!(crate::checksum::verify(&reply[IP_OFFSET..IP_OFFSET + 20]));
+
+        let icmp: Header = crate::read_header(&reply, IP_OFFSET + 20).unwrap();
+        assert_eq!(Type::ECHO_REPLY, icmp.msg_type);
+        assert_eq!(be16::new(0x1c46), icmp.id());
+        assert_eq!(be16::new(1), icmp.seq());
+        assert!(crate::checksum::verify(&reply[IP_OFFSET + 20..]));
+        assert_eq!(ECHO_REQUEST[42..], reply[42..]);
+
+        // A reply is not answered again.
+        assert!(echo_reply_frame(frame.range(ECHO_REQUEST.len())).is_err());
+        assert_eq!(reply, frame.0);
+    }
+
+    #[test]
+    fn echo_broadcast() {
+        // Sent to the limited broadcast address, to a multicast group, and to a unicast IP
+        // address in a broadcast Ethernet frame.
+        let cases: [(usize, &[u8]); 3] = [
+            (IP_OFFSET + 16, &[255, 255, 255, 255]),
+            (IP_OFFSET + 16, &[224, 0, 0, 1]),
+            (0, &[0xff; 6]),
+        ];
+        for (offset, bytes) in cases {
+            let mut frame = crate::Aligned(ECHO_REQUEST);
+            frame.0[offset..offset + bytes.len()].copy_from_slice(bytes);
+            let request = frame.0;
+            assert!(echo_reply_frame(frame.range(ECHO_REQUEST.len())).is_err());
+            assert_eq!(request, frame.0);
+        }
+    }
+
+    #[test]
+    fn echo_truncated() {
+        let mut frame = crate::Aligned(ECHO_REQUEST);
+        assert!(echo_reply_frame(frame.range(IP_OFFSET + 27)).is_err());
+        assert_eq!(ECHO_REQUEST, frame.0);
+    }
+
+    fn check_reply(out: &[u8], len: usize, quote: usize) {
+        assert_eq!(QUOTE_OFFSET + quote, len);
+        assert_eq!(FRAME[6..12], out[0..6]);
+        assert_eq!(FRAME[0..6], out[6..12]);
+        let ip: crate::ipv4::Header = crate::read_header(out, IP_OFFSET).unwrap();
+        assert_eq!(crate::ip::Proto::ICMP, ip.proto);
+        assert_eq!(ROUTER, ip.source());
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 2), ip.destination());
+        assert_eq!((len - IP_OFFSET) as u16, ip.tot_len.get());
+        assert!(crate::checksum::verify(&out[IP_OFFSET..IP_OFFSET + 20]));
+        assert!(crate::checksum::verify(&out[IP_OFFSET + 20..len]));
+        assert_eq!(FRAME[IP_OFFSET..IP_OFFSET + quote], out[QUOTE_OFFSET..len]);
+    }
+
+    #[test]
+    fn time_exceeded() {
+        let mut out = [0u8; 128];
+        let len = error_reply(&FRAME, &mut out, Error::TimeExceeded, Some(ROUTER)).unwrap();
+        check_reply(&out, len, 60);
+        let icmp: Header = crate::read_header(&out, IP_OFFSET + 20).unwrap();
+        assert_eq!(Type::TIME_EXCEEDED, icmp.msg_type);
+        assert_eq!(0, icmp.code);
+    }
+
+    #[test]
+    fn frag_needed_in_place() {
+        let mut buf = [0u8; 128];
+        buf[..FRAME.len()].copy_from_slice(&FRAME);
+        let error = Error::FragmentationNeeded { mtu: 1400 };
+        let len = error_reply_in_place(&mut buf, FRAME.len(), error, Some(ROUTER)).unwrap();
+        check_reply(&buf, len, 60);
+        let icmp: Header = crate::read_header(&buf, IP_OFFSET + 20).unwrap();
+        assert_eq!(Type::DEST_UNREACH, icmp.msg_type);
+        assert_eq!(unreach::FRAG_NEEDED, icmp.code);
+        assert_eq!(be16::new(1400), icmp.mtu());
+    }
+
+    #[test]
+    fn truncated_quote() {
+        // Only room for the IP header and 8 bytes of payload.
+        let mut out = [0u8; QUOTE_OFFSET + 28];
+        let error = Error::DestinationUnreachable(unreach::PORT);
+        let len = error_reply(&FRAME, &mut out, error, Some(ROUTER)).unwrap();
+        check_reply(&out, len, 28);
+
+        let mut out = [0u8; QUOTE_OFFSET + 27];
+        assert!(error_reply(&FRAME, &mut out, error, Some(ROUTER)).is_err());
+    }
+
+    #[test]
+    fn source_defaults_to_destination() {
+        let mut out = [0u8; 128];
+        let error = Error::DestinationUnreachable(unreach::PORT);
+        error_reply(&FRAME, &mut out, error, None).unwrap();
+        let ip: crate::ipv4::Header = crate::read_header(&out, IP_OFFSET).unwrap();
+        assert_eq!(Ipv4Addr::new(8, 8, 8, 8), ip.source());
+    }
+
+    #[test]
+    fn suppressed() {
+        let mut out = [0u8; 128];
+        let error = Error::TimeExceeded;
+
+        let mut frame = FRAME;
+        frame[0] = 0xff;
+        assert!(error_reply(&frame, &mut out, error, None).is_err());
+
+        let mut frame = FRAME;
+        frame[IP_OFFSET + 7] = 0xb9;
+        assert!(error_reply(&frame, &mut out, error, None).is_err());
+
+        let mut frame = FRAME;
+        frame[IP_OFFSET + 9] = 1;
+        frame[IP_OFFSET + 20] = Type::TIME_EXCEEDED.val;
+        assert!(error_reply(&frame, &mut out, error, None).is_err());
+        frame[IP_OFFSET + 20] = Type::ECHO_REQUEST.val;
+        assert!(error_reply(&frame, &mut out, error, None).is_ok());
+
+        assert!(error_reply(&FRAME[..IP_OFFSET + 27], &mut out, error, None).is_err());
+    }
+}
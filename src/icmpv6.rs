@@ -0,0 +1,536 @@
+use core::mem;
+
+use crate::{be16, be32};
+
+#[repr(transparent)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Type {
+    val: u8,
+}
+
+impl Type {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    pub const DEST_UNREACH: Type = Type::new(1);
+    pub const PACKET_TOO_BIG: Type = Type::new(2);
+    pub const TIME_EXCEEDED: Type = Type::new(3);
+    pub const PARAMETER_PROBLEM: Type = Type::new(4);
+    pub const ECHO_REQUEST: Type = Type::new(128);
+    pub const ECHO_REPLY: Type = Type::new(129);
+
+    /// Returns true for error messages, which have the high-order bit of the type clear.
+    #[inline(always)]
+    pub fn is_error(&self) -> bool {
+        self.val < 128
+    }
+}
+
+/// Destination Unreachable codes.
+pub mod unreach {
+    pub const NO_ROUTE: u8 = 0;
+    pub const ADMIN_PROHIBITED: u8 = 1;
+    pub const ADDRESS: u8 = 3;
+    pub const PORT: u8 = 4;
+}
+
+/// ICMPv6 header, which is present after the IPv6 header.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Header {
+    pub msg_type: Type,
+    pub code: u8,
+    pub check: be16,
+    /// Rest of the header, whose meaning depends on the type.
+    pub rest: [u8; 4],
+}
+
+const _: () = assert!(mem::size_of::<Header>() == 8 && mem::align_of::<Header>() == 2);
+
+impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Icmpv6;
+}
+
+impl Header {
+    pub const LEN: usize = mem::size_of::<Header>();
+
+    /// Returns the identifier of an echo request or reply.
+    #[inline(always)]
+    pub fn id(&self) -> be16 {
+        [self.rest[0], self.rest[1]].into()
+    }
+
+    /// Returns the sequence number of an echo request or reply.
+    #[inline(always)]
+    pub fn seq(&self) -> be16 {
+        [self.rest[2], self.rest[3]].into()
+    }
+
+    /// Returns the MTU of a Packet Too Big message.
+    #[inline(always)]
+    pub fn mtu(&self) -> be32 {
+        self.rest.into()
+    }
+}
+
+/// Turns an ICMPv6 echo request into an echo reply in place. The Ethernet and IP addresses are
+/// swapped, the hop limit is reset to 64 and the checksum is patched incrementally. Returns `Err`
+/// without modifying anything if `icmp` is not an echo request, or if it was sent to a multicast
+/// address, which the reply would otherwise come from.
+#[inline(always)]
+pub fn echo_reply(
+    eth: &mut super::eth::Header,
+    ip: &mut super::ipv6::Header,
+    icmp: &mut Header,
+) -> Result<(), ()> {
+    if icmp.msg_type != Type::ECHO_REQUEST || icmp.code != 0 {
+        return Err(());
+    }
+    if ip.destination().is_multicast() || eth.dst_addr[0] & 1 != 0 {
+        return Err(());
+    }
+
+    core::mem::swap(&mut eth.src_addr, &mut eth.dst_addr);
+
+    // The pseudo header sum doesn't depend on the order of the addresses.
+    core::mem::swap(&mut ip.source, &mut ip.destination);
+    ip.hop_limit = 64;
+
+    icmp.msg_type = Type::ECHO_REPLY;
+    icmp.check = super::checksum::update16(
+        icmp.check,
+        be16::new((Type::ECHO_REQUEST.val as u16) << 8),
+        be16::new((Type::ECHO_REPLY.val as u16) << 8),
+    );
+    Ok(())
+}
+
+/// Same as [`echo_reply`] for the Ethernet frame in `frame`, which must hold an ICMPv6 echo
+/// request directly after the IPv6 header.
+#[inline(always)]
+pub fn echo_reply_frame(frame: core::ops::Range<*mut core::ffi::c_void>) -> Result<(), ()> {
+    use super::NextHeader;
+
+    let mut eth = super::eth::Header::from_frame(frame.clone())?;
+    if eth.ether_type != super::eth::Type::IPV6 {
+        return Err(());
+    }
+    let mut ip = eth.next_t::<super::ipv6::Header>(frame.clone())?;
+    if ip.proto != super::ip::Proto::ICMPV6 {
+        return Err(());
+    }
+    let mut icmp = ip.next_t::<Header>(frame)?;
+    echo_reply(&mut eth, &mut ip, &mut icmp)
+}
+
+/// An ICMPv6 error to send in response to a dropped IPv6 packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Time Exceeded, hop limit exceeded in transit.
+    TimeExceeded,
+    /// Destination Unreachable with one of the [`unreach`] codes.
+    DestinationUnreachable(u8),
+    /// Packet Too Big with the MTU of the next-hop link.
+    PacketTooBig { mtu: u32 },
+}
+
+impl Error {
+    #[inline(always)]
+    fn header(&self) -> Header {
+        let (msg_type, code, rest) = match *self {
+            Error::TimeExceeded => (Type::TIME_EXCEEDED, 0, [0; 4]),
+            Error::DestinationUnreachable(code) => (Type::DEST_UNREACH, code, [0; 4]),
+            Error::PacketTooBig { mtu } => (Type::PACKET_TOO_BIG, 0, mtu.to_be_bytes()),
+        };
+        Header {
+            msg_type,
+            code,
+            check: be16::ZERO,
+            rest,
+        }
+    }
+}
+
+/// Largest packet an error message may occupy, the IPv6 minimum MTU (RFC 4443, section 2.4).
+pub const MAX_ERROR_LEN: usize = 1280;
+
+const IP_OFFSET: usize = super::eth::Header::LEN;
+const QUOTE_OFFSET: usize = IP_OFFSET + super::ipv6::Header::LEN + Header::LEN;
+
+/// Most extension headers walked to find the upper-layer header of a packet.
+const MAX_EXT_HEADERS: usize = 8;
+
+/// Returns true if the IPv6 packet in `frame` is an ICMPv6 error message, looking past its
+/// extension headers. A fragment other than the first doesn't carry the ICMPv6 header and counts
+/// as not being one. Returns `Err` if the headers don't fit or there are too many of them.
+#[inline(always)]
+fn is_error_message(frame: &[u8]) -> Result<bool, ()> {
+    use super::ip::Proto;
+    use super::ipv6::{ExtHeader, FragmentHeader};
+
+    let ip: super::ipv6::Header = super::read_header(frame, IP_OFFSET)?;
+    let mut proto = ip.proto;
+    let mut offset = IP_OFFSET + super::ipv6::Header::LEN;
+    for _ in 0..=MAX_EXT_HEADERS {
+        match proto {
+            Proto::ICMPV6 => {
+                let icmp: Header = super::read_header(frame, offset)?;
+                return Ok(icmp.msg_type.is_error());
+            }
+            Proto::FRAGMENT => {
+                let frag: FragmentHeader = super::read_header(frame, offset)?;
+                if frag.offset() != 0 {
+                    return Ok(false);
+                }
+                proto = frag.next_header;
+                offset += FragmentHeader::LEN;
+            }
+            _ if proto.is_ipv6_ext() => {
+                let ext: ExtHeader = super::read_header(frame, offset)?;
+                proto = ext.next_header;
+                offset += ext.header_len();
+            }
+            _ => return Ok(false),
+        }
+    }
+    Err(())
+}
+
+/// Checks that an ICMPv6 error may be sent about the IPv6 packet in `frame` (RFC 4443, section
+/// 2.4) and returns its Ethernet and IP headers along with the number of bytes to quote.
+#[inline(always)]
+fn quotable(
+    frame: &[u8],
+    error: Error,
+) -> Result<(super::eth::Header, super::ipv6::Header, usize), ()> {
+    let eth: super::eth::Header = super::read_header(frame, 0)?;
+    if eth.ether_type != super::eth::Type::IPV6 {
+        return Err(());
+    }
+    let ip: super::ipv6::Header = super::read_header(frame, IP_OFFSET)?;
+    if ip.version() != 6 {
+        return Err(());
+    }
+    let (src, dst) = (ip.source(), ip.destination());
+    if src.is_multicast() || src.is_unspecified() || src.is_loopback() {
+        return Err(());
+    }
+    // Packet Too Big is the one error that is sent for multicast destinations.
+    let multicast = dst.is_multicast() || eth.dst_addr[0] & 1 != 0;
+    if multicast && !matches!(error, Error::PacketTooBig { .. }) {
+        return Err(());
+    }
+    if is_error_message(frame)? {
+        return Err(());
+    }
+
+    let available = frame.len() - IP_OFFSET;
+    let total = super::ipv6::Header::LEN + ip.payload_len.get() as usize;
+    let max = MAX_ERROR_LEN - super::ipv6::Header::LEN - Header::LEN;
+    Ok((eth, ip, total.min(available).min(max)))
+}
+
+/// Writes the Ethernet, IPv6 and ICMPv6 headers in front of a quote already at `QUOTE_OFFSET`.
+#[inline(always)]
+fn finish(
+    out: &mut [u8],
+    eth: &super::eth::Header,
+    ip: &super::ipv6::Header,
+    error: Error,
+    source: Option<core::net::Ipv6Addr>,
+    quote_len: usize,
+) -> Result<usize, ()> {
+    let len = QUOTE_OFFSET + quote_len;
+    if out.len() < len {
+        return Err(());
+    }
+
+    let reply_eth = super::eth::Header {
+        dst_addr: eth.src_addr,
+        src_addr: eth.dst_addr,
+        ether_type: super::eth::Type::IPV6,
+    };
+    let payload_len = (len - IP_OFFSET - super::ipv6::Header::LEN) as u16;
+    let reply_ip = super::ipv6::Header {
+        payload_len: payload_len.into(),
+        proto: super::ip::Proto::ICMPV6,
+        source: source.map_or(ip.destination, |s| s.octets()),
+        destination: ip.source,
+        ..Default::default()
+    };
+
+    let icmp_offset = IP_OFFSET + super::ipv6::Header::LEN;
+    let mut icmp = error.header();
+    super::write_header(out, icmp_offset, &icmp)?;
+    let pseudo = super::checksum::pseudo_ipv6(
+        reply_ip.source,
+        reply_ip.destination,
+        58,
+        payload_len as u32,
+    );
+    icmp.check = super::checksum::finish(super::checksum::sum(&out[icmp_offset..len], pseudo));
+    super::write_header(out, icmp_offset, &icmp)?;
+    super::write_header(out, IP_OFFSET, &reply_ip)?;
+    super::write_header(out, 0, &reply_eth)?;
+    Ok(len)
+}
+
+/// Builds the ICMPv6 error for the Ethernet + IPv6 `frame` into `out`, returning the length of
+/// the new frame. The reply is addressed back to the sender, from `source` if given or from the
+/// original destination otherwise. Returns `Err` if no error may be sent about this packet (e.g.
+/// it is itself an ICMPv6 error or was multicast) or `out` is too small to hold the IPv6 header
+/// and 8 bytes of its payload.
+#[inline(always)]
+pub fn error_reply(
+    frame: &[u8],
+    out: &mut [u8],
+    error: Error,
+    source: Option<core::net::Ipv6Addr>,
+) -> Result<usize, ()> {
+    let (eth, ip, quote_len) = quotable(frame, error)?;
+    let quote_len = quote_len.min(out.len().saturating_sub(QUOTE_OFFSET));
+    if quote_len < super::ipv6::Header::LEN + 8 {
+        return Err(());
+    }
+    out[QUOTE_OFFSET..QUOTE_OFFSET + quote_len]
+        .copy_from_slice(&frame[IP_OFFSET..IP_OFFSET + quote_len]);
+    finish(out, &eth, &ip, error, source, quote_len)
+}
+
+/// Same as [`error_reply`] but transforms the frame held in the first `len` bytes of `buf` in
+/// place. The original packet is moved back to make room for the new headers, so `buf` needs
+/// spare room after the frame; the quote is truncated to fit.
+#[inline(always)]
+pub fn error_reply_in_place(
+    buf: &mut [u8],
+    len: usize,
+    error: Error,
+    source: Option<core::net::Ipv6Addr>,
+) -> Result<usize, ()> {
+    let (eth, ip, quote_len) = quotable(buf.get(..len).ok_or(())?, error)?;
+    let quote_len = quote_len.min(buf.len().saturating_sub(QUOTE_OFFSET));
+    if quote_len < super::ipv6::Header::LEN + 8 {
+        return Err(());
+    }
+    buf.copy_within(IP_OFFSET..IP_OFFSET + quote_len, QUOTE_OFFSET);
+    finish(buf, &eth, &ip, error, source, quote_len)
+}
+
+impl core::fmt::Display for Type {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Type::DEST_UNREACH => f.write_str("destination unreachable"),
+            Type::PACKET_TOO_BIG => f.write_str("packet too big"),
+            Type::TIME_EXCEEDED => f.write_str("time exceeded"),
+            Type::PARAMETER_PROBLEM => f.write_str("parameter problem"),
+            Type::ECHO_REQUEST => f.write_str("echo request"),
+            Type::ECHO_REPLY => f.write_str("echo reply"),
+            _ => write!(f, "type {}", self.val),
+        }
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.msg_type {
+            Type::ECHO_REQUEST | Type::ECHO_REPLY => write!(
+                f,
+                "ICMP6 {}, id {}, seq {}",
+                self.msg_type,
+                self.id(),
+                self.seq()
+            ),
+            Type::PACKET_TOO_BIG => write!(f, "ICMP6 {}, mtu {}", self.msg_type, self.mtu()),
+            _ => write!(f, "ICMP6 {}, code {}", self.msg_type, self.code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::net::Ipv6Addr;
+
+    // UDP datagram from 2001:db8::2 to 2001:db8:1::1 port 33434 with hop limit 1.
+    const FRAME: [u8; 70] = [
+        0x52, 0x54, 0x00, 0x12, 0x34, 0x56, 0x52, 0x54, 0x00, 0xab, 0xcd, 0xef, 0x86,
+        0xdd, // eth
+        0x60, 0x00, 0x00, 0x00, 0x00, 0x10, 0x11, 0x01, // ip
+        0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, // source
+        0x20, 0x01, 0x0d, 0xb8, 0, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, // destination
+        0x82, 0x9b, 0x82, 0x9a, 0x00, 0x10, 0x00, 0x00, // udp
+        0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47,
+    ];
+
+    const ROUTER: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+
+    // Echo request as sent by iputils ping between two link-local addresses.
+    const ECHO_REQUEST: [u8; 118] = [
+        0x52, 0x54, 0x00, 0xa1, 0xb2, 0xc3, 0xfe, 0x54, 0x00, 0x12, 0x34, 0x56, 0x86, 0xdd, 0x60,
+        0x0a, 0x5c, 0x2f, 0x00, 0x40, 0x3a, 0x40, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xfc, 0x54, 0x00, 0xff, 0xfe, 0x12, 0x34, 0x56, 0xfe, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x50, 0x54, 0x00, 0xff, 0xfe, 0xa1, 0xb2, 0xc3, 0x80, 0x00, 0x38, 0xde, 0x2d, 0x1f,
+        0x00, 0x01, 0xc3, 0xa6, 0xf0, 0x66, 0x00, 0x00, 0x00, 0x00, 0x6c, 0x2e, 0x0b, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b,
+        0x1c, 0x1d, 0x1e, 0x1f, 0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a,
+        0x2b, 0x2c, 0x2d, 0x2e, 0x2f, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37,
+    ];
+
+    #[test]
+    fn echo() {
+        let mut frame = crate::Aligned(ECHO_REQUEST);
+        verify(&frame.0, ECHO_REQUEST.len());
+        echo_reply_frame(frame.range(ECHO_REQUEST.len())).unwrap();
+        let reply = frame.0;
+
+        assert_eq!(ECHO_REQUEST[6..12], reply[0..6]);
+        assert_eq!(ECHO_REQUEST[0..6], reply[6..12]);
+        let ip = verify(&reply, reply.len());
+        assert_eq!(ECHO_REQUEST[38..54], ip.source);
+        assert_eq!(ECHO_REQUEST[22..38], ip.destination);
+        assert_eq!(0xa5c2f, ip.flow());
+
+        let icmp: Header = crate::read_header(&reply, IP_OFFSET + 40).unwrap();
+        assert_eq!(Type::ECHO_REPLY, icmp.msg_type);
+        assert_eq!(be16::new(0x2d1f), icmp.id());
+        assert_eq!(be16::new(1), icmp.seq());
+        assert_eq!(ECHO_REQUEST[62..], reply[62..]);
+
+        assert!(echo_reply_frame(frame.range(ECHO_REQUEST.len())).is_err());
+        assert_eq!(reply, frame.0);
+    }
+
+    #[test]
+    fn echo_multicast() {
+        // Sent to the all-nodes group, and to a unicast IP address in a multicast Ethernet frame.
+        let cases: [(usize, &[u8]); 2] = [
+            (
+                IP_OFFSET + 24,
+                &[0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            ),
+            (0, &[0x33, 0x33, 0, 0, 0, 1]),
+        ];
+        for (offset, bytes) in cases {
+            let mut frame = crate::Aligned(ECHO_REQUEST);
+            frame.0[offset..offset + bytes.len()].copy_from_slice(bytes);
+            let request = frame.0;
+            assert!(echo_reply_frame(frame.range(ECHO_REQUEST.len())).is_err());
+            assert_eq!(request, frame.0);
+        }
+    }
+
+    fn verify(out: &[u8], len: usize) -> crate::ipv6::Header {
+        let ip: crate::ipv6::Header = crate::read_header(out, IP_OFFSET).unwrap();
+        let pseudo = crate::checksum::pseudo_ipv6(
+            ip.source,
+            ip.destination,
+            58,
+            ip.payload_len.get() as u32,
+        );
+        let sum = crate::checksum::sum(&out[IP_OFFSET + 40..len], pseudo);
+        assert_eq!(0xffff, crate::checksum::fold(sum));
+        ip
+    }
+
+    #[test]
+    fn time_exceeded() {
+        let mut out = [0u8; 256];
+        let len = error_reply(&FRAME, &mut out, Error::TimeExceeded, Some(ROUTER)).unwrap();
+        assert_eq!(QUOTE_OFFSET + 56, len);
+        let ip = verify(&out, len);
+        assert_eq!(ROUTER, ip.source());
+        assert_eq!(
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2),
+            ip.destination()
+        );
+        assert_eq!(crate::ip::Proto::ICMPV6, ip.proto);
+        assert_eq!(64, ip.payload_len.get());
+        assert_eq!(FRAME[6..12], out[0..6]);
+        assert_eq!(FRAME[0..6], out[6..12]);
+        assert_eq!(FRAME[IP_OFFSET..], out[QUOTE_OFFSET..len]);
+        let icmp: Header = crate::read_header(&out, IP_OFFSET + 40).unwrap();
+        assert_eq!(Type::TIME_EXCEEDED, icmp.msg_type);
+    }
+
+    #[test]
+    fn packet_too_big_in_place() {
+        let mut buf = [0u8; 256];
+        buf[..FRAME.len()].copy_from_slice(&FRAME);
+        let error = Error::PacketTooBig { mtu: 1280 };
+        let len = error_reply_in_place(&mut buf, FRAME.len(), error, Some(ROUTER)).unwrap();
+        verify(&buf, len);
+        let icmp: Header = crate::read_header(&buf, IP_OFFSET + 40).unwrap();
+        assert_eq!(Type::PACKET_TOO_BIG, icmp.msg_type);
+        assert_eq!(be32::new(1280), icmp.mtu());
+        assert_eq!(FRAME[IP_OFFSET..], buf[QUOTE_OFFSET..len]);
+    }
+
+    #[test]
+    fn suppressed() {
+        let mut out = [0u8; 256];
+
+        let mut frame = FRAME;
+        frame[IP_OFFSET + 24] = 0xff;
+        assert!(error_reply(&frame, &mut out, Error::TimeExceeded, None).is_err());
+        assert!(error_reply(&frame, &mut out, Error::PacketTooBig { mtu: 1280 }, None).is_ok());
+
+        let mut frame = FRAME;
+        frame[IP_OFFSET + 6] = 58;
+        frame[IP_OFFSET + 40] = Type::DEST_UNREACH.val;
+        let error = Error::DestinationUnreachable(unreach::PORT);
+        assert!(error_reply(&frame, &mut out, error, None).is_err());
+        frame[IP_OFFSET + 40] = Type::ECHO_REQUEST.val;
+        assert!(error_reply(&frame, &mut out, error, None).is_ok());
+
+        let mut out = [0u8; QUOTE_OFFSET + 47];
+        assert!(error_reply(&FRAME, &mut out, error, None).is_err());
+    }
+
+    #[test]
+    fn suppressed_after_extensions() {
+        let mut out = [0u8; 256];
+        let error = Error::DestinationUnreachable(unreach::PORT);
+
+        // A Destination Unreachable behind a Hop-by-Hop Options header.
+        let mut frame = [0u8; FRAME.len() + 8];
+        frame[..IP_OFFSET + 40].copy_from_slice(&FRAME[..IP_OFFSET + 40]);
+        frame[IP_OFFSET + 5] = 0x18;
+        frame[IP_OFFSET + 6] = 0;
+        frame[IP_OFFSET + 40..IP_OFFSET + 48].copy_from_slice(&[58, 0, 1, 4, 0, 0, 0, 0]);
+        frame[IP_OFFSET + 48] = Type::DEST_UNREACH.val;
+        assert!(error_reply(&frame, &mut out, error, None).is_err());
+        frame[IP_OFFSET + 48] = Type::ECHO_REQUEST.val;
+        assert!(error_reply(&frame, &mut out, error, None).is_ok());
+
+        // Behind the Fragment header of a first fragment, and of a later one which can't be told
+        // apart from any other packet.
+        frame[IP_OFFSET + 6] = 44;
+        frame[IP_OFFSET + 40..IP_OFFSET + 48].copy_from_slice(&[58, 0, 0, 1, 0, 0, 0, 7]);
+        frame[IP_OFFSET + 48] = Type::DEST_UNREACH.val;
+        assert!(error_reply(&frame, &mut out, error, None).is_err());
+        frame[IP_OFFSET + 42..IP_OFFSET + 44].copy_from_slice(&[0, 0x81]);
+        assert!(error_reply(&frame, &mut out, error, None).is_ok());
+
+        // A chain that runs past the end of the frame.
+        frame[IP_OFFSET + 6] = 0;
+        frame[IP_OFFSET + 40..IP_OFFSET + 42].copy_from_slice(&[0, 8]);
+        assert!(error_reply(&frame, &mut out, error, None).is_err());
+    }
+}
@@ -1,4 +1,10 @@
+#[repr(transparent)]
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Proto {
     val: u8,
 }
@@ -8,7 +14,210 @@ impl Proto {
         Self { val }
     }
 
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    pub const HOPOPTS: Proto = Proto::new(0);
     pub const ICMP: Proto = Proto::new(1);
     pub const TCP: Proto = Proto::new(6);
     pub const UDP: Proto = Proto::new(17);
+    pub const ROUTING: Proto = Proto::new(43);
+    pub const FRAGMENT: Proto = Proto::new(44);
+    pub const ICMPV6: Proto = Proto::new(58);
+    pub const NONE: Proto = Proto::new(59);
+    pub const DSTOPTS: Proto = Proto::new(60);
+    pub const SCTP: Proto = Proto::new(132);
+
+    /// Returns true for the IPv6 extension headers that precede the Fragment header in the
+    /// unfragmentable part of a packet and that share the generic extension header layout.
+    #[inline(always)]
+    pub fn is_ipv6_ext(&self) -> bool {
+        matches!(*self, Proto::HOPOPTS | Proto::ROUTING | Proto::DSTOPTS)
+    }
+}
+
+impl From<Proto> for u8 {
+    #[inline(always)]
+    fn from(proto: Proto) -> Self {
+        proto.val
+    }
+}
+
+/// The Explicit Congestion Notification codepoint, the low two bits of the IPv4 TOS or IPv6
+/// traffic class (RFC 3168).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Ecn {
+    val: u8,
+}
+
+impl Ecn {
+    /// Returns the codepoint in the low two bits of `val`.
+    pub const fn new(val: u8) -> Self {
+        Self { val: val & 3 }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    /// Not ECN-Capable Transport.
+    pub const NOT_ECT: Ecn = Ecn::new(0);
+    /// ECN-Capable Transport, ECT(1).
+    pub const ECT_1: Ecn = Ecn::new(1);
+    /// ECN-Capable Transport, ECT(0).
+    pub const ECT_0: Ecn = Ecn::new(2);
+    /// Congestion Experienced.
+    pub const CE: Ecn = Ecn::new(3);
+
+    /// Returns true if the transport is ECN-capable, including when congestion has already been
+    /// marked.
+    #[inline(always)]
+    pub fn is_ect(&self) -> bool {
+        *self != Ecn::NOT_ECT
+    }
+}
+
+impl core::fmt::Display for Ecn {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Ecn::NOT_ECT => f.write_str("Not-ECT"),
+            Ecn::ECT_1 => f.write_str("ECT(1)"),
+            Ecn::ECT_0 => f.write_str("ECT(0)"),
+            _ => f.write_str("CE"),
+        }
+    }
+}
+
+/// The Differentiated Services codepoint, the top six bits of the IPv4 TOS or IPv6 traffic class
+/// (RFC 2474).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Dscp {
+    val: u8,
+}
+
+impl Dscp {
+    /// Returns the codepoint `val`, or `Err` if it doesn't fit in six bits.
+    pub const fn new(val: u8) -> Result<Self, ()> {
+        match val < 64 {
+            true => Ok(Self { val }),
+            false => Err(()),
+        }
+    }
+
+    /// Returns the codepoint in the top six bits of an IPv4 TOS or IPv6 traffic class.
+    #[inline(always)]
+    pub const fn from_tos(tos: u8) -> Self {
+        Self { val: tos >> 2 }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    /// Default forwarding.
+    pub const DEFAULT: Dscp = Dscp { val: 0 };
+    /// Expedited Forwarding (RFC 3246).
+    pub const EF: Dscp = Dscp { val: 46 };
+}
+
+/// An IPv4 or IPv6 header in a frame.
+pub enum IpHeader {
+    V4(super::Ptr<super::ipv4::Header>),
+    V6(super::Ptr<super::ipv6::Header>),
+}
+
+impl IpHeader {
+    /// Returns the IP header that follows an Ethernet header, or `Err` if the EtherType is
+    /// neither IPv4 nor IPv6 or the header doesn't fit in `frame`.
+    #[inline(always)]
+    pub fn after_eth(
+        eth: &mut super::eth::Header,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<Self, ()> {
+        use super::NextHeader;
+
+        match eth.ether_type {
+            super::eth::Type::IPV4 => Ok(Self::V4(eth.next_t(frame)?)),
+            super::eth::Type::IPV6 => Ok(Self::V6(eth.next_t(frame)?)),
+            _ => Err(()),
+        }
+    }
+
+    /// Returns the Differentiated Services codepoint.
+    #[inline(always)]
+    pub fn dscp(&self) -> Dscp {
+        match self {
+            Self::V4(ip) => ip.dscp(),
+            Self::V6(ip) => ip.dscp(),
+        }
+    }
+
+    /// Sets the Differentiated Services codepoint, patching the IPv4 header checksum
+    /// incrementally.
+    #[inline(always)]
+    pub fn set_dscp(&mut self, dscp: Dscp) {
+        match self {
+            Self::V4(ip) => ip.update_dscp(dscp),
+            Self::V6(ip) => ip.set_dscp(dscp),
+        }
+    }
+
+    #[inline(always)]
+    pub fn ecn(&self) -> Ecn {
+        match self {
+            Self::V4(ip) => ip.ecn(),
+            Self::V6(ip) => ip.ecn(),
+        }
+    }
+
+    /// Sets the ECN field, patching the IPv4 header checksum incrementally.
+    #[inline(always)]
+    pub fn set_ecn(&mut self, ecn: Ecn) {
+        match self {
+            Self::V4(ip) => ip.update_ecn(ecn),
+            Self::V6(ip) => ip.set_ecn(ecn),
+        }
+    }
+
+    /// Marks Congestion Experienced, as [`ipv4::Header::mark_ce`](super::ipv4::Header::mark_ce)
+    /// and [`ipv6::Header::mark_ce`](super::ipv6::Header::mark_ce) do.
+    #[inline(always)]
+    pub fn mark_ce(&mut self) -> bool {
+        match self {
+            Self::V4(ip) => ip.mark_ce(),
+            Self::V6(ip) => ip.mark_ce(),
+        }
+    }
+}
+
+/// Result of decrementing the TTL or hop limit of a packet being forwarded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Forward {
+    /// The packet may be forwarded; the TTL or hop limit has been decremented.
+    Forward,
+    /// The TTL or hop limit would reach zero. The header is left untouched so that it can be
+    /// quoted in an ICMP Time Exceeded message.
+    Expired,
+}
+
+impl core::fmt::Display for Proto {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Proto::HOPOPTS => f.write_str("HOPOPTS"),
+            Proto::ICMP => f.write_str("ICMP"),
+            Proto::TCP => f.write_str("TCP"),
+            Proto::UDP => f.write_str("UDP"),
+            Proto::ROUTING => f.write_str("ROUTING"),
+            Proto::FRAGMENT => f.write_str("FRAGMENT"),
+            Proto::ICMPV6 => f.write_str("ICMPv6"),
+            Proto::NONE => f.write_str("NONE"),
+            Proto::DSTOPTS => f.write_str("DSTOPTS"),
+            Proto::SCTP => f.write_str("SCTP"),
+            _ => write!(f, "proto {}", self.val),
+        }
+    }
 }
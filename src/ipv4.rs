@@ -5,12 +5,18 @@ use crate::be16;
 /// IPv4 header, which is present after the Ethernet header.
 #[repr(C, packed(2))]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Header {
     pub _bitfields: u8,
     pub tos: u8,
     pub tot_len: be16,
     pub id: be16,
-    pub frag_off: be16, // TODO also has flags
+    /// Flags in the top three bits and fragment offset, in units of 8 bytes, in the rest.
+    pub frag_off: be16,
     pub ttl: u8,
     pub proto: super::ip::Proto,
     pub check: be16,
@@ -18,6 +24,8 @@ pub struct Header {
     pub destination: [u8; 4],
 }
 
+const _: () = assert!(mem::size_of::<Header>() == 20 && mem::align_of::<Header>() == 2);
+
 impl Default for Header {
     #[inline(always)]
     fn default() -> Self {
@@ -39,6 +47,81 @@ impl Default for Header {
 impl Header {
     pub const LEN: usize = mem::size_of::<Header>();
 
+    /// Don't Fragment flag in `frag_off`.
+    pub const DF: be16 = be16::new(0x4000);
+    /// More Fragments flag in `frag_off`.
+    pub const MF: be16 = be16::new(0x2000);
+    /// Mask of the fragment offset in `frag_off`.
+    pub const OFFSET_MASK: be16 = be16::new(0x1fff);
+
+    /// Returns the length of the header including options, in bytes.
+    #[inline(always)]
+    pub fn header_len(&self) -> usize {
+        (self.ihl() as usize) << 2
+    }
+
+    #[inline(always)]
+    pub fn dont_fragment(&self) -> bool {
+        self.frag_off & Self::DF != be16::ZERO
+    }
+
+    #[inline(always)]
+    pub fn more_fragments(&self) -> bool {
+        self.frag_off & Self::MF != be16::ZERO
+    }
+
+    /// Returns the fragment offset in bytes.
+    #[inline(always)]
+    pub fn fragment_offset(&self) -> u16 {
+        (self.frag_off & Self::OFFSET_MASK).get() << 3
+    }
+
+    /// Returns true if this packet is a fragment, including the first one.
+    #[inline(always)]
+    pub fn is_fragment(&self) -> bool {
+        self.frag_off & (Self::MF | Self::OFFSET_MASK) != be16::ZERO
+    }
+
+    #[inline(always)]
+    pub fn set_dont_fragment(&mut self, val: bool) {
+        self.set_flag(Self::DF, val)
+    }
+
+    #[inline(always)]
+    pub fn set_more_fragments(&mut self, val: bool) {
+        self.set_flag(Self::MF, val)
+    }
+
+    /// Sets the fragment offset given in bytes, which must be a multiple of 8.
+    #[inline(always)]
+    pub fn set_fragment_offset(&mut self, offset: u16) {
+        self.frag_off = self.frag_off & !Self::OFFSET_MASK | be16::new(offset >> 3);
+    }
+
+    #[inline(always)]
+    fn set_flag(&mut self, flag: be16, val: bool) {
+        let frag_off = self.frag_off & !flag;
+        self.frag_off = match val {
+            true => frag_off | flag,
+            false => frag_off,
+        };
+    }
+
+    /// Computes the header checksum, assuming a header without options.
+    #[inline(always)]
+    pub fn compute_checksum(&self) -> be16 {
+        let mut h = *self;
+        h.check = be16::ZERO;
+        let bytes: [u8; Self::LEN] = unsafe { mem::transmute(h) };
+        super::checksum::checksum(&bytes)
+    }
+
+    /// Sets the checksum field, assuming a header without options.
+    #[inline(always)]
+    pub fn update_checksum(&mut self) {
+        self.check = self.compute_checksum();
+    }
+
     #[inline(always)]
     pub fn ihl(&self) -> u8 {
         self._bitfields & 0xf
@@ -54,6 +137,83 @@ impl Header {
         self._bitfields >> 4
     }
 
+    /// Returns the Differentiated Services codepoint, the top six bits of `tos`.
+    #[inline(always)]
+    pub fn dscp(&self) -> super::ip::Dscp {
+        super::ip::Dscp::from_tos(self.tos)
+    }
+
+    /// Sets the Differentiated Services codepoint, keeping the ECN bits. The checksum is not
+    /// updated.
+    #[inline(always)]
+    pub fn set_dscp(&mut self, dscp: super::ip::Dscp) {
+        self.tos = dscp.get() << 2 | self.tos & 3;
+    }
+
+    /// Returns the ECN codepoint, the low two bits of `tos`.
+    #[inline(always)]
+    pub fn ecn(&self) -> super::ip::Ecn {
+        super::ip::Ecn::new(self.tos)
+    }
+
+    /// Sets the ECN codepoint, keeping the DSCP. The checksum is not updated.
+    #[inline(always)]
+    pub fn set_ecn(&mut self, ecn: super::ip::Ecn) {
+        self.tos = self.tos & !3 | ecn.get();
+    }
+
+    /// Marks Congestion Experienced on an ECN-capable packet, patching the checksum
+    /// incrementally, and returns true. Returns false without modifying the header if the packet
+    /// is Not-ECT, in which case a queue that would have marked it should drop it instead.
+    #[inline(always)]
+    pub fn mark_ce(&mut self) -> bool {
+        if !self.ecn().is_ect() {
+            return false;
+        }
+        self.update_ecn(super::ip::Ecn::CE);
+        true
+    }
+
+    /// Sets the ECN codepoint, keeping the DSCP and patching the checksum incrementally.
+    #[inline(always)]
+    pub fn update_ecn(&mut self, ecn: super::ip::Ecn) {
+        self.update_tos(self.tos & !3 | ecn.get());
+    }
+
+    /// Sets the Differentiated Services codepoint, keeping the ECN bits and patching the checksum
+    /// incrementally.
+    #[inline(always)]
+    pub fn update_dscp(&mut self, dscp: super::ip::Dscp) {
+        self.update_tos(dscp.get() << 2 | self.tos & 3);
+    }
+
+    /// Sets the TOS field, patching the checksum incrementally.
+    #[inline(always)]
+    pub fn update_tos(&mut self, tos: u8) {
+        // TOS is the low byte of the word it shares with the version and IHL.
+        let old = be16::new(u16::from_be_bytes([self._bitfields, self.tos]));
+        self.tos = tos;
+        let new = be16::new(u16::from_be_bytes([self._bitfields, tos]));
+        self.check = super::checksum::update16(self.check, old, new);
+    }
+
+    /// Decrements the TTL of a packet being forwarded, patching the checksum incrementally. A
+    /// packet whose TTL is 0 or 1 must not be forwarded and is reported as
+    /// [`Expired`](super::ip::Forward::Expired) without modifying the header.
+    #[inline(always)]
+    pub fn decrement_ttl(&mut self) -> super::ip::Forward {
+        if self.ttl <= 1 {
+            return super::ip::Forward::Expired;
+        }
+        // TTL is the high byte of the word it shares with the protocol, so decrementing it
+        // subtracts 0x100 from that word.
+        let old = be16::new((self.ttl as u16) << 8);
+        let new = be16::new(((self.ttl - 1) as u16) << 8);
+        self.ttl -= 1;
+        self.check = super::checksum::update16(self.check, old, new);
+        super::ip::Forward::Forward
+    }
+
     /// Returns the source address field.
     #[inline(always)]
     pub fn source(&self) -> core::net::Ipv4Addr {
@@ -80,6 +240,23 @@ impl Header {
 }
 
 impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Ipv4;
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.header_len()
+    }
+
+    /// Checks the version and that the IHL covers at least the fixed header.
+    #[inline(always)]
+    fn validate(&self, remaining: usize) -> Result<(), ()> {
+        match self.version() == 4 && (Self::LEN..=remaining).contains(&self.header_len()) {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
 impl super::AutoNextHeader for Header {
     #[inline(always)]
     fn next(
@@ -95,22 +272,200 @@ impl super::AutoNextHeader for Header {
             super::ip::Proto::UDP => Ok(super::HeaderPtr::Udp(
                 self.next_t::<super::udp::Header>(frame)?,
             )),
+            super::ip::Proto::ICMP => Ok(super::HeaderPtr::Icmp(
+                self.next_t::<super::icmp::Header>(frame)?,
+            )),
+            super::ip::Proto::SCTP => Ok(super::HeaderPtr::Sctp(
+                self.next_t::<super::sctp::Header>(frame)?,
+            )),
             _ => Ok(super::HeaderPtr::Unhandled()),
         }
     }
 }
 
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let flags = match (self.dont_fragment(), self.more_fragments()) {
+            (true, true) => "DF+",
+            (true, false) => "DF",
+            (false, true) => "+",
+            (false, false) => "none",
+        };
+        let (proto, id, tot_len) = (self.proto, self.id, self.tot_len);
+        write!(
+            f,
+            "{} > {}: {}, tos 0x{:x}, ttl {}, id {}, offset {}, flags [{}], length {}",
+            self.source(),
+            self.destination(),
+            proto,
+            self.tos,
+            self.ttl,
+            id,
+            self.fragment_offset(),
+            flags,
+            tot_len
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn fragment_fields() {
+        let mut h = Header::default();
+        assert!(!h.is_fragment());
+        h.set_dont_fragment(true);
+        assert!(h.dont_fragment());
+        assert!(!h.is_fragment());
+        h.set_more_fragments(true);
+        h.set_fragment_offset(1480);
+        assert_eq!(be16::new(0x60b9), h.frag_off);
+        assert_eq!(1480, h.fragment_offset());
+        assert!(h.is_fragment());
+        h.set_dont_fragment(false);
+        h.set_more_fragments(false);
+        assert_eq!(be16::new(0x00b9), h.frag_off);
+        assert!(h.is_fragment());
+        assert_eq!(20, h.header_len());
+    }
+
+    #[test]
+    fn next_past_options() {
+        use crate::{Header as _, NextHeader};
+
+        let mut buf = crate::Aligned([0u8; 24 + 8]);
+        let mut h = Header {
+            proto: crate::ip::Proto::UDP,
+            ..Default::default()
+        };
+        h.set_ihl(6);
+        crate::write_header(&mut buf.0, 0, &h).unwrap();
+        let udp = crate::udp::Header {
+            source: 53.into(),
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 24, &udp).unwrap();
+
+        let r = buf.range(32);
+        let mut ip = crate::Ptr::new(r.start as *mut Header);
+        assert_eq!(24, ip.len());
+        let udp = ip.next_t::<crate::udp::Header>(r.clone()).unwrap();
+        assert_eq!(24, udp.offset(r.clone()));
+        assert_eq!(53, udp.source.get());
+        assert!(ip.next_t::<crate::udp::Header>(buf.range(31)).is_err());
+
+        // Options running past the frame, an IHL below 5 and the wrong version.
+        assert!(ip.validate(23).is_err());
+        ip.set_ihl(4);
+        assert!(ip.validate(32).is_err());
+        ip._bitfields = 0x65;
+        assert!(ip.validate(32).is_err());
+        assert!(ip.next_t::<crate::udp::Header>(r).is_err());
+    }
 
     #[test]
-    fn size() {
-        assert_eq!(20, Header::LEN);
+    fn dscp_ecn() {
+        use crate::ip::{Dscp, Ecn};
+
+        let mut h = Header {
+            ttl: 64,
+            ..Default::default()
+        };
+        h.set_dscp(Dscp::EF);
+        h.set_ecn(Ecn::ECT_0);
+        assert_eq!(0xba, h.tos);
+        assert_eq!(Dscp::EF, h.dscp());
+        assert_eq!(Ecn::ECT_0, h.ecn());
+        h.set_dscp(Dscp::new(10).unwrap());
+        assert_eq!(Ecn::ECT_0, h.ecn());
+
+        h.update_checksum();
+        assert!(h.mark_ce());
+        assert_eq!(Ecn::CE, h.ecn());
+        assert_eq!(10, h.dscp().get());
+        assert_eq!(h.compute_checksum(), h.check);
+        assert!(h.mark_ce());
+        assert_eq!(h.compute_checksum(), h.check);
+
+        h.set_ecn(Ecn::NOT_ECT);
+        h.update_checksum();
+        assert!(!h.mark_ce());
+        assert_eq!(Ecn::NOT_ECT, h.ecn());
+        assert_eq!(h.compute_checksum(), h.check);
+    }
+
+    fn bytes(h: &Header) -> [u8; Header::LEN] {
+        unsafe { core::mem::transmute(*h) }
     }
 
     #[test]
-    fn alignment() {
-        assert_eq!(2, mem::align_of::<Header>());
+    fn decrement_ttl() {
+        // ICMP echo request from 192.168.122.1 to 192.168.122.10.
+        let mut h: Header = unsafe {
+            core::mem::transmute([
+                0x45u8, 0x00, 0x00, 0x54, 0x3c, 0x5b, 0x40, 0x00, 0x40, 0x01, 0x88, 0xf1, 0xc0,
+                0xa8, 0x7a, 0x01, 0xc0, 0xa8, 0x7a, 0x0a,
+            ])
+        };
+        assert!(crate::checksum::verify(&bytes(&h)));
+        assert_eq!(h.check, h.compute_checksum());
+
+        for ttl in (1..64).rev() {
+            assert_eq!(crate::ip::Forward::Forward, h.decrement_ttl());
+            assert_eq!(ttl, h.ttl);
+            assert!(crate::checksum::verify(&bytes(&h)));
+        }
+        assert_eq!(1, h.ttl);
+        let check = h.check;
+        assert_eq!(crate::ip::Forward::Expired, h.decrement_ttl());
+        assert_eq!(1, h.ttl);
+        assert_eq!(check, h.check);
+
+        h.ttl = 0;
+        assert_eq!(crate::ip::Forward::Expired, h.decrement_ttl());
+    }
+
+    proptest! {
+        #[test]
+        fn accessors(
+            offset in (0u16..8192).prop_map(|o| o << 3),
+            df: bool,
+            mf: bool,
+            ihl in 0u8..16,
+            source: [u8; 4],
+            destination: [u8; 4],
+        ) {
+            let mut h = Header::default();
+            h.set_fragment_offset(offset);
+            h.set_dont_fragment(df);
+            h.set_more_fragments(mf);
+            h.set_ihl(ihl);
+            h.set_source(source.into());
+            h.set_destination(destination.into());
+            prop_assert_eq!(offset, h.fragment_offset());
+            prop_assert_eq!(df, h.dont_fragment());
+            prop_assert_eq!(mf, h.more_fragments());
+            prop_assert_eq!(mf || offset != 0, h.is_fragment());
+            prop_assert_eq!(ihl, h.ihl());
+            prop_assert_eq!(4, h.version());
+            prop_assert_eq!(source, h.source().octets());
+            prop_assert_eq!(destination, h.destination().octets());
+        }
+
+        #[test]
+        fn checksum(raw: [u8; Header::LEN]) {
+            let mut h: Header = unsafe { core::mem::transmute(raw) };
+            h.update_checksum();
+            prop_assert!(crate::checksum::verify(&bytes(&h)));
+            let ttl = h.ttl;
+            match h.decrement_ttl() {
+                crate::ip::Forward::Forward => prop_assert_eq!(ttl - 1, h.ttl),
+                crate::ip::Forward::Expired => prop_assert!(ttl <= 1),
+            }
+            prop_assert!(crate::checksum::verify(&bytes(&h)));
+        }
     }
 }
@@ -1,9 +1,14 @@
-use crate::be16;
+use crate::{be16, be32};
 use core::{fmt::Debug, mem};
 
 /// IPv4 header, which is present after the Ethernet header.
 #[repr(C, packed(2))]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Header {
     pub _bitfields: [u8; 4],
     pub payload_len: be16,
@@ -13,6 +18,8 @@ pub struct Header {
     pub destination: [u8; 16],
 }
 
+const _: () = assert!(mem::size_of::<Header>() == 40 && mem::align_of::<Header>() == 2);
+
 impl Default for Header {
     #[inline(always)]
     fn default() -> Self {
@@ -30,13 +37,76 @@ impl Default for Header {
 impl Header {
     pub const LEN: usize = mem::size_of::<Header>();
 
+    /// Returns a default header whose payload length covers the rest of `frame` after this one,
+    /// or `Err` if the header doesn't fit in the frame or the payload is too long for
+    /// `payload_len`, in which case it has to be sent as a jumbogram with a [`JumboHeader`].
     #[inline(always)]
-    pub fn from_frame(&self, frame: core::ops::Range<*mut core::ffi::c_void>) -> Self {
-        let len = (frame.end as usize - self as *const Header as usize - Self::LEN) as u16;
-        Self {
-            payload_len: len.into(),
+    pub fn from_frame(&self, frame: core::ops::Range<*mut core::ffi::c_void>) -> Result<Self, ()> {
+        let len = (frame.end as usize)
+            .checked_sub(self as *const Header as usize + Self::LEN)
+            .ok_or(())?;
+        Ok(Self {
+            payload_len: u16::try_from(len).map_err(|_| ())?.into(),
             ..Self::default()
+        })
+    }
+
+    /// Returns the length of the payload after this header, checking the version and that the
+    /// payload fits in `frame`, which may hold trailing padding after it. A `payload_len` of 0
+    /// after a Hop-by-Hop Options header is a jumbogram, whose length is taken from the Jumbo
+    /// Payload option and must be more than 65535 (RFC 2675).
+    #[inline(always)]
+    pub fn checked_payload_len(
+        &self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<u32, ()> {
+        if self.version() != 6 {
+            return Err(());
+        }
+        let remaining = super::frame_from(self, frame.clone()).ok_or(())?.len() - Self::LEN;
+        let len = match (self.payload_len.get(), self.jumbo_payload_len(frame)) {
+            (0, Some(jumbo)) if jumbo > u16::MAX as u32 => jumbo,
+            (0, None) if self.proto != super::ip::Proto::HOPOPTS => 0,
+            (len, None) if len != 0 => len as u32,
+            _ => return Err(()),
+        };
+        match len as usize <= remaining {
+            true => Ok(len),
+            false => Err(()),
+        }
+    }
+
+    /// Returns the length from the Jumbo Payload option if this header is followed by a
+    /// Hop-by-Hop Options header holding one, or `None` if there is none, the options are
+    /// truncated or this header isn't in `frame`.
+    #[inline(always)]
+    pub fn jumbo_payload_len(
+        &self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Option<u32> {
+        if self.proto != super::ip::Proto::HOPOPTS {
+            return None;
+        }
+        let bytes = &super::frame_from(self, frame)?[Self::LEN..];
+        let len = (*bytes.get(1)? as usize + 1) * 8;
+        let options = bytes.get(ExtHeader::LEN..len)?;
+
+        let mut i = 0;
+        while i < options.len() {
+            match options[i] {
+                // Pad1 has no length byte.
+                0 => i += 1,
+                JumboHeader::OPTION_TYPE => {
+                    let value = options.get(i + 2..i + 6)?;
+                    return match options[i + 1] {
+                        4 => Some(u32::from_be_bytes([value[0], value[1], value[2], value[3]])),
+                        _ => None,
+                    };
+                }
+                _ => i += 2 + *options.get(i + 1)? as usize,
+            }
         }
+        None
     }
 
     #[inline(always)]
@@ -57,6 +127,42 @@ impl Header {
         bits[1] = bits[1] & 0x0f | val << 4;
     }
 
+    /// Returns the Differentiated Services codepoint, the top six bits of the traffic class.
+    #[inline(always)]
+    pub fn dscp(&self) -> super::ip::Dscp {
+        super::ip::Dscp::from_tos(self.priority())
+    }
+
+    /// Sets the Differentiated Services codepoint, keeping the ECN bits.
+    #[inline(always)]
+    pub fn set_dscp(&mut self, dscp: super::ip::Dscp) {
+        self.set_priority(dscp.get() << 2 | self.priority() & 3);
+    }
+
+    /// Returns the ECN codepoint, the low two bits of the traffic class.
+    #[inline(always)]
+    pub fn ecn(&self) -> super::ip::Ecn {
+        super::ip::Ecn::new(self.priority())
+    }
+
+    /// Sets the ECN codepoint, keeping the DSCP.
+    #[inline(always)]
+    pub fn set_ecn(&mut self, ecn: super::ip::Ecn) {
+        self.set_priority(self.priority() & !3 | ecn.get());
+    }
+
+    /// Marks Congestion Experienced on an ECN-capable packet and returns true. Returns false
+    /// without modifying the header if the packet is Not-ECT, in which case a queue that would
+    /// have marked it should drop it instead.
+    #[inline(always)]
+    pub fn mark_ce(&mut self) -> bool {
+        if !self.ecn().is_ect() {
+            return false;
+        }
+        self.set_ecn(super::ip::Ecn::CE);
+        true
+    }
+
     #[inline(always)]
     pub fn flow(&self) -> u32 {
         u32::from_be_bytes([
@@ -76,6 +182,18 @@ impl Header {
         bits[3] = bytes[3];
     }
 
+    /// Decrements the hop limit of a packet being forwarded. A packet whose hop limit is 0 or 1
+    /// must not be forwarded and is reported as [`Expired`](super::ip::Forward::Expired) without
+    /// modifying the header.
+    #[inline(always)]
+    pub fn decrement_hop_limit(&mut self) -> super::ip::Forward {
+        if self.hop_limit <= 1 {
+            return super::ip::Forward::Expired;
+        }
+        self.hop_limit -= 1;
+        super::ip::Forward::Forward
+    }
+
     /// Returns the source address field.
     #[inline(always)]
     pub fn source(&self) -> core::net::Ipv6Addr {
@@ -101,7 +219,172 @@ impl Header {
     }
 }
 
+/// IPv6 Fragment extension header (RFC 8200, section 4.5).
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct FragmentHeader {
+    pub next_header: super::ip::Proto,
+    pub _reserved: u8,
+    /// Fragment offset, in units of 8 bytes, in the top 13 bits and the M flag in the lowest.
+    pub frag_off: be16,
+    pub identification: be32,
+}
+
+const _: () =
+    assert!(mem::size_of::<FragmentHeader>() == 8 && mem::align_of::<FragmentHeader>() == 2);
+
+impl FragmentHeader {
+    pub const LEN: usize = mem::size_of::<FragmentHeader>();
+
+    /// Returns the fragment offset in bytes.
+    #[inline(always)]
+    pub fn offset(&self) -> u16 {
+        self.frag_off.get() & 0xfff8
+    }
+
+    /// Sets the fragment offset given in bytes, which must be a multiple of 8.
+    #[inline(always)]
+    pub fn set_offset(&mut self, offset: u16) {
+        self.frag_off = be16::new(offset & 0xfff8 | self.frag_off.get() & 1);
+    }
+
+    #[inline(always)]
+    pub fn more_fragments(&self) -> bool {
+        self.frag_off.get() & 1 != 0
+    }
+
+    #[inline(always)]
+    pub fn set_more_fragments(&mut self, val: bool) {
+        self.frag_off = be16::new(self.frag_off.get() & 0xfff8 | val as u16);
+    }
+
+    /// Returns true for an atomic fragment (RFC 6946), which is a whole packet that carries a
+    /// Fragment header and must be processed on its own.
+    #[inline(always)]
+    pub fn is_atomic(&self) -> bool {
+        self.offset() == 0 && !self.more_fragments()
+    }
+}
+
+impl super::NextHeader for FragmentHeader {}
+impl super::Header for FragmentHeader {
+    const PROTOCOL: super::Protocol = super::Protocol::Ipv6Fragment;
+}
+
+/// The two fields common to the Hop-by-Hop, Routing and Destination Options headers.
+#[repr(C, packed(1))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct ExtHeader {
+    pub next_header: super::ip::Proto,
+    /// Length of the header in 8 byte units, not counting the first 8 bytes.
+    pub hdr_ext_len: u8,
+}
+
+const _: () = assert!(mem::size_of::<ExtHeader>() == 2 && mem::align_of::<ExtHeader>() == 1);
+
+impl ExtHeader {
+    pub const LEN: usize = mem::size_of::<ExtHeader>();
+
+    /// Returns the length of the whole extension header in bytes.
+    #[inline(always)]
+    pub fn header_len(&self) -> usize {
+        (self.hdr_ext_len as usize + 1) * 8
+    }
+}
+
+/// A Hop-by-Hop Options header holding only the Jumbo Payload option, which carries the payload
+/// length of a jumbogram whose IPv6 `payload_len` is 0 (RFC 2675).
+#[repr(C, packed(2))]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct JumboHeader {
+    pub next_header: super::ip::Proto,
+    pub hdr_ext_len: u8,
+    pub opt_type: u8,
+    pub opt_len: u8,
+    /// Length of the packet after the IPv6 header, including this header.
+    pub jumbo_len: be32,
+}
+
+const _: () = assert!(mem::size_of::<JumboHeader>() == 8 && mem::align_of::<JumboHeader>() == 2);
+
+impl JumboHeader {
+    pub const LEN: usize = mem::size_of::<JumboHeader>();
+    /// Option type of the Jumbo Payload option.
+    pub const OPTION_TYPE: u8 = 0xc2;
+
+    #[inline(always)]
+    pub fn new(next_header: super::ip::Proto, jumbo_len: u32) -> Self {
+        Self {
+            next_header,
+            hdr_ext_len: 0,
+            opt_type: Self::OPTION_TYPE,
+            opt_len: 4,
+            jumbo_len: jumbo_len.into(),
+        }
+    }
+
+    /// Returns true if the header holds exactly one Jumbo Payload option with a length that
+    /// needs it.
+    #[inline(always)]
+    pub fn is_valid(&self) -> bool {
+        self.hdr_ext_len == 0
+            && self.opt_type == Self::OPTION_TYPE
+            && self.opt_len == 4
+            && self.jumbo_len.get() > u16::MAX as u32
+    }
+}
+
+impl super::NextHeader for JumboHeader {}
+impl super::Header for JumboHeader {
+    const PROTOCOL: super::Protocol = super::Protocol::Ipv6Ext;
+
+    #[inline(always)]
+    fn validate(&self, remaining: usize) -> Result<(), ()> {
+        match self.is_valid() && Self::LEN <= remaining {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
+
+impl super::NextHeader for ExtHeader {}
+impl super::Header for ExtHeader {
+    const PROTOCOL: super::Protocol = super::Protocol::Ipv6Ext;
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.header_len()
+    }
+}
+
 impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Ipv6;
+
+    /// Checks the version and that the fixed header fits.
+    #[inline(always)]
+    fn validate(&self, remaining: usize) -> Result<(), ()> {
+        match self.version() == 6 && Self::LEN <= remaining {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
 impl super::AutoNextHeader for Header {
     #[inline(always)]
     fn next(
@@ -117,23 +400,70 @@ impl super::AutoNextHeader for Header {
             super::ip::Proto::UDP => Ok(super::HeaderPtr::Udp(
                 self.next_t::<super::udp::Header>(frame)?,
             )),
+            super::ip::Proto::ICMPV6 => Ok(super::HeaderPtr::Icmpv6(
+                self.next_t::<super::icmpv6::Header>(frame)?,
+            )),
+            super::ip::Proto::SCTP => Ok(super::HeaderPtr::Sctp(
+                self.next_t::<super::sctp::Header>(frame)?,
+            )),
+            super::ip::Proto::FRAGMENT => Ok(super::HeaderPtr::Ipv6Fragment(
+                self.next_t::<FragmentHeader>(frame)?,
+            )),
             _ => Ok(super::HeaderPtr::Unhandled()),
         }
     }
 }
 
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (proto, payload_len) = (self.proto, self.payload_len);
+        write!(
+            f,
+            "{} > {}: {}, class 0x{:02x}, flowlabel 0x{:05x}, hlim {}, payload length {}",
+            self.source(),
+            self.destination(),
+            proto,
+            self.priority(),
+            self.flow(),
+            self.hop_limit,
+            payload_len
+        )
+    }
+}
+
+impl core::fmt::Display for FragmentHeader {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (next_header, identification) = (self.next_header, self.identification);
+        write!(
+            f,
+            "frag (0x{:08x}:{}{}), next {}",
+            identification,
+            self.offset(),
+            if self.more_fragments() { "+" } else { "" },
+            next_header
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
-    fn size() {
-        assert_eq!(40, Header::LEN);
-    }
-
-    #[test]
-    fn alignment() {
-        assert_eq!(2, mem::align_of::<Header>());
+    fn fragment_header() {
+        assert_eq!(8, FragmentHeader::LEN);
+        let mut f = FragmentHeader::default();
+        assert!(f.is_atomic());
+        f.set_more_fragments(true);
+        f.set_offset(1448);
+        assert_eq!(be16::new(0x05a9), f.frag_off);
+        assert_eq!(1448, f.offset());
+        assert!(f.more_fragments());
+        assert!(!f.is_atomic());
+        f.set_more_fragments(false);
+        assert_eq!(1448, f.offset());
+        assert!(!f.is_atomic());
     }
 
     #[test]
@@ -142,6 +472,18 @@ mod tests {
         assert_eq!(6, ipv6.version())
     }
 
+    #[test]
+    fn decrement_hop_limit() {
+        let mut ipv6 = Header {
+            hop_limit: 2,
+            ..Header::default()
+        };
+        assert_eq!(crate::ip::Forward::Forward, ipv6.decrement_hop_limit());
+        assert_eq!(1, ipv6.hop_limit);
+        assert_eq!(crate::ip::Forward::Expired, ipv6.decrement_hop_limit());
+        assert_eq!(1, ipv6.hop_limit);
+    }
+
     #[test]
     fn priority() {
         let mut ipv6 = Header::default();
@@ -155,6 +497,96 @@ mod tests {
         assert_eq!(0xe2, ipv6.priority());
     }
 
+    #[test]
+    fn dscp_ecn() {
+        use crate::ip::{Dscp, Ecn};
+
+        let mut ipv6 = Header::default();
+        ipv6.set_flow(0x71d11);
+        ipv6.set_dscp(Dscp::EF);
+        ipv6.set_ecn(Ecn::ECT_1);
+        assert_eq!(0xb9, ipv6.priority());
+        assert_eq!(Dscp::EF, ipv6.dscp());
+        assert_eq!(Ecn::ECT_1, ipv6.ecn());
+        assert!(ipv6.mark_ce());
+        assert_eq!(Ecn::CE, ipv6.ecn());
+        assert_eq!(Dscp::EF, ipv6.dscp());
+        assert_eq!(0x71d11, ipv6.flow());
+        assert_eq!(6, ipv6.version());
+
+        ipv6.set_ecn(Ecn::NOT_ECT);
+        assert!(!ipv6.mark_ce());
+        assert_eq!(0xb8, ipv6.priority());
+    }
+
+    #[test]
+    fn payload_len() {
+        let mut buf = crate::Aligned([0u8; 40 + 16]);
+        let r = buf.range(56);
+        let ip = crate::Ptr::new(r.start as *mut Header);
+        let h = ip.from_frame(r.clone()).unwrap();
+        assert_eq!(16, h.payload_len.get());
+        assert!(ip.from_frame(buf.range(39)).is_err());
+
+        crate::write_header(&mut buf.0, 0, &h).unwrap();
+        let ip = crate::Ptr::new(r.start as *mut Header);
+        assert_eq!(Ok(16), ip.checked_payload_len(r.clone()));
+        // Trailing padding is allowed, a truncated payload isn't.
+        assert_eq!(Ok(10), {
+            let mut h = h;
+            h.payload_len = 10.into();
+            crate::write_header(&mut buf.0, 0, &h).unwrap();
+            ip.checked_payload_len(r.clone())
+        });
+        assert!(ip.checked_payload_len(buf.range(49)).is_err());
+
+        let mut v4 = h;
+        v4._bitfields[0] = 0x40;
+        crate::write_header(&mut buf.0, 0, &v4).unwrap();
+        assert!(ip.checked_payload_len(r).is_err());
+    }
+
+    #[test]
+    fn jumbogram() {
+        let mut buf = crate::Aligned([0u8; 40 + 8 + 8]);
+        let h = Header {
+            proto: crate::ip::Proto::HOPOPTS,
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 0, &h).unwrap();
+        let jumbo = JumboHeader::new(crate::ip::Proto::UDP, 0x10000);
+        assert!(jumbo.is_valid());
+        crate::write_header(&mut buf.0, 40, &jumbo).unwrap();
+
+        let r = buf.range(56);
+        let ip = crate::Ptr::new(r.start as *mut Header);
+        assert_eq!(Some(0x10000), ip.jumbo_payload_len(r.clone()));
+        // The frame holds only the start of the jumbogram.
+        assert!(ip.checked_payload_len(r.clone()).is_err());
+        assert!(ip.jumbo_payload_len(buf.range(47)).is_none());
+        // A copy isn't in the frame, so the option after it can't be read.
+        let copy = *ip;
+        assert!(copy.jumbo_payload_len(r.clone()).is_none());
+        assert!(copy.checked_payload_len(r.clone()).is_err());
+
+        let mut big = std::vec![0u64; (40 + 0x10000) / 8];
+        let start = big.as_mut_ptr() as *mut core::ffi::c_void;
+        let whole = start..unsafe { start.add(40 + 0x10000) };
+        unsafe { core::ptr::copy_nonoverlapping(buf.0.as_ptr(), start as *mut u8, 48) };
+        let big_ip = crate::Ptr::new(start as *mut Header);
+        assert_eq!(Ok(0x10000), big_ip.checked_payload_len(whole));
+
+        // The option after padding, with a length that doesn't need a jumbogram.
+        buf.0[40..56].copy_from_slice(&[17, 1, 0, 1, 1, 0, 0xc2, 4, 0, 0, 0, 16, 1, 2, 0, 0]);
+        assert_eq!(Some(16), ip.jumbo_payload_len(r.clone()));
+        assert!(ip.checked_payload_len(r.clone()).is_err());
+
+        // A payload_len of 0 with no Jumbo Payload option.
+        unsafe { *(r.start as *mut u8).add(46) = 1 };
+        assert_eq!(None, ip.jumbo_payload_len(r.clone()));
+        assert!(ip.checked_payload_len(r).is_err());
+    }
+
     #[test]
     fn flow_label() {
         let mut ipv6 = Header::default();
@@ -172,4 +604,42 @@ mod tests {
         assert_eq!(0x8e2ee, ipv6.flow());
         assert_eq!(0x1d, ipv6.priority());
     }
+
+    proptest! {
+        #[test]
+        fn accessors(raw: [u8; Header::LEN], class: u8, flow in 0u32..1 << 20) {
+            let mut ipv6: Header = unsafe { core::mem::transmute(raw) };
+            let version = ipv6.version();
+            ipv6.set_priority(class);
+            ipv6.set_flow(flow);
+            prop_assert_eq!(class, ipv6.priority());
+            prop_assert_eq!(flow, ipv6.flow());
+            prop_assert_eq!(version, ipv6.version());
+            ipv6.set_priority(!class);
+            prop_assert_eq!(flow, ipv6.flow());
+        }
+
+        #[test]
+        fn addresses(source: [u8; 16], destination: [u8; 16]) {
+            let mut ipv6 = Header::default();
+            ipv6.set_source(source.into());
+            ipv6.set_destination(destination.into());
+            prop_assert_eq!(source, ipv6.source().octets());
+            prop_assert_eq!(destination, ipv6.destination().octets());
+        }
+
+        #[test]
+        fn fragment_accessors(offset in (0u16..8192).prop_map(|o| o << 3), more: bool, id: u32) {
+            let mut f = FragmentHeader {
+                identification: id.into(),
+                ..Default::default()
+            };
+            f.set_offset(offset);
+            f.set_more_fragments(more);
+            prop_assert_eq!(offset, f.offset());
+            prop_assert_eq!(more, f.more_fragments());
+            prop_assert_eq!(offset == 0 && !more, f.is_atomic());
+            prop_assert_eq!(id, f.identification.get());
+        }
+    }
 }
@@ -1,113 +1,230 @@
 #![no_std]
+// Header accessors signal a frame that is too short with `Err(())`, which is all an XDP program
+// can act on anyway.
+#![allow(clippy::result_unit_err)]
 
-#[allow(non_camel_case_types)]
-#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
-pub struct be16 {
-    val: u16,
-}
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
 
-impl be16 {
-    #[inline(always)]
-    pub const fn new(value: u16) -> Self {
-        Self { val: value.to_be() }
-    }
+/// Converts a network-order value into its host-order representation.
+pub trait ToHost {
+    type Host;
+
+    fn to_host(self) -> Self::Host;
 }
 
-impl From<u16> for be16 {
-    #[inline(always)]
-    fn from(value: u16) -> Self {
-        Self { val: value.to_be() }
-    }
+/// Builds a network-order value from its host-order representation.
+pub trait FromHost: ToHost {
+    fn from_host(value: Self::Host) -> Self;
 }
 
-impl From<be16> for u16 {
+impl ToHost for u8 {
+    type Host = u8;
+
     #[inline(always)]
-    fn from(be: be16) -> Self {
-        u16::from_be(be.val)
+    fn to_host(self) -> u8 {
+        self
     }
 }
 
-impl From<be16> for [u8; 2] {
+impl FromHost for u8 {
     #[inline(always)]
-    fn from(value: be16) -> Self {
-        value.val.to_ne_bytes()
+    fn from_host(value: u8) -> Self {
+        value
     }
 }
 
-impl From<[u8; 2]> for be16 {
-    #[inline(always)]
-    fn from(value: [u8; 2]) -> Self {
-        be16 {
-            val: u16::from_ne_bytes(value),
+/// Defines a big-endian integer type that stores its value in network byte order. Equality and
+/// bitwise operations work directly on the stored bytes while ordering, arithmetic and formatting
+/// use the host-order value.
+macro_rules! be_int {
+    ($name:ident, $host:ty, $bytes:literal) => {
+        #[allow(non_camel_case_types)]
+        #[repr(transparent)]
+        #[derive(Debug, Default, PartialEq, Eq, Hash, Copy, Clone)]
+        #[cfg_attr(
+            feature = "zerocopy",
+            derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+        )]
+        #[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+        pub struct $name {
+            val: $host,
         }
-    }
-}
 
-impl core::ops::BitXor for be16 {
-    type Output = be16;
+        impl $name {
+            pub const ZERO: $name = $name::new(0);
+            pub const MAX: $name = $name::new(<$host>::MAX);
 
-    #[inline(always)]
-    fn bitxor(self, rhs: Self) -> Self::Output {
-        Self {
-            val: self.val ^ rhs.val,
+            #[inline(always)]
+            pub const fn new(value: $host) -> Self {
+                Self { val: value.to_be() }
+            }
+
+            /// Returns the value in host byte order.
+            #[inline(always)]
+            pub const fn get(self) -> $host {
+                <$host>::from_be(self.val)
+            }
+
+            #[inline(always)]
+            pub const fn from_be_bytes(bytes: [u8; $bytes]) -> Self {
+                Self {
+                    val: <$host>::from_ne_bytes(bytes),
+                }
+            }
+
+            #[inline(always)]
+            pub const fn to_be_bytes(self) -> [u8; $bytes] {
+                self.val.to_ne_bytes()
+            }
+
+            #[inline(always)]
+            pub const fn wrapping_add(self, rhs: $host) -> Self {
+                Self::new(self.get().wrapping_add(rhs))
+            }
+
+            #[inline(always)]
+            pub const fn wrapping_sub(self, rhs: $host) -> Self {
+                Self::new(self.get().wrapping_sub(rhs))
+            }
+
+            #[inline(always)]
+            pub const fn checked_add(self, rhs: $host) -> Option<Self> {
+                match self.get().checked_add(rhs) {
+                    Some(v) => Some(Self::new(v)),
+                    None => None,
+                }
+            }
+
+            #[inline(always)]
+            pub const fn checked_sub(self, rhs: $host) -> Option<Self> {
+                match self.get().checked_sub(rhs) {
+                    Some(v) => Some(Self::new(v)),
+                    None => None,
+                }
+            }
         }
-    }
-}
 
-#[allow(non_camel_case_types)]
-#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
-pub struct be32 {
-    val: u32,
-}
+        impl ToHost for $name {
+            type Host = $host;
 
-impl be32 {
-    #[inline(always)]
-    pub const fn new(value: u32) -> Self {
-        Self { val: value.to_be() }
-    }
-}
+            #[inline(always)]
+            fn to_host(self) -> $host {
+                self.get()
+            }
+        }
 
-impl From<u32> for be32 {
-    #[inline(always)]
-    fn from(value: u32) -> Self {
-        Self { val: value.to_be() }
-    }
-}
+        impl FromHost for $name {
+            #[inline(always)]
+            fn from_host(value: $host) -> Self {
+                Self::new(value)
+            }
+        }
 
-impl From<be32> for u32 {
-    #[inline(always)]
-    fn from(be: be32) -> Self {
-        u32::from_be(be.val)
-    }
-}
+        impl From<$host> for $name {
+            #[inline(always)]
+            fn from(value: $host) -> Self {
+                Self::new(value)
+            }
+        }
 
-impl From<be32> for [u8; 4] {
-    #[inline(always)]
-    fn from(value: be32) -> Self {
-        value.val.to_ne_bytes()
-    }
-}
+        impl From<$name> for $host {
+            #[inline(always)]
+            fn from(be: $name) -> Self {
+                be.get()
+            }
+        }
 
-impl From<[u8; 4]> for be32 {
-    #[inline(always)]
-    fn from(value: [u8; 4]) -> Self {
-        be32 {
-            val: u32::from_ne_bytes(value),
+        impl From<$name> for [u8; $bytes] {
+            #[inline(always)]
+            fn from(value: $name) -> Self {
+                value.to_be_bytes()
+            }
         }
-    }
-}
 
-impl core::ops::BitXor for be32 {
-    type Output = be32;
+        impl From<[u8; $bytes]> for $name {
+            #[inline(always)]
+            fn from(value: [u8; $bytes]) -> Self {
+                Self::from_be_bytes(value)
+            }
+        }
 
-    #[inline(always)]
-    fn bitxor(self, rhs: Self) -> Self::Output {
-        Self {
-            val: self.val ^ rhs.val,
+        impl PartialOrd for $name {
+            #[inline(always)]
+            fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
         }
-    }
+
+        impl Ord for $name {
+            #[inline(always)]
+            fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+                self.get().cmp(&other.get())
+            }
+        }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Display::fmt(&self.get(), f)
+            }
+        }
+
+        impl core::fmt::LowerHex for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::LowerHex::fmt(&self.get(), f)
+            }
+        }
+
+        impl core::fmt::UpperHex for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::UpperHex::fmt(&self.get(), f)
+            }
+        }
+
+        be_int!(@bitop $name, BitAnd, bitand, BitAndAssign, bitand_assign, &);
+        be_int!(@bitop $name, BitOr, bitor, BitOrAssign, bitor_assign, |);
+        be_int!(@bitop $name, BitXor, bitxor, BitXorAssign, bitxor_assign, ^);
+
+        impl core::ops::Not for $name {
+            type Output = $name;
+
+            #[inline(always)]
+            fn not(self) -> Self::Output {
+                Self { val: !self.val }
+            }
+        }
+    };
+    (@bitop $name:ident, $trait:ident, $fn:ident, $atrait:ident, $afn:ident, $op:tt) => {
+        impl core::ops::$trait for $name {
+            type Output = $name;
+
+            #[inline(always)]
+            fn $fn(self, rhs: Self) -> Self::Output {
+                Self {
+                    val: self.val $op rhs.val,
+                }
+            }
+        }
+
+        impl core::ops::$atrait for $name {
+            #[inline(always)]
+            fn $afn(&mut self, rhs: Self) {
+                self.val = self.val $op rhs.val;
+            }
+        }
+    };
 }
 
+be_int!(be16, u16, 2);
+be_int!(be32, u32, 4);
+be_int!(be64, u64, 8);
+
+const _: () = assert!(core::mem::size_of::<be16>() == 2 && core::mem::align_of::<be16>() == 2);
+const _: () = assert!(core::mem::size_of::<be32>() == 4 && core::mem::align_of::<be32>() == 4);
+const _: () = assert!(core::mem::size_of::<be64>() == 8 && core::mem::align_of::<be64>() == 8);
+
 pub struct Ptr<T: ?Sized> {
     pointer: *mut T,
 }
@@ -139,11 +256,105 @@ impl<T> core::ops::DerefMut for Ptr<T> {
     }
 }
 
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A packed header type with no padding and no invalid bit patterns, so that any bytes of its
+/// size are a valid value. Only the header types of this crate implement it, which is what makes
+/// [`read_header`] and [`write_header`] safe.
+///
+/// ```compile_fail
+/// let b: bool = packets::read_header(&[2], 0).unwrap();
+/// ```
+pub trait Plain: Copy + sealed::Sealed {}
+
+macro_rules! plain {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl sealed::Sealed for $ty {}
+            impl Plain for $ty {}
+        )*
+    };
+}
+
+plain!(
+    arp::Header,
+    arp::VarHeader,
+    eth::Header,
+    gtp::Header,
+    gtp::Options,
+    icmp::Header,
+    icmpv6::Header,
+    ipv4::Header,
+    ipv6::Header,
+    ipv6::ExtHeader,
+    ipv6::FragmentHeader,
+    ipv6::JumboHeader,
+    sctp::Header,
+    sctp::ChunkHeader,
+    sctp::DataChunk,
+    sctp::InitChunk,
+    sctp::SackChunk,
+    tcp::Header,
+    udp::Header,
+    virtio_net::Header,
+    virtio_net::MrgRxbufHeader,
+    virtio_net::HashHeader,
+    vxlan::Header,
+);
+
+/// Copies a header out of `buf` at `offset`, or returns `Err` if it doesn't fit. Byte buffers are
+/// not necessarily aligned for the packed header types, so the read is unaligned.
+#[inline(always)]
+pub fn read_header<T: Plain>(buf: &[u8], offset: usize) -> Result<T, ()> {
+    match offset.checked_add(core::mem::size_of::<T>()) {
+        Some(end) if end <= buf.len() => {
+            Ok(unsafe { core::ptr::read_unaligned(buf.as_ptr().add(offset) as *const T) })
+        }
+        _ => Err(()),
+    }
+}
+
+/// Writes a header into `buf` at `offset`, or returns `Err` if it doesn't fit.
+#[inline(always)]
+pub fn write_header<T: Plain>(buf: &mut [u8], offset: usize, header: &T) -> Result<(), ()> {
+    match offset.checked_add(core::mem::size_of::<T>()) {
+        Some(end) if end <= buf.len() => {
+            unsafe { core::ptr::write_unaligned(buf.as_mut_ptr().add(offset) as *mut T, *header) };
+            Ok(())
+        }
+        _ => Err(()),
+    }
+}
+
+/// Returns the bytes of `frame` from the start of `header` to the end of the frame, or `None` if
+/// `header` doesn't lie within `frame`, as a copy of a header on the stack doesn't.
+#[inline(always)]
+pub(crate) fn frame_from<T>(
+    header: &T,
+    frame: core::ops::Range<*mut core::ffi::c_void>,
+) -> Option<&[u8]> {
+    let start = header as *const T as usize;
+    let end = start.checked_add(core::mem::size_of::<T>())?;
+    if start < frame.start as usize || end > frame.end as usize {
+        return None;
+    }
+    let len = frame.end as usize - start;
+    Some(unsafe { core::slice::from_raw_parts(start as *const u8, len) })
+}
+
 pub enum HeaderPtr {
     Arp(Ptr<arp::Header>),
+    ArpVar(Ptr<arp::VarHeader>),
     Eth(Ptr<eth::Header>),
+    Gtp(Ptr<gtp::Header>),
+    Icmp(Ptr<icmp::Header>),
+    Icmpv6(Ptr<icmpv6::Header>),
     Ipv4(Ptr<ipv4::Header>),
     Ipv6(Ptr<ipv6::Header>),
+    Ipv6Fragment(Ptr<ipv6::FragmentHeader>),
+    Sctp(Ptr<sctp::Header>),
     Tcp(Ptr<tcp::Header>),
     Udp(Ptr<udp::Header>),
     Vxlan(Ptr<vxlan::Header>),
@@ -158,39 +369,293 @@ pub trait AutoNextHeader: Sized {
     fn next(&mut self, range: core::ops::Range<*mut core::ffi::c_void>) -> Result<HeaderPtr, ()>;
 }
 
-pub trait NextHeader: Sized {
+/// The protocol of a header type, as given by [`Header::PROTOCOL`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Protocol {
+    Arp,
+    Ethernet,
+    Gtp,
+    Icmp,
+    Icmpv6,
+    Ipv4,
+    Ipv6,
+    /// An IPv6 Hop-by-Hop, Routing or Destination Options header.
+    Ipv6Ext,
+    Ipv6Fragment,
+    Sctp,
+    Tcp,
+    Udp,
+    VirtioNet,
+    Vxlan,
+}
+
+/// A protocol header that can be read in place from a frame.
+///
+/// Some headers are followed by options or addresses whose length is given by a field of the
+/// fixed part, such as the IPv4 IHL or the TCP data offset. For those, [`Header::len`] reads the
+/// field and [`Header::validate`] checks it along with any version field.
+#[allow(clippy::len_without_is_empty)]
+pub trait Header: Sized {
+    /// The length of the fixed part of the header, which is all of it for most types.
+    const MIN_LEN: usize = core::mem::size_of::<Self>();
+    /// The protocol of the header.
+    const PROTOCOL: Protocol;
+
+    /// Returns the length of the header including any options, in bytes.
+    #[inline(always)]
+    fn len(&self) -> usize {
+        Self::MIN_LEN
+    }
+
+    /// Returns `Err` if the fields of the header are malformed or if its length is more than the
+    /// `remaining` bytes of the frame, counted from the start of the header.
+    #[inline(always)]
+    fn validate(&self, remaining: usize) -> Result<(), ()> {
+        match (Self::MIN_LEN..=remaining).contains(&self.len()) {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
+
+pub trait NextHeader: Header {
     #[inline(always)]
     /// Returns the next header, interpreted as the given type. For example, if you have a UDP
     /// header (e.g. `udp_hdr`) and you know the next header will be VXLAN, pass T = [`vxlan::Header`]
     /// and it will return a pointer to the VXLAN header:
     ///
     /// let vxlan_h = udp_h.next_t::<vxlan::Header>(&ctx)?;
+    ///
+    /// The current header is validated first and the next one starts [`Header::len`] bytes after
+    /// it, past any options.
     fn next_t<T: NextHeader>(
         &mut self,
         range: core::ops::Range<*mut core::ffi::c_void>,
     ) -> Result<Ptr<T>, ()> {
-        let next = unsafe {
-            let me = self as *mut Self;
-            me.offset(1) as *mut T
-        };
+        let me = self as *mut Self as *mut u8;
+        let remaining = (range.end as usize).checked_sub(me as usize).ok_or(())?;
+        self.validate(remaining)?;
 
-        let next_end = unsafe { next.offset(1) };
-        let end = range.end as *mut T;
+        let next = unsafe { me.add(self.len()) };
+        let next_end = next.wrapping_add(T::MIN_LEN);
+        let end = range.end as *mut u8;
 
         match next_end > end {
-            false => Ok(Ptr::new(next)),
+            false => Ok(Ptr::new(next as *mut T)),
             true => Err(()),
         }
     }
 }
 
 pub mod arp;
+#[cfg(feature = "aya")]
+pub mod aya;
+pub mod checksum;
+pub mod cursor;
+pub mod dissect;
+pub mod ecn;
 pub mod eth;
+pub mod fragmentation;
+pub mod gro;
+pub mod gso;
+pub mod gtp;
+pub mod icmp;
+pub mod icmpv6;
 pub mod ip;
 pub mod ipv4;
 pub mod ipv6;
+#[cfg(feature = "std")]
+pub mod pcap;
+#[cfg(feature = "std")]
+pub mod pcapng;
+pub mod reassembly;
+pub mod remark;
+pub mod sctp;
+#[cfg(feature = "serde")]
+mod serialize;
 pub mod tcp;
 pub mod udp;
+pub mod virtio_net;
 pub mod vxlan;
 
 pub mod meta;
+
+/// Byte buffer for tests that walk headers through a frame range, which requires the 2 byte
+/// alignment of the packed header types.
+#[cfg(test)]
+#[repr(C, align(8))]
+pub(crate) struct Aligned<const N: usize>(pub [u8; N]);
+
+#[cfg(test)]
+impl<const N: usize> Aligned<N> {
+    pub fn range(&mut self, len: usize) -> core::ops::Range<*mut core::ffi::c_void> {
+        let r = self.0[..len].as_mut_ptr_range();
+        r.start as *mut _..r.end as *mut _
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn be_layout() {
+        assert_eq!([0x12, 0x34], be16::new(0x1234).to_be_bytes());
+        assert_eq!(
+            [0x12, 0x34, 0x56, 0x78],
+            <[u8; 4]>::from(be32::new(0x12345678))
+        );
+        assert_eq!(
+            be64::new(0x0102030405060708),
+            be64::from([1, 2, 3, 4, 5, 6, 7, 8])
+        );
+        assert_eq!(0x1234u16, be16::from_be_bytes([0x12, 0x34]).get());
+    }
+
+    #[test]
+    fn be_ordering() {
+        // 0x0100 is smaller than 0x00ff in little-endian storage order.
+        assert!(be16::new(0x00ff) < be16::new(0x0100));
+        assert!(be32::new(1) < be32::new(0x100));
+        assert_eq!(be64::new(7), core::cmp::max(be64::new(7), be64::new(3)));
+        assert!((be16::new(1024)..=be16::new(65535)).contains(&be16::new(8080)));
+    }
+
+    #[test]
+    fn be_match_pattern() {
+        const HTTP: be16 = be16::new(80);
+        let port = be16::from(80);
+        assert!(matches!(port, HTTP));
+    }
+
+    #[test]
+    fn be_bitops() {
+        let mut v = be16::new(0xff00);
+        assert_eq!(be16::new(0x0f00), v & be16::new(0x0ff0));
+        assert_eq!(be16::new(0xfff0), v | be16::new(0x00f0));
+        assert_eq!(be16::new(0x0ff0), v ^ be16::new(0xf0f0));
+        assert_eq!(be16::new(0x00ff), !v);
+        v ^= be16::new(0xffff);
+        assert_eq!(be16::new(0x00ff), v);
+        v |= be16::new(0x0100);
+        v &= be16::new(0x0180);
+        assert_eq!(be16::new(0x0180), v);
+    }
+
+    #[test]
+    fn be_arithmetic() {
+        assert_eq!(be32::new(0), be32::MAX.wrapping_add(1));
+        assert_eq!(be32::MAX, be32::ZERO.wrapping_sub(1));
+        assert_eq!(be16::new(0x100), be16::new(0xff).wrapping_add(1));
+        assert_eq!(None, be16::MAX.checked_add(1));
+        assert_eq!(Some(be64::new(41)), be64::new(42).checked_sub(1));
+    }
+
+    #[test]
+    fn be_host_traits() {
+        fn roundtrip<T: FromHost + PartialEq + core::fmt::Debug + Copy>(v: T) {
+            assert_eq!(v, T::from_host(v.to_host()));
+        }
+        roundtrip(be16::new(0xabcd));
+        roundtrip(be32::new(0xdeadbeef));
+        roundtrip(be64::new(u64::MAX - 1));
+        roundtrip(7u8);
+        assert_eq!(0xabcdu16, be16::new(0xabcd).to_host());
+    }
+
+    #[cfg(feature = "zerocopy")]
+    #[test]
+    fn zerocopy() {
+        use zerocopy::{AsBytes, FromBytes};
+
+        let bytes = [
+            0xff, 0x45, 0, 0, 20, 0, 1, 0x40, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+        ];
+        // Offset by one to check that the read is unaligned.
+        let ip = ipv4::Header::read_from(&bytes[1..]).unwrap();
+        assert_eq!(ip::Proto::TCP, ip.proto);
+        assert!(ip.dont_fragment());
+        assert_eq!(&bytes[1..], ip.as_bytes());
+        assert!(udp::Header::read_from(&bytes[1..8]).is_none());
+        assert_eq!([0, 80], be16::new(80).as_bytes());
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn bytemuck() {
+        let bytes = [2u8, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 1, 0x86, 0xdd];
+        let eth: eth::Header = bytemuck::pod_read_unaligned(&bytes);
+        assert_eq!(eth::Type::IPV6, { eth.ether_type });
+        assert_eq!(&bytes, bytemuck::bytes_of(&eth));
+        let zero: tcp::Header = bytemuck::Zeroable::zeroed();
+        assert_eq!(0, zero.data_offset());
+        assert_eq!(be32::new(7), bytemuck::cast::<[u8; 4], be32>([0, 0, 0, 7]));
+    }
+
+    #[test]
+    fn be_format() {
+        use core::fmt::Write;
+
+        struct Buf([u8; 32], usize);
+        impl Write for Buf {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                self.0[self.1..self.1 + s.len()].copy_from_slice(s.as_bytes());
+                self.1 += s.len();
+                Ok(())
+            }
+        }
+
+        let mut buf = Buf([0; 32], 0);
+        write!(
+            buf,
+            "{} {:x} {:#06X}",
+            be16::new(4789),
+            be32::new(0xbeef),
+            be16::new(0xab)
+        )
+        .unwrap();
+        assert_eq!(b"4789 beef 0x00AB", &buf.0[..buf.1]);
+    }
+
+    proptest! {
+        #[test]
+        fn be_round_trip(a: u16, b: u32, c: u64) {
+            prop_assert_eq!(a, be16::new(a).get());
+            prop_assert_eq!(a.to_be_bytes(), be16::new(a).to_be_bytes());
+            prop_assert_eq!(be32::new(b), be32::from_be_bytes(b.to_be_bytes()));
+            prop_assert_eq!(c, be64::from_host(c).to_host());
+        }
+
+        #[test]
+        fn be_ops_match_host(a: u32, b: u32) {
+            let (x, y) = (be32::new(a), be32::new(b));
+            prop_assert_eq!(a.cmp(&b), x.cmp(&y));
+            prop_assert_eq!(a & b, (x & y).get());
+            prop_assert_eq!(a ^ b, (x ^ y).get());
+            prop_assert_eq!(!a, (!x).get());
+            prop_assert_eq!(a.wrapping_add(b), x.wrapping_add(b).get());
+            prop_assert_eq!(a.wrapping_sub(b), x.wrapping_sub(b).get());
+            prop_assert_eq!(a.checked_add(b), x.checked_add(b).map(be32::get));
+            prop_assert_eq!(a.checked_sub(b), x.checked_sub(b).map(be32::get));
+        }
+
+        #[test]
+        fn header_bounds(len in 0usize..64, offset in 0usize..64) {
+            let mut buf = [0u8; 64];
+            let fits = offset + crate::eth::Header::LEN <= len;
+            prop_assert_eq!(
+                fits,
+                read_header::<crate::eth::Header>(&buf[..len], offset).is_ok()
+            );
+            let eth = crate::eth::Header {
+                dst_addr: [1; 6],
+                src_addr: [2; 6],
+                ether_type: crate::eth::Type::IPV6,
+            };
+            prop_assert_eq!(fits, write_header(&mut buf[..len], offset, &eth).is_ok());
+            prop_assert!(buf[len..].iter().all(|b| *b == 0));
+        }
+    }
+}
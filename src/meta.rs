@@ -8,6 +8,6 @@ pub fn from_range<T: Sized>(
             return None;
         }
 
-        Some(super::Ptr::new(pointer as *mut T))
+        Some(super::Ptr::new(pointer))
     }
 }
@@ -0,0 +1,482 @@
+//! Streaming reader and writer for the classic libpcap capture format.
+//!
+//! Frames are read into a buffer aligned for the header structs, so [`Packet::frame`] can be
+//! handed to [`super::eth::Header::from_frame`] and [`Packet::first_header`] starts a walk with
+//! [`super::AutoNextHeader`] as for any other frame.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::io::{self, Read, Write};
+
+/// Largest record accepted by the reader, the snapshot length of current tcpdump.
+pub const MAX_SNAPLEN: u32 = 262144;
+
+const MAGIC_MICRO: u32 = 0xa1b2_c3d4;
+const MAGIC_NANO: u32 = 0xa1b2_3c4d;
+const VERSION: (u16, u16) = (2, 4);
+const FILE_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/// Link-layer header type of a capture, as registered with tcpdump.org.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LinkType {
+    val: u32,
+}
+
+impl LinkType {
+    pub const fn new(val: u32) -> Self {
+        Self { val }
+    }
+
+    pub const fn get(self) -> u32 {
+        self.val
+    }
+
+    pub const NULL: LinkType = LinkType::new(0);
+    pub const ETHERNET: LinkType = LinkType::new(1);
+    /// IPv4 or IPv6 packets without a link-layer header.
+    pub const RAW: LinkType = LinkType::new(101);
+    pub const IPV4: LinkType = LinkType::new(228);
+    pub const IPV6: LinkType = LinkType::new(229);
+}
+
+/// Resolution of the timestamps in a capture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    Micro,
+    Nano,
+}
+
+impl Resolution {
+    /// Returns the sub-second part of `ts` in this resolution.
+    pub(crate) fn fraction(self, ts: Duration) -> u32 {
+        match self {
+            Resolution::Micro => ts.subsec_micros(),
+            Resolution::Nano => ts.subsec_nanos(),
+        }
+    }
+
+    pub(crate) fn duration(self, secs: u64, fraction: u32) -> Duration {
+        match self {
+            Resolution::Micro => Duration::from_secs(secs) + Duration::from_micros(fraction as u64),
+            Resolution::Nano => Duration::from_secs(secs) + Duration::from_nanos(fraction as u64),
+        }
+    }
+}
+
+/// A captured frame, borrowed from the reader until the next one is read.
+#[derive(Debug)]
+pub struct Packet<'a> {
+    /// Time since the Unix epoch at which the frame was captured.
+    pub timestamp: Duration,
+    /// Length of the frame on the wire, which is more than the captured length if the capture
+    /// was truncated.
+    pub orig_len: u32,
+    pub link_type: LinkType,
+    data: &'a mut [u8],
+}
+
+impl<'a> Packet<'a> {
+    pub(crate) fn new(
+        timestamp: Duration,
+        orig_len: u32,
+        link_type: LinkType,
+        data: &'a mut [u8],
+    ) -> Self {
+        Self {
+            timestamp,
+            orig_len,
+            link_type,
+            data,
+        }
+    }
+
+    /// The captured bytes.
+    #[inline(always)]
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    #[inline(always)]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    /// Returns true if fewer bytes were captured than were on the wire.
+    #[inline(always)]
+    pub fn is_truncated(&self) -> bool {
+        (self.data.len() as u64) < self.orig_len as u64
+    }
+
+    /// The captured bytes as a frame for the header walking API.
+    #[inline(always)]
+    pub fn frame(&mut self) -> core::ops::Range<*mut core::ffi::c_void> {
+        let r = self.data.as_mut_ptr_range();
+        r.start as *mut _..r.end as *mut _
+    }
+
+    /// Returns the outermost header of the frame, according to its link type. Fails if the
+    /// frame is too short or the link type isn't Ethernet or raw IP.
+    pub fn first_header(&mut self) -> Result<super::HeaderPtr, ()> {
+        let frame = self.frame();
+        match self.link_type {
+            LinkType::ETHERNET => Ok(super::HeaderPtr::Eth(super::eth::Header::from_frame(
+                frame,
+            )?)),
+            LinkType::RAW | LinkType::IPV4 | LinkType::IPV6 => {
+                match (self.data.first().ok_or(())? >> 4, self.link_type) {
+                    (4, LinkType::RAW | LinkType::IPV4) => {
+                        Ok(super::HeaderPtr::Ipv4(start::<super::ipv4::Header>(frame)?))
+                    }
+                    (6, LinkType::RAW | LinkType::IPV6) => {
+                        Ok(super::HeaderPtr::Ipv6(start::<super::ipv6::Header>(frame)?))
+                    }
+                    _ => Err(()),
+                }
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+/// Returns a pointer to the header at the start of `frame`.
+fn start<T>(frame: core::ops::Range<*mut core::ffi::c_void>) -> Result<super::Ptr<T>, ()> {
+    if frame.end as usize - (frame.start as usize) < core::mem::size_of::<T>() {
+        return Err(());
+    }
+    Ok(super::Ptr::new(frame.start as *mut T))
+}
+
+/// Record storage aligned for the header structs, which a `Vec<u8>` doesn't guarantee.
+#[derive(Debug, Default)]
+pub(crate) struct Buffer {
+    words: Vec<u64>,
+}
+
+impl Buffer {
+    /// Returns the first `len` bytes of the buffer, growing it as needed.
+    pub fn get(&mut self, len: usize) -> &mut [u8] {
+        let words = len.div_ceil(8);
+        if self.words.len() < words {
+            self.words.resize(words, 0);
+        }
+        unsafe { core::slice::from_raw_parts_mut(self.words.as_mut_ptr() as *mut u8, len) }
+    }
+}
+
+/// Fills `buf` from `reader`, returning 0 at a clean end of file and an error if it ends after
+/// part of `buf`.
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut done = 0;
+    while done < buf.len() {
+        match reader.read(&mut buf[done..]) {
+            Ok(0) => break,
+            Ok(n) => done += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    match done {
+        0 => Ok(0),
+        n if n == buf.len() => Ok(n),
+        _ => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+pub(crate) fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads frames from a pcap file in either byte order.
+pub struct Reader<R> {
+    reader: R,
+    big_endian: bool,
+    resolution: Resolution,
+    snaplen: u32,
+    link_type: LinkType,
+    buf: Buffer,
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the file header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut hdr = [0u8; FILE_HEADER_LEN];
+        reader.read_exact(&mut hdr)?;
+        let magic = [hdr[0], hdr[1], hdr[2], hdr[3]];
+        let (big_endian, resolution) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic))
+        {
+            (MAGIC_MICRO, _) => (false, Resolution::Micro),
+            (MAGIC_NANO, _) => (false, Resolution::Nano),
+            (_, MAGIC_MICRO) => (true, Resolution::Micro),
+            (_, MAGIC_NANO) => (true, Resolution::Nano),
+            _ => return Err(invalid("not a pcap file")),
+        };
+        let mut this = Self {
+            reader,
+            big_endian,
+            resolution,
+            snaplen: 0,
+            link_type: LinkType::default(),
+            buf: Buffer::default(),
+        };
+        if this.u16(&hdr[4..6]) != VERSION.0 {
+            return Err(invalid("unsupported pcap version"));
+        }
+        this.snaplen = this.u32(&hdr[16..20]);
+        // The upper bits hold the FCS length, which isn't needed to walk the headers.
+        this.link_type = LinkType::new(this.u32(&hdr[20..24]) & 0x0fff_ffff);
+        Ok(this)
+    }
+
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    pub fn snaplen(&self) -> u32 {
+        self.snaplen
+    }
+
+    /// Reads the next frame, returning `None` at the end of the file.
+    pub fn next_packet(&mut self) -> Option<io::Result<Packet<'_>>> {
+        let mut hdr = [0u8; RECORD_HEADER_LEN];
+        match read_full(&mut self.reader, &mut hdr) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        let secs = self.u32(&hdr[0..4]);
+        let fraction = self.u32(&hdr[4..8]);
+        let incl_len = self.u32(&hdr[8..12]);
+        let orig_len = self.u32(&hdr[12..16]);
+        if incl_len > MAX_SNAPLEN {
+            return Some(Err(invalid("pcap record too large")));
+        }
+        let data = self.buf.get(incl_len as usize);
+        if let Err(e) = self.reader.read_exact(data) {
+            return Some(Err(e));
+        }
+        let timestamp = self.resolution.duration(secs as u64, fraction);
+        Some(Ok(Packet::new(timestamp, orig_len, self.link_type, data)))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn u16(&self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        match self.big_endian {
+            true => u16::from_be_bytes(b),
+            false => u16::from_le_bytes(b),
+        }
+    }
+
+    fn u32(&self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        match self.big_endian {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        }
+    }
+}
+
+/// Writes frames to a little-endian pcap file.
+pub struct Writer<W: Write> {
+    writer: W,
+    resolution: Resolution,
+    snaplen: u32,
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes the file header. Frames longer than `snaplen` are truncated.
+    pub fn new(
+        mut writer: W,
+        link_type: LinkType,
+        resolution: Resolution,
+        snaplen: u32,
+    ) -> io::Result<Self> {
+        let magic = match resolution {
+            Resolution::Micro => MAGIC_MICRO,
+            Resolution::Nano => MAGIC_NANO,
+        };
+        let mut hdr = [0u8; FILE_HEADER_LEN];
+        hdr[0..4].copy_from_slice(&magic.to_le_bytes());
+        hdr[4..6].copy_from_slice(&VERSION.0.to_le_bytes());
+        hdr[6..8].copy_from_slice(&VERSION.1.to_le_bytes());
+        hdr[16..20].copy_from_slice(&snaplen.to_le_bytes());
+        hdr[20..24].copy_from_slice(&link_type.get().to_le_bytes());
+        writer.write_all(&hdr)?;
+        Ok(Self {
+            writer,
+            resolution,
+            snaplen,
+        })
+    }
+
+    /// Writes a frame captured at `timestamp`, the time since the Unix epoch.
+    pub fn write_packet(&mut self, timestamp: Duration, data: &[u8]) -> io::Result<()> {
+        self.write_truncated(timestamp, data, data.len() as u32)
+    }
+
+    /// Writes a frame of which only `data` was captured out of `orig_len` bytes.
+    pub fn write_truncated(
+        &mut self,
+        timestamp: Duration,
+        data: &[u8],
+        orig_len: u32,
+    ) -> io::Result<()> {
+        let data = &data[..data.len().min(self.snaplen as usize)];
+        let secs = u32::try_from(timestamp.as_secs()).map_err(|_| invalid("timestamp too late"))?;
+        let mut hdr = [0u8; RECORD_HEADER_LEN];
+        hdr[0..4].copy_from_slice(&secs.to_le_bytes());
+        hdr[4..8].copy_from_slice(&self.resolution.fraction(timestamp).to_le_bytes());
+        hdr[8..12].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        hdr[12..16].copy_from_slice(&orig_len.to_le_bytes());
+        self.writer.write_all(&hdr)?;
+        self.writer.write_all(data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AutoNextHeader;
+
+    /// An Ethernet frame with an IPv4 header.
+    fn frame() -> [u8; 34] {
+        let mut frame = [0u8; 34];
+        let eth = crate::eth::Header {
+            dst_addr: crate::eth::BROADCAST,
+            src_addr: [2, 0, 0, 0, 0, 1],
+            ether_type: crate::eth::Type::IPV4,
+        };
+        crate::write_header(&mut frame, 0, &eth).unwrap();
+        let mut ip = crate::ipv4::Header {
+            tot_len: 20.into(),
+            ttl: 64,
+            proto: crate::ip::Proto::UDP,
+            source: [10, 0, 0, 1],
+            destination: [10, 0, 0, 2],
+            ..Default::default()
+        };
+        ip.update_checksum();
+        crate::write_header(&mut frame, 14, &ip).unwrap();
+        frame
+    }
+
+    #[test]
+    fn round_trip() {
+        for resolution in [Resolution::Micro, Resolution::Nano] {
+            let mut w = Writer::new(Vec::new(), LinkType::ETHERNET, resolution, 65535).unwrap();
+            let ts = Duration::new(1_700_000_000, 123_456_789);
+            w.write_packet(ts, &frame()).unwrap();
+            w.write_packet(ts + Duration::from_secs(1), &frame()[..14])
+                .unwrap();
+            let file = w.into_inner();
+
+            let mut r = Reader::new(&file[..]).unwrap();
+            assert_eq!(LinkType::ETHERNET, r.link_type());
+            assert_eq!(resolution, r.resolution());
+            assert_eq!(65535, r.snaplen());
+
+            let mut p = r.next_packet().unwrap().unwrap();
+            let expected = match resolution {
+                Resolution::Micro => Duration::new(1_700_000_000, 123_456_000),
+                Resolution::Nano => ts,
+            };
+            assert_eq!(expected, p.timestamp);
+            assert_eq!(frame(), p.data());
+            let range = p.frame();
+            match p.first_header() {
+                Ok(crate::HeaderPtr::Eth(mut eth)) => match eth.next(range) {
+                    Ok(crate::HeaderPtr::Ipv4(ip)) => assert_eq!([10, 0, 0, 2], ip.destination),
+                    _ => panic!("expected IPv4"),
+                },
+                _ => panic!("expected Ethernet"),
+            }
+
+            let p = r.next_packet().unwrap().unwrap();
+            assert_eq!(14, p.data().len());
+            assert!(r.next_packet().is_none());
+        }
+    }
+
+    #[test]
+    fn raw_ip() {
+        let mut w = Writer::new(Vec::new(), LinkType::RAW, Resolution::Micro, 65535).unwrap();
+        w.write_packet(Duration::ZERO, &frame()[14..]).unwrap();
+        w.write_packet(Duration::ZERO, &[0x60]).unwrap();
+        let file = w.into_inner();
+
+        let mut r = Reader::new(&file[..]).unwrap();
+        let mut p = r.next_packet().unwrap().unwrap();
+        assert!(matches!(p.first_header(), Ok(crate::HeaderPtr::Ipv4(_))));
+        // Too short for an IPv6 header.
+        let mut p = r.next_packet().unwrap().unwrap();
+        assert!(p.first_header().is_err());
+    }
+
+    #[test]
+    fn snaplen() {
+        let mut w = Writer::new(Vec::new(), LinkType::ETHERNET, Resolution::Micro, 20).unwrap();
+        w.write_packet(Duration::ZERO, &frame()).unwrap();
+        let file = w.into_inner();
+        let mut r = Reader::new(&file[..]).unwrap();
+        let p = r.next_packet().unwrap().unwrap();
+        assert_eq!(20, p.data().len());
+        assert_eq!(34, p.orig_len);
+        assert!(p.is_truncated());
+    }
+
+    #[test]
+    fn big_endian() {
+        let mut file = Vec::new();
+        file.extend_from_slice(&MAGIC_NANO.to_be_bytes());
+        file.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff]);
+        file.extend_from_slice(&1u32.to_be_bytes());
+        file.extend_from_slice(&[0, 0, 0, 5, 0, 0, 0, 7, 0, 0, 0, 2, 0, 0, 0, 2, 0xab, 0xcd]);
+        let mut r = Reader::new(&file[..]).unwrap();
+        assert_eq!(Resolution::Nano, r.resolution());
+        assert_eq!(LinkType::ETHERNET, r.link_type());
+        let p = r.next_packet().unwrap().unwrap();
+        assert_eq!(Duration::new(5, 7), p.timestamp);
+        assert_eq!([0xab, 0xcd], p.data());
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(Reader::new(&[0u8; 24][..]).is_err());
+
+        let mut w = Writer::new(Vec::new(), LinkType::ETHERNET, Resolution::Micro, 65535).unwrap();
+        w.write_packet(Duration::ZERO, &frame()).unwrap();
+        let file = w.into_inner();
+        // Ends in the middle of a record header and of a record.
+        for end in [FILE_HEADER_LEN + 3, file.len() - 1] {
+            let mut r = Reader::new(&file[..end]).unwrap();
+            assert!(r.next_packet().unwrap().is_err());
+        }
+
+        let mut file = file;
+        file[FILE_HEADER_LEN + 8..FILE_HEADER_LEN + 12]
+            .copy_from_slice(&(MAX_SNAPLEN + 1).to_le_bytes());
+        let mut r = Reader::new(&file[..]).unwrap();
+        assert!(r.next_packet().unwrap().is_err());
+        // A huge snapshot length in the file header doesn't raise the limit.
+        file[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut r = Reader::new(&file[..]).unwrap();
+        assert_eq!(u32::MAX, r.snaplen());
+        assert!(r.next_packet().unwrap().is_err());
+    }
+}
@@ -0,0 +1,992 @@
+//! Streaming reader and writer for the pcapng capture format.
+//!
+//! A pcapng file holds one or more sections, each describing its own interfaces with their link
+//! type and timestamp resolution. Packets read from Enhanced and Simple Packet Blocks are
+//! [`Packet`]s like those of the pcap reader, and plug into the header walking API the same way.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::io::{self, Read, Write};
+
+use super::pcap::{invalid, read_full, Buffer, LinkType, Packet, Resolution};
+
+/// Largest block accepted by the reader.
+pub const MAX_BLOCK_LEN: u32 = 16 << 20;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+/// Block types.
+pub mod block {
+    pub const SECTION_HEADER: u32 = 0x0a0d_0d0a;
+    pub const INTERFACE_DESCRIPTION: u32 = 1;
+    pub const SIMPLE_PACKET: u32 = 3;
+    pub const NAME_RESOLUTION: u32 = 4;
+    pub const ENHANCED_PACKET: u32 = 6;
+}
+
+/// Option codes. Codes above 1 depend on the block the option belongs to.
+pub mod code {
+    pub const END: u16 = 0;
+    pub const COMMENT: u16 = 1;
+    pub const CUSTOM_STR_COPY: u16 = 2988;
+    pub const CUSTOM_BIN_COPY: u16 = 2989;
+    pub const CUSTOM_STR: u16 = 19372;
+    pub const CUSTOM_BIN: u16 = 19373;
+
+    pub const SHB_HARDWARE: u16 = 2;
+    pub const SHB_OS: u16 = 3;
+    pub const SHB_USERAPPL: u16 = 4;
+
+    pub const IF_NAME: u16 = 2;
+    pub const IF_DESCRIPTION: u16 = 3;
+    pub const IF_TSRESOL: u16 = 9;
+
+    pub const EPB_FLAGS: u16 = 2;
+}
+
+/// A block option, as read from a file or to be written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Opt<'a> {
+    /// A UTF-8 comment.
+    Comment(&'a str),
+    /// A custom option, identified by the IANA Private Enterprise Number `pen`. `code` is one of
+    /// the four custom codes, which tell whether `data` is a string and whether the option may
+    /// be copied to a new file.
+    Custom { code: u16, pen: u32, data: &'a [u8] },
+    /// Any other option, whose meaning depends on the block.
+    Other { code: u16, value: &'a [u8] },
+}
+
+impl<'a> Opt<'a> {
+    fn parse(code: u16, value: &'a [u8], endian: Endian) -> Self {
+        match code {
+            code::COMMENT => match core::str::from_utf8(value) {
+                Ok(comment) => Opt::Comment(comment),
+                Err(_) => Opt::Other { code, value },
+            },
+            code::CUSTOM_STR_COPY | code::CUSTOM_BIN_COPY | code::CUSTOM_STR | code::CUSTOM_BIN
+                if value.len() >= 4 =>
+            {
+                Opt::Custom {
+                    code,
+                    pen: endian.u32(&value[..4]),
+                    data: &value[4..],
+                }
+            }
+            _ => Opt::Other { code, value },
+        }
+    }
+
+    fn code(&self) -> u16 {
+        match *self {
+            Opt::Comment(_) => code::COMMENT,
+            Opt::Custom { code, .. } | Opt::Other { code, .. } => code,
+        }
+    }
+
+    fn value_len(&self) -> usize {
+        match *self {
+            Opt::Comment(comment) => comment.len(),
+            Opt::Custom { data, .. } => 4 + data.len(),
+            Opt::Other { value, .. } => value.len(),
+        }
+    }
+}
+
+/// The options of a block.
+#[derive(Debug, Copy, Clone)]
+pub struct Options<'a> {
+    data: &'a [u8],
+    endian: Endian,
+}
+
+impl<'a> Options<'a> {
+    /// Returns the value of the first option with `code`.
+    pub fn get(&self, code: u16) -> Option<&'a [u8]> {
+        let mut options = *self;
+        core::iter::from_fn(move || options.next_raw()).find_map(|(c, v)| (c == code).then_some(v))
+    }
+
+    /// Returns the comments among the options.
+    pub fn comments(&self) -> impl Iterator<Item = &'a str> {
+        let options = *self;
+        options.filter_map(|o| match o {
+            Opt::Comment(comment) => Some(comment),
+            _ => None,
+        })
+    }
+
+    fn next_raw(&mut self) -> Option<(u16, &'a [u8])> {
+        if self.data.len() < 4 {
+            return None;
+        }
+        let code = self.endian.u16(&self.data[0..2]);
+        let len = self.endian.u16(&self.data[2..4]) as usize;
+        if code == code::END || 4 + len > self.data.len() {
+            self.data = &[];
+            return None;
+        }
+        let value = &self.data[4..4 + len];
+        self.data = &self.data[(4 + padded(len)).min(self.data.len())..];
+        Some((code, value))
+    }
+}
+
+impl<'a> Iterator for Options<'a> {
+    type Item = Opt<'a>;
+
+    fn next(&mut self) -> Option<Opt<'a>> {
+        let (code, value) = self.next_raw()?;
+        Some(Opt::parse(code, value, self.endian))
+    }
+}
+
+/// An address in a Name Resolution Block.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Address {
+    Ipv4([u8; 4]),
+    Ipv6([u8; 16]),
+}
+
+impl Address {
+    /// Returns the record type and the address bytes.
+    fn record(&self) -> (u16, &[u8]) {
+        match self {
+            Address::Ipv4(a) => (1, a),
+            Address::Ipv6(a) => (2, a),
+        }
+    }
+}
+
+/// An address and the names it resolves to.
+#[derive(Debug, Copy, Clone)]
+pub struct NameRecord<'a> {
+    pub address: Address,
+    names: &'a [u8],
+}
+
+impl<'a> NameRecord<'a> {
+    /// Returns the names of the address, skipping any that aren't UTF-8.
+    pub fn names(&self) -> impl Iterator<Item = &'a str> {
+        self.names
+            .split(|b| *b == 0)
+            .filter(|n| !n.is_empty())
+            .filter_map(|n| core::str::from_utf8(n).ok())
+    }
+}
+
+/// The records of a Name Resolution Block.
+#[derive(Debug, Copy, Clone)]
+pub struct NameRecords<'a> {
+    data: &'a [u8],
+    endian: Endian,
+}
+
+impl<'a> Iterator for NameRecords<'a> {
+    type Item = NameRecord<'a>;
+
+    fn next(&mut self) -> Option<NameRecord<'a>> {
+        loop {
+            if self.data.len() < 4 {
+                return None;
+            }
+            let kind = self.endian.u16(&self.data[0..2]);
+            let len = self.endian.u16(&self.data[2..4]) as usize;
+            if kind == 0 || 4 + len > self.data.len() {
+                self.data = &[];
+                return None;
+            }
+            let value = &self.data[4..4 + len];
+            self.data = &self.data[(4 + padded(len)).min(self.data.len())..];
+            let (address, names) = match kind {
+                1 if len >= 4 => (Address::Ipv4(value[..4].try_into().ok()?), &value[4..]),
+                2 if len >= 16 => (Address::Ipv6(value[..16].try_into().ok()?), &value[16..]),
+                // Unknown record types are skipped.
+                _ => continue,
+            };
+            return Some(NameRecord { address, names });
+        }
+    }
+}
+
+/// An interface described by an Interface Description Block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub link_type: LinkType,
+    /// Maximum number of bytes captured from each packet, or 0 for no limit.
+    pub snaplen: u32,
+    /// Timestamp units per second.
+    pub units_per_sec: u64,
+    pub name: Option<String>,
+}
+
+impl Interface {
+    fn timestamp(&self, ts: u64) -> Duration {
+        let secs = ts / self.units_per_sec;
+        let nanos = (ts % self.units_per_sec) as u128 * 1_000_000_000 / self.units_per_sec as u128;
+        Duration::new(secs, nanos as u32)
+    }
+}
+
+/// A block read from a pcapng file.
+#[derive(Debug)]
+pub enum Block<'a> {
+    /// The start of a new section, which forgets the interfaces of the previous one and replaces
+    /// [`Reader::section_options`].
+    Section { options: Options<'a> },
+    /// An interface was added to [`Reader::interfaces`].
+    Interface { id: u32, options: Options<'a> },
+    /// A packet from an Enhanced Packet Block or, with no timestamp and no options, a Simple
+    /// Packet Block.
+    Packet {
+        interface: u32,
+        packet: Packet<'a>,
+        options: Options<'a>,
+    },
+    NameResolution {
+        records: NameRecords<'a>,
+        options: Options<'a>,
+    },
+    /// A block of a type the reader doesn't interpret.
+    Other { block_type: u32, body: &'a [u8] },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        match self.big {
+            true => u16::from_be_bytes(b),
+            false => u16::from_le_bytes(b),
+        }
+    }
+
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        match self.big {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        }
+    }
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Reads the blocks of a pcapng file, in any byte order.
+pub struct Reader<R> {
+    reader: R,
+    endian: Endian,
+    interfaces: Vec<Interface>,
+    section_options: Vec<u8>,
+    buf: Buffer,
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the first Section Header Block.
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut this = Self {
+            reader,
+            endian: Endian { big: false },
+            interfaces: Vec::new(),
+            section_options: Vec::new(),
+            buf: Buffer::default(),
+        };
+        match this.read_block()? {
+            Some((block::SECTION_HEADER, _)) => Ok(this),
+            _ => Err(invalid("not a pcapng file")),
+        }
+    }
+
+    /// The interfaces of the current section, indexed by interface id.
+    pub fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+
+    /// The options of the current Section Header Block, such as its comments or
+    /// [`code::SHB_HARDWARE`].
+    pub fn section_options(&self) -> Options<'_> {
+        Options {
+            data: &self.section_options,
+            endian: self.endian,
+        }
+    }
+
+    /// Reads the next block, returning `None` at the end of the file.
+    pub fn next_block(&mut self) -> Option<io::Result<Block<'_>>> {
+        match self.read_block() {
+            Ok(Some((block_type, len))) => Some(self.parse(block_type, len)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Reads the next packet, skipping blocks of other types.
+    pub fn next_packet(&mut self) -> Option<io::Result<Packet<'_>>> {
+        loop {
+            match self.read_block() {
+                Ok(Some((t @ (block::ENHANCED_PACKET | block::SIMPLE_PACKET), len))) => {
+                    return match self.parse(t, len) {
+                        Ok(Block::Packet { packet, .. }) => Some(Ok(packet)),
+                        Ok(_) => None,
+                        Err(e) => Some(Err(e)),
+                    };
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads a block into the buffer and returns its type and body length. Section headers and
+    /// interface descriptions update the state of the reader.
+    fn read_block(&mut self) -> io::Result<Option<(u32, usize)>> {
+        let mut hdr = [0u8; 8];
+        if read_full(&mut self.reader, &mut hdr)? == 0 {
+            return Ok(None);
+        }
+        let block_type = self.endian.u32(&hdr[0..4]);
+        let mut prefix = 0;
+        if block_type == block::SECTION_HEADER {
+            // The byte order of a section is only known from its magic.
+            let mut magic = [0u8; 4];
+            self.reader.read_exact(&mut magic)?;
+            self.endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+                (BYTE_ORDER_MAGIC, _) => Endian { big: false },
+                (_, BYTE_ORDER_MAGIC) => Endian { big: true },
+                _ => return Err(invalid("bad pcapng byte-order magic")),
+            };
+            self.interfaces.clear();
+            prefix = 4;
+        }
+        let total = self.endian.u32(&hdr[4..8]);
+        if total < 12 + prefix as u32 || !total.is_multiple_of(4) || total > MAX_BLOCK_LEN {
+            return Err(invalid("bad pcapng block length"));
+        }
+        let len = total as usize - 12;
+        let body = self.buf.get(len + 4);
+        body[..prefix].copy_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes()[..prefix]);
+        self.reader.read_exact(&mut body[prefix..])?;
+        if self.endian.u32(&body[len..]) != total {
+            return Err(invalid("pcapng block lengths differ"));
+        }
+
+        let body = &self.buf.get(len)[..];
+        match block_type {
+            block::SECTION_HEADER if len < 16 || self.endian.u16(&body[4..6]) != 1 => {
+                return Err(invalid("unsupported pcapng version"));
+            }
+            block::SECTION_HEADER => {
+                self.section_options.clear();
+                self.section_options.extend_from_slice(&body[16..]);
+            }
+            block::INTERFACE_DESCRIPTION => {
+                if len < 8 {
+                    return Err(invalid("short interface description"));
+                }
+                let options = Options {
+                    data: &body[8..],
+                    endian: self.endian,
+                };
+                let units_per_sec = match options.get(code::IF_TSRESOL) {
+                    None => 1_000_000,
+                    Some(&[r]) if r & 0x80 == 0 && r <= 19 => 10u64.pow(r as u32),
+                    Some(&[r]) if r & 0x80 != 0 && r & 0x7f <= 63 => 1 << (r & 0x7f),
+                    Some(_) => return Err(invalid("bad interface timestamp resolution")),
+                };
+                let name = options
+                    .get(code::IF_NAME)
+                    .map(|n| String::from_utf8_lossy(n).into_owned());
+                self.interfaces.push(Interface {
+                    link_type: LinkType::new(self.endian.u16(&body[0..2]) as u32),
+                    snaplen: self.endian.u32(&body[4..8]),
+                    units_per_sec,
+                    name,
+                });
+            }
+            _ => {}
+        }
+        Ok(Some((block_type, len)))
+    }
+
+    fn parse(&mut self, block_type: u32, len: usize) -> io::Result<Block<'_>> {
+        let endian = self.endian;
+        let options = |data| Options { data, endian };
+        let body = self.buf.get(len);
+        Ok(match block_type {
+            block::SECTION_HEADER => Block::Section {
+                options: options(&body[16.min(len)..]),
+            },
+            block::INTERFACE_DESCRIPTION => Block::Interface {
+                id: self.interfaces.len() as u32 - 1,
+                options: options(&body[8..]),
+            },
+            block::ENHANCED_PACKET => {
+                if len < 20 {
+                    return Err(invalid("short enhanced packet block"));
+                }
+                let id = endian.u32(&body[0..4]);
+                let ts = (endian.u32(&body[4..8]) as u64) << 32 | endian.u32(&body[8..12]) as u64;
+                let caplen = endian.u32(&body[12..16]) as usize;
+                let orig_len = endian.u32(&body[16..20]);
+                let interface = self
+                    .interfaces
+                    .get(id as usize)
+                    .ok_or_else(|| invalid("unknown pcapng interface"))?;
+                if 20 + caplen > len {
+                    return Err(invalid("pcapng packet exceeds its block"));
+                }
+                let (head, rest) = body.split_at_mut(20 + caplen);
+                let opts = &rest[(padded(caplen) - caplen).min(rest.len())..];
+                Block::Packet {
+                    interface: id,
+                    packet: Packet::new(
+                        interface.timestamp(ts),
+                        orig_len,
+                        interface.link_type,
+                        &mut head[20..],
+                    ),
+                    options: options(opts),
+                }
+            }
+            block::SIMPLE_PACKET => {
+                if len < 4 {
+                    return Err(invalid("short simple packet block"));
+                }
+                let interface = self
+                    .interfaces
+                    .first()
+                    .ok_or_else(|| invalid("unknown pcapng interface"))?;
+                let orig_len = endian.u32(&body[0..4]);
+                let mut caplen = (orig_len as usize).min(len - 4);
+                if interface.snaplen != 0 {
+                    caplen = caplen.min(interface.snaplen as usize);
+                }
+                Block::Packet {
+                    interface: 0,
+                    packet: Packet::new(
+                        Duration::ZERO,
+                        orig_len,
+                        interface.link_type,
+                        &mut body[4..4 + caplen],
+                    ),
+                    options: options(&[]),
+                }
+            }
+            block::NAME_RESOLUTION => {
+                let body = &body[..];
+                let mut end = 0;
+                while end + 4 <= len {
+                    let kind = endian.u16(&body[end..end + 2]);
+                    let rlen = endian.u16(&body[end + 2..end + 4]) as usize;
+                    end = (end + 4 + padded(rlen)).min(len);
+                    if kind == 0 {
+                        break;
+                    }
+                }
+                Block::NameResolution {
+                    records: NameRecords {
+                        data: &body[..end],
+                        endian,
+                    },
+                    options: options(&body[end..]),
+                }
+            }
+            block_type => Block::Other {
+                block_type,
+                body: &body[..],
+            },
+        })
+    }
+}
+
+/// Writes a little-endian pcapng file with a single section.
+pub struct Writer<W: Write> {
+    writer: W,
+    interfaces: Vec<(Resolution, u32)>,
+    block: Vec<u8>,
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes a Section Header Block with `options`.
+    pub fn new(writer: W, options: &[Opt<'_>]) -> io::Result<Self> {
+        let mut this = Self {
+            writer,
+            interfaces: Vec::new(),
+            block: Vec::new(),
+        };
+        this.block
+            .extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        this.block.extend_from_slice(&1u16.to_le_bytes());
+        this.block.extend_from_slice(&0u16.to_le_bytes());
+        // The section length is unknown while streaming.
+        this.block.extend_from_slice(&(-1i64).to_le_bytes());
+        this.finish(block::SECTION_HEADER, options)?;
+        Ok(this)
+    }
+
+    /// Writes an Interface Description Block and returns the id of the interface. Frames longer
+    /// than a non-zero `snaplen` are truncated.
+    pub fn add_interface(
+        &mut self,
+        link_type: LinkType,
+        snaplen: u32,
+        resolution: Resolution,
+        name: Option<&str>,
+        options: &[Opt<'_>],
+    ) -> io::Result<u32> {
+        if name.is_some_and(|name| name.len() > u16::MAX as usize) {
+            return Err(invalid("pcapng option too long"));
+        }
+        let link_type =
+            u16::try_from(link_type.get()).map_err(|_| invalid("link type out of range"))?;
+        self.block.extend_from_slice(&link_type.to_le_bytes());
+        self.block.extend_from_slice(&0u16.to_le_bytes());
+        self.block.extend_from_slice(&snaplen.to_le_bytes());
+        if let Some(name) = name {
+            self.option(Opt::Other {
+                code: code::IF_NAME,
+                value: name.as_bytes(),
+            });
+        }
+        if resolution == Resolution::Nano {
+            self.option(Opt::Other {
+                code: code::IF_TSRESOL,
+                value: &[9],
+            });
+        }
+        self.finish(block::INTERFACE_DESCRIPTION, options)?;
+        self.interfaces.push((resolution, snaplen));
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+
+    /// Writes an Enhanced Packet Block with a frame captured on `interface` at `timestamp`, the
+    /// time since the Unix epoch.
+    pub fn write_packet(
+        &mut self,
+        interface: u32,
+        timestamp: Duration,
+        data: &[u8],
+        options: &[Opt<'_>],
+    ) -> io::Result<()> {
+        let (resolution, snaplen) = *self
+            .interfaces
+            .get(interface as usize)
+            .ok_or_else(|| invalid("unknown pcapng interface"))?;
+        let captured = truncate(data, snaplen);
+        let ts = match resolution {
+            Resolution::Micro => timestamp.as_micros(),
+            Resolution::Nano => timestamp.as_nanos(),
+        };
+        let ts = u64::try_from(ts).map_err(|_| invalid("timestamp too late"))?;
+        self.block.extend_from_slice(&interface.to_le_bytes());
+        self.block
+            .extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        self.block.extend_from_slice(&(ts as u32).to_le_bytes());
+        self.block
+            .extend_from_slice(&(captured.len() as u32).to_le_bytes());
+        self.block
+            .extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.data(captured);
+        self.finish(block::ENHANCED_PACKET, options)
+    }
+
+    /// Writes a Simple Packet Block, which belongs to the first interface and carries neither
+    /// timestamp nor options.
+    pub fn write_simple_packet(&mut self, data: &[u8]) -> io::Result<()> {
+        let (_, snaplen) = *self
+            .interfaces
+            .first()
+            .ok_or_else(|| invalid("unknown pcapng interface"))?;
+        self.block
+            .extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.data(truncate(data, snaplen));
+        self.finish(block::SIMPLE_PACKET, &[])
+    }
+
+    /// Writes a Name Resolution Block with a record for each address and name.
+    pub fn write_name_resolution(
+        &mut self,
+        records: &[(Address, &str)],
+        options: &[Opt<'_>],
+    ) -> io::Result<()> {
+        // Nothing may be left in the block if a record is rejected.
+        let too_long = |(address, name): &(Address, &str)| {
+            address.record().1.len() + name.len() + 1 > u16::MAX as usize
+        };
+        if records.iter().any(too_long) {
+            return Err(invalid("name too long"));
+        }
+        for (address, name) in records {
+            let (kind, addr) = address.record();
+            let len = (addr.len() + name.len() + 1) as u16;
+            self.block.extend_from_slice(&kind.to_le_bytes());
+            self.block.extend_from_slice(&len.to_le_bytes());
+            self.block.extend_from_slice(addr);
+            self.block.extend_from_slice(name.as_bytes());
+            self.block.push(0);
+            self.pad();
+        }
+        self.block.extend_from_slice(&[0; 4]);
+        self.finish(block::NAME_RESOLUTION, options)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn data(&mut self, data: &[u8]) {
+        self.block.extend_from_slice(data);
+        self.pad();
+    }
+
+    fn pad(&mut self) {
+        self.block.resize(padded(self.block.len()), 0);
+    }
+
+    fn option(&mut self, option: Opt<'_>) {
+        self.block.extend_from_slice(&option.code().to_le_bytes());
+        self.block
+            .extend_from_slice(&(option.value_len() as u16).to_le_bytes());
+        match option {
+            Opt::Comment(comment) => self.block.extend_from_slice(comment.as_bytes()),
+            Opt::Custom { pen, data, .. } => {
+                self.block.extend_from_slice(&pen.to_le_bytes());
+                self.block.extend_from_slice(data);
+            }
+            Opt::Other { value, .. } => self.block.extend_from_slice(value),
+        }
+        self.pad();
+    }
+
+    /// Appends `options` to the block being built and writes it out.
+    fn finish(&mut self, block_type: u32, options: &[Opt<'_>]) -> io::Result<()> {
+        if options.iter().any(|o| o.value_len() > u16::MAX as usize) {
+            self.block.clear();
+            return Err(invalid("pcapng option too long"));
+        }
+        for option in options {
+            self.option(*option);
+        }
+        // An end of options is needed whenever the block has any.
+        let has_options = !options.is_empty()
+            || (block_type == block::INTERFACE_DESCRIPTION && self.block.len() > 8);
+        if has_options {
+            self.block.extend_from_slice(&[0; 4]);
+        }
+        let total = (self.block.len() + 12) as u32;
+        let result = (|| {
+            self.writer.write_all(&block_type.to_le_bytes())?;
+            self.writer.write_all(&total.to_le_bytes())?;
+            self.writer.write_all(&self.block)?;
+            self.writer.write_all(&total.to_le_bytes())
+        })();
+        self.block.clear();
+        result
+    }
+}
+
+fn truncate(data: &[u8], snaplen: u32) -> &[u8] {
+    match snaplen {
+        0 => data,
+        n => &data[..data.len().min(n as usize)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AutoNextHeader;
+
+    fn frame() -> [u8; 34] {
+        let mut frame = [0u8; 34];
+        let eth = crate::eth::Header {
+            dst_addr: crate::eth::BROADCAST,
+            src_addr: [2, 0, 0, 0, 0, 1],
+            ether_type: crate::eth::Type::IPV4,
+        };
+        crate::write_header(&mut frame, 0, &eth).unwrap();
+        let ip = crate::ipv4::Header {
+            tot_len: 20.into(),
+            ttl: 64,
+            proto: crate::ip::Proto::UDP,
+            source: [10, 0, 0, 1],
+            destination: [10, 0, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut frame, 14, &ip).unwrap();
+        frame
+    }
+
+    fn file() -> Vec<u8> {
+        let section = [
+            Opt::Comment("test capture"),
+            Opt::Other {
+                code: code::SHB_HARDWARE,
+                value: b"x86_64",
+            },
+        ];
+        let mut w = Writer::new(Vec::new(), &section).unwrap();
+        let eth = w
+            .add_interface(LinkType::ETHERNET, 0, Resolution::Micro, Some("eth0"), &[])
+            .unwrap();
+        let raw = w
+            .add_interface(LinkType::RAW, 16, Resolution::Nano, None, &[])
+            .unwrap();
+        let ts = Duration::new(1_700_000_000, 123_456_789);
+        w.write_packet(
+            eth,
+            ts,
+            &frame(),
+            &[
+                Opt::Comment("first"),
+                Opt::Custom {
+                    code: code::CUSTOM_BIN_COPY,
+                    pen: 32473,
+                    data: &[1, 2, 3],
+                },
+            ],
+        )
+        .unwrap();
+        w.write_packet(raw, ts, &frame()[14..], &[]).unwrap();
+        w.write_simple_packet(&frame()[..15]).unwrap();
+        w.write_name_resolution(
+            &[
+                (Address::Ipv4([10, 0, 0, 1]), "a.example"),
+                (Address::Ipv6([0xfd; 16]), "b.example"),
+            ],
+            &[Opt::Comment("names")],
+        )
+        .unwrap();
+        w.into_inner()
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = file();
+        let mut r = Reader::new(&file[..]).unwrap();
+        assert_eq!(Some("test capture"), r.section_options().comments().next());
+        assert_eq!(
+            Some(&b"x86_64"[..]),
+            r.section_options().get(code::SHB_HARDWARE)
+        );
+
+        assert!(matches!(
+            r.next_block(),
+            Some(Ok(Block::Interface { id: 0, .. }))
+        ));
+        assert!(matches!(
+            r.next_block(),
+            Some(Ok(Block::Interface { id: 1, .. }))
+        ));
+        assert_eq!(
+            Interface {
+                link_type: LinkType::ETHERNET,
+                snaplen: 0,
+                units_per_sec: 1_000_000,
+                name: Some(String::from("eth0")),
+            },
+            r.interfaces()[0]
+        );
+        assert_eq!(1_000_000_000, r.interfaces()[1].units_per_sec);
+
+        match r.next_block() {
+            Some(Ok(Block::Packet {
+                interface: 0,
+                mut packet,
+                options,
+            })) => {
+                assert_eq!(Duration::new(1_700_000_000, 123_456_000), packet.timestamp);
+                assert_eq!(frame(), packet.data());
+                let mut options = options;
+                assert_eq!(Some(Opt::Comment("first")), options.next());
+                assert_eq!(
+                    Some(Opt::Custom {
+                        code: code::CUSTOM_BIN_COPY,
+                        pen: 32473,
+                        data: &[1, 2, 3]
+                    }),
+                    options.next()
+                );
+                assert_eq!(None, options.next());
+                let range = packet.frame();
+                match packet.first_header() {
+                    Ok(crate::HeaderPtr::Eth(mut eth)) => {
+                        assert!(matches!(eth.next(range), Ok(crate::HeaderPtr::Ipv4(_))))
+                    }
+                    _ => panic!("expected Ethernet"),
+                }
+            }
+            _ => panic!("expected a packet"),
+        }
+
+        match r.next_block() {
+            Some(Ok(Block::Packet {
+                interface: 1,
+                packet,
+                ..
+            })) => {
+                assert_eq!(Duration::new(1_700_000_000, 123_456_789), packet.timestamp);
+                assert_eq!(16, packet.data().len());
+                assert_eq!(20, packet.orig_len);
+                assert_eq!(LinkType::RAW, packet.link_type);
+            }
+            _ => panic!("expected a packet"),
+        }
+
+        match r.next_block() {
+            Some(Ok(Block::Packet { packet, .. })) => {
+                assert_eq!(Duration::ZERO, packet.timestamp);
+                assert_eq!(frame()[..15], *packet.data());
+            }
+            _ => panic!("expected a simple packet"),
+        }
+
+        match r.next_block() {
+            Some(Ok(Block::NameResolution { records, options })) => {
+                let records: Vec<_> = records.collect();
+                assert_eq!(2, records.len());
+                assert_eq!(Address::Ipv4([10, 0, 0, 1]), records[0].address);
+                assert_eq!(Some("a.example"), records[0].names().next());
+                assert_eq!(Address::Ipv6([0xfd; 16]), records[1].address);
+                assert_eq!(Some("b.example"), records[1].names().next());
+                assert_eq!(Some("names"), options.comments().next());
+            }
+            _ => panic!("expected name resolution"),
+        }
+        assert!(r.next_block().is_none());
+    }
+
+    #[test]
+    fn packets() {
+        let file = file();
+        let mut r = Reader::new(&file[..]).unwrap();
+        let mut count = 0;
+        while let Some(p) = r.next_packet() {
+            p.unwrap();
+            count += 1;
+        }
+        assert_eq!(3, count);
+    }
+
+    #[test]
+    fn big_endian() {
+        let mut file = Vec::new();
+        let block = |file: &mut Vec<u8>, t: u32, body: &[u8]| {
+            let total = (body.len() + 12) as u32;
+            file.extend_from_slice(&t.to_be_bytes());
+            file.extend_from_slice(&total.to_be_bytes());
+            file.extend_from_slice(body);
+            file.extend_from_slice(&total.to_be_bytes());
+        };
+        let mut shb = Vec::new();
+        shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_be_bytes());
+        shb.extend_from_slice(&[0, 1, 0, 0]);
+        shb.extend_from_slice(&[0xff; 8]);
+        block(&mut file, block::SECTION_HEADER, &shb);
+        // Ethernet, with timestamps in 2^-10 seconds.
+        block(
+            &mut file,
+            block::INTERFACE_DESCRIPTION,
+            &[
+                0, 1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1, 0x8a, 0, 0, 0, 0, 0, 0, 0,
+            ],
+        );
+        let mut epb = Vec::new();
+        epb.extend_from_slice(&0u32.to_be_bytes());
+        epb.extend_from_slice(&0u32.to_be_bytes());
+        epb.extend_from_slice(&1536u32.to_be_bytes());
+        epb.extend_from_slice(&2u32.to_be_bytes());
+        epb.extend_from_slice(&2u32.to_be_bytes());
+        epb.extend_from_slice(&[0xab, 0xcd, 0, 0]);
+        block(&mut file, block::ENHANCED_PACKET, &epb);
+
+        let mut r = Reader::new(&file[..]).unwrap();
+        let p = r.next_packet().unwrap().unwrap();
+        assert_eq!(Duration::from_millis(1500), p.timestamp);
+        assert_eq!([0xab, 0xcd], p.data());
+        assert!(r.next_packet().is_none());
+
+        // A second section in the other byte order.
+        let mut second = file.clone();
+        second.extend_from_slice(&self::file());
+        let mut r = Reader::new(&second[..]).unwrap();
+        let mut count = 0;
+        while let Some(p) = r.next_packet() {
+            p.unwrap();
+            count += 1;
+        }
+        assert_eq!(4, count);
+        assert_eq!(2, r.interfaces().len());
+        assert_eq!(Some("test capture"), r.section_options().comments().next());
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(Reader::new(&[0u8; 28][..]).is_err());
+        let file = file();
+        // A length that doesn't match the trailing one.
+        let mut bad = file.clone();
+        let shb = u32::from_le_bytes(bad[4..8].try_into().unwrap()) as usize;
+        bad[shb + 4] += 4;
+        let mut r = Reader::new(&bad[..]).unwrap();
+        assert!(r.next_block().unwrap().is_err());
+        // A file that ends inside a block.
+        let mut r = Reader::new(&file[..file.len() - 2]).unwrap();
+        let mut failed = false;
+        while let Some(b) = r.next_block() {
+            failed |= b.is_err();
+        }
+        assert!(failed);
+        // A packet on an interface that wasn't described.
+        let mut w = Writer::new(Vec::new(), &[]).unwrap();
+        assert!(w.write_packet(0, Duration::ZERO, &[], &[]).is_err());
+        // A record that doesn't fit leaves nothing behind for the next block.
+        let mut w = Writer::new(Vec::new(), &[]).unwrap();
+        w.add_interface(LinkType::ETHERNET, 0, Resolution::Micro, None, &[])
+            .unwrap();
+        let long = "n".repeat(65536);
+        let records = [
+            (Address::Ipv4([10, 0, 0, 1]), "a.example"),
+            (Address::Ipv4([10, 0, 0, 2]), &long[..]),
+        ];
+        assert!(w.write_name_resolution(&records, &[]).is_err());
+        w.write_packet(0, Duration::ZERO, &frame(), &[]).unwrap();
+        let file = w.into_inner();
+        let mut r = Reader::new(&file[..]).unwrap();
+        assert!(matches!(r.next_block(), Some(Ok(Block::Interface { .. }))));
+        match r.next_block() {
+            Some(Ok(Block::Packet { packet, .. })) => assert_eq!(frame(), packet.data()),
+            _ => panic!("expected a packet"),
+        }
+        assert!(r.next_block().is_none());
+        let mut w = Writer::new(Vec::new(), &[]).unwrap();
+
+        // An interface name that doesn't fit an option.
+        let name = "e".repeat(65536);
+        let link = LinkType::ETHERNET;
+        assert!(w
+            .add_interface(link, 0, Resolution::Micro, Some(&name), &[])
+            .is_err());
+        assert_eq!(
+            Ok(0),
+            w.add_interface(link, 0, Resolution::Micro, Some(&name[1..]), &[])
+                .map_err(|_| ())
+        );
+    }
+}
@@ -0,0 +1,897 @@
+//! Fragment reassembly with bounded memory.
+//!
+//! [`Reassembler`] is a fixed-capacity table that never allocates: it holds `SLOTS` datagrams of
+//! up to `N` bytes each. Time is supplied by the caller on every insert, in whatever unit the
+//! timeout was given in, so the table works the same in userspace and in environments without a
+//! clock. When the table is full, the oldest datagram in progress is evicted, and a datagram is
+//! abandoned as soon as it exceeds its buffer or [`MAX_FRAGMENTS`], which bounds the work and
+//! memory an attacker can tie up with a fragment flood.
+//!
+//! Overlapping fragments cause the whole datagram to be discarded, along with any of its fragments
+//! that arrive later until the datagram times out (RFC 5722). Exact duplicates are ignored.
+
+use crate::be16;
+
+/// Maximum number of fragments accepted for one datagram.
+pub const MAX_FRAGMENTS: usize = 64;
+
+/// Longest datagram that the 16-bit length field of the reassembled header can describe.
+const MAX_DATAGRAM: usize = u16::MAX as usize;
+
+/// Room reserved in front of the payload for the unfragmentable headers, which are taken from the
+/// first fragment.
+pub const HEADROOM: usize = 128;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The packet is not a fragment and can be processed as is.
+    NotFragment,
+    /// The packet is truncated or its fragment fields are inconsistent.
+    Malformed,
+    /// The reassembled datagram would not fit in its buffer, or would be longer than its 16-bit
+    /// length field can describe. The datagram has been discarded.
+    TooLarge,
+    /// The datagram has more than [`MAX_FRAGMENTS`] fragments.
+    TooManyFragments,
+    /// The fragment overlaps one received earlier. The datagram has been discarded.
+    Overlap,
+    /// The fragment belongs to a datagram that was discarded and has not yet timed out.
+    Discarded,
+    /// The IPv6 packet is an atomic fragment (RFC 6946). It is complete and must be processed on
+    /// its own, past its Fragment header, without involving any other fragment.
+    AtomicFragment,
+}
+
+/// Identifies the datagram that an IPv4 fragment belongs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ipv4Key {
+    pub source: [u8; 4],
+    pub destination: [u8; 4],
+    pub id: be16,
+    pub proto: super::ip::Proto,
+}
+
+/// Identifies the datagram that an IPv6 fragment belongs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ipv6Key {
+    pub source: [u8; 16],
+    pub destination: [u8; 16],
+    pub identification: crate::be32,
+}
+
+/// A fragment as seen by the protocol independent part of reassembly.
+pub(crate) struct Fragment<'a> {
+    /// Offset of the payload in the reassembled datagram, in bytes.
+    pub offset: usize,
+    /// True unless this is the last fragment.
+    pub more: bool,
+    /// The fragmentable part carried by this fragment.
+    pub payload: &'a [u8],
+    /// The unfragmentable headers, only used from the fragment at offset 0.
+    pub prefix: &'a [u8],
+    /// Bytes of the prefix that the length field of the reassembled packet doesn't count.
+    pub uncounted: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Active,
+    Discarding,
+    Done,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Entry<K> {
+    key: K,
+    state: State,
+    created: u64,
+    prefix_len: Option<usize>,
+    total: Option<usize>,
+    received: usize,
+    count: usize,
+    ranges: [(usize, usize); MAX_FRAGMENTS],
+}
+
+impl<K> Entry<K> {
+    fn new(key: K, now: u64) -> Self {
+        Self {
+            key,
+            state: State::Active,
+            created: now,
+            prefix_len: None,
+            total: None,
+            received: 0,
+            count: 0,
+            ranges: [(0, 0); MAX_FRAGMENTS],
+        }
+    }
+
+    fn discard(&mut self, error: Error) -> Error {
+        self.state = State::Discarding;
+        error
+    }
+
+    /// Adds a fragment, returning the length of the prefix and the payload once the datagram is
+    /// complete. The datagram is laid out in `buf` with its payload at `HEADROOM` and the prefix
+    /// right in front of it.
+    fn add<B: Storage>(
+        &mut self,
+        buf: &mut B,
+        frag: &Fragment<'_>,
+    ) -> Result<Option<(usize, usize)>, Error> {
+        match self.state {
+            State::Active => {}
+            State::Discarding => return Err(Error::Discarded),
+            State::Done => return Err(Error::Malformed),
+        }
+
+        let start = frag.offset;
+        let end = start + frag.payload.len();
+        if frag.more && (frag.payload.is_empty() || !frag.payload.len().is_multiple_of(8)) {
+            return Err(Error::Malformed);
+        }
+
+        let ranges = &self.ranges[..self.count];
+        if ranges.contains(&(start, end)) {
+            return Ok(None);
+        }
+        if ranges.iter().any(|&(s, e)| start < e && s < end) {
+            return Err(self.discard(Error::Overlap));
+        }
+        match (frag.more, self.total) {
+            (false, Some(_)) => return Err(self.discard(Error::Malformed)),
+            (false, None) if ranges.iter().any(|&(_, e)| e > end) => {
+                return Err(self.discard(Error::Malformed))
+            }
+            (true, Some(total)) if end > total => return Err(self.discard(Error::Malformed)),
+            _ => {}
+        }
+        if self.count == MAX_FRAGMENTS {
+            return Err(self.discard(Error::TooManyFragments));
+        }
+        if start == 0 && frag.prefix.len() > HEADROOM {
+            return Err(self.discard(Error::TooLarge));
+        }
+        // The headers of the first fragment go in front of the payload, so the datagram can't be
+        // told to be too long until both it and the furthest fragment have arrived.
+        let prefix = match start {
+            0 => frag.prefix.len(),
+            _ => self.prefix_len.unwrap_or(frag.prefix.len()),
+        };
+        let furthest = ranges.iter().map(|&(_, e)| e).fold(end, usize::max);
+        if prefix.saturating_sub(frag.uncounted) + furthest > MAX_DATAGRAM {
+            return Err(self.discard(Error::TooLarge));
+        }
+
+        let bytes = match buf.grow(HEADROOM + end) {
+            Some(bytes) => bytes,
+            None => return Err(self.discard(Error::TooLarge)),
+        };
+        bytes[HEADROOM + start..HEADROOM + end].copy_from_slice(frag.payload);
+        if start == 0 {
+            bytes[HEADROOM - frag.prefix.len()..HEADROOM].copy_from_slice(frag.prefix);
+            self.prefix_len = Some(frag.prefix.len());
+        }
+        if !frag.more {
+            self.total = Some(end);
+        }
+        self.ranges[self.count] = (start, end);
+        self.count += 1;
+        self.received += end - start;
+
+        match (self.prefix_len, self.total) {
+            (Some(prefix), Some(total)) if self.received == total => {
+                self.state = State::Done;
+                Ok(Some((prefix, total)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Length of the prefix and payload of a completed datagram, and the buffer holding it.
+type Completed<'a> = (usize, usize, &'a mut [u8]);
+
+/// Backing storage for one datagram.
+trait Storage {
+    fn grow(&mut self, len: usize) -> Option<&mut [u8]>;
+}
+
+impl<const N: usize> Storage for [u8; N] {
+    #[inline(always)]
+    fn grow(&mut self, len: usize) -> Option<&mut [u8]> {
+        match len <= N {
+            true => Some(self),
+            false => None,
+        }
+    }
+}
+
+/// Finds the entry for `key`, reusing a free or expired slot or evicting the oldest datagram if
+/// there is none. Returns `None` only if `entries` is empty.
+fn lookup<K: Copy + PartialEq>(
+    entries: &mut [Option<Entry<K>>],
+    key: K,
+    now: u64,
+    timeout: u64,
+) -> Option<usize> {
+    let mut free = None;
+    let mut oldest: Option<(usize, u64)> = None;
+    for (i, slot) in entries.iter_mut().enumerate() {
+        if let Some(e) = slot {
+            if e.state == State::Done || now.saturating_sub(e.created) >= timeout {
+                *slot = None;
+            }
+        }
+        match slot {
+            Some(e) if e.key == key => return Some(i),
+            Some(e) => {
+                if oldest.is_none_or(|(_, t)| e.created < t) {
+                    oldest = Some((i, e.created));
+                }
+            }
+            None => {
+                free.get_or_insert(i);
+            }
+        }
+    }
+    let i = free.or(oldest.map(|(i, _)| i))?;
+    entries[i] = Some(Entry::new(key, now));
+    Some(i)
+}
+
+/// Fixes up the first fragment's IPv4 header in front of the reassembled payload.
+fn finish_ipv4(buf: &mut [u8], prefix: usize, total: usize) -> &[u8] {
+    let start = HEADROOM - prefix;
+    let packet = &mut buf[start..HEADROOM + total];
+    let mut ip: super::ipv4::Header = match super::read_header(packet, 0) {
+        Ok(ip) => ip,
+        Err(_) => return packet,
+    };
+    ip.tot_len = ((prefix + total) as u16).into();
+    ip.set_more_fragments(false);
+    ip.set_fragment_offset(0);
+    ip.check = be16::ZERO;
+    let _ = super::write_header(packet, 0, &ip);
+    ip.check = super::checksum::checksum(&packet[..prefix]);
+    let _ = super::write_header(packet, 0, &ip);
+    packet
+}
+
+/// Splits an IPv4 packet into its reassembly key and fragment.
+fn ipv4_fragment(packet: &[u8]) -> Result<(Ipv4Key, Fragment<'_>), Error> {
+    let ip: super::ipv4::Header = super::read_header(packet, 0).map_err(|_| Error::Malformed)?;
+    let ihl = ip.header_len();
+    let len = ip.tot_len.get() as usize;
+    if ip.version() != 4 || ihl < super::ipv4::Header::LEN || len < ihl || len > packet.len() {
+        return Err(Error::Malformed);
+    }
+    if !ip.is_fragment() {
+        return Err(Error::NotFragment);
+    }
+    let key = Ipv4Key {
+        source: ip.source,
+        destination: ip.destination,
+        id: ip.id,
+        proto: ip.proto,
+    };
+    let frag = Fragment {
+        offset: ip.fragment_offset() as usize,
+        more: ip.more_fragments(),
+        payload: &packet[ihl..len],
+        prefix: &packet[..ihl],
+        uncounted: 0,
+    };
+    Ok((key, frag))
+}
+
+/// Most extension headers walked in the unfragmentable part of an IPv6 packet.
+const MAX_EXT_HEADERS: usize = 8;
+
+/// Splits an IPv6 packet into its reassembly key and fragment. The prefix holds the unfragmentable
+/// part followed by the Fragment header, which [`finish_ipv6`] removes.
+fn ipv6_fragment(packet: &[u8]) -> Result<(Ipv6Key, Fragment<'_>), Error> {
+    use super::ipv6::{ExtHeader, FragmentHeader, Header};
+
+    let ip: Header = super::read_header(packet, 0).map_err(|_| Error::Malformed)?;
+    let len = Header::LEN + ip.payload_len.get() as usize;
+    if ip.version() != 6 || len > packet.len() {
+        return Err(Error::Malformed);
+    }
+
+    let mut proto = ip.proto;
+    let mut offset = Header::LEN;
+    for _ in 0..MAX_EXT_HEADERS {
+        if !proto.is_ipv6_ext() {
+            break;
+        }
+        let ext: ExtHeader =
+            super::read_header(&packet[..len], offset).map_err(|_| Error::Malformed)?;
+        proto = ext.next_header;
+        offset += ext.header_len();
+    }
+    if proto != super::ip::Proto::FRAGMENT {
+        return Err(Error::NotFragment);
+    }
+    let frag: FragmentHeader =
+        super::read_header(&packet[..len], offset).map_err(|_| Error::Malformed)?;
+    if frag.is_atomic() {
+        return Err(Error::AtomicFragment);
+    }
+
+    let key = Ipv6Key {
+        source: ip.source,
+        destination: ip.destination,
+        identification: frag.identification,
+    };
+    let end = offset + FragmentHeader::LEN;
+    let frag = Fragment {
+        offset: frag.offset() as usize,
+        more: frag.more_fragments(),
+        payload: &packet[end..len],
+        prefix: &packet[..end],
+        // The payload length doesn't count the fixed header, and the Fragment header is removed.
+        uncounted: Header::LEN + FragmentHeader::LEN,
+    };
+    Ok((key, frag))
+}
+
+/// Removes the Fragment header from the first fragment's headers in front of the reassembled
+/// payload, pointing the header before it at the fragmented protocol.
+fn finish_ipv6(buf: &mut [u8], prefix: usize, total: usize) -> &[u8] {
+    use super::ipv6::{ExtHeader, FragmentHeader, Header};
+
+    let frag_start = HEADROOM - FragmentHeader::LEN;
+    let frag: FragmentHeader = match super::read_header(buf, frag_start) {
+        Ok(frag) => frag,
+        Err(_) => return &buf[HEADROOM - prefix..HEADROOM + total],
+    };
+    let start = HEADROOM - prefix;
+    let unfragmentable = prefix - FragmentHeader::LEN;
+
+    // Find the next header field that names the Fragment header. The headers were walked by
+    // ipv6_fragment already, so they are known to be in bounds.
+    let mut next = start + 6;
+    let mut offset = start + Header::LEN;
+    while offset < frag_start {
+        let ext: ExtHeader = match super::read_header(buf, offset) {
+            Ok(ext) => ext,
+            Err(_) => break,
+        };
+        next = offset;
+        offset += ext.header_len();
+    }
+    buf[next] = frag.next_header.into();
+    buf.copy_within(start..frag_start, start + FragmentHeader::LEN);
+
+    let start = start + FragmentHeader::LEN;
+    let payload_len = (unfragmentable - Header::LEN + total) as u16;
+    buf[start + 4..start + 6].copy_from_slice(&payload_len.to_be_bytes());
+    &buf[start..HEADROOM + total]
+}
+
+/// Fixed-capacity reassembly table holding up to `SLOTS` datagrams in buffers of `N` bytes, of
+/// which [`HEADROOM`] is reserved for headers.
+pub struct Reassembler<K, const SLOTS: usize, const N: usize> {
+    entries: [Option<Entry<K>>; SLOTS],
+    buffers: [[u8; N]; SLOTS],
+    timeout: u64,
+}
+
+impl<K: Copy + PartialEq, const SLOTS: usize, const N: usize> Reassembler<K, SLOTS, N> {
+    /// Returns an empty table that drops datagrams not completed within `timeout`, measured in
+    /// the same unit as the timestamps passed to the insert methods.
+    pub fn new(timeout: u64) -> Self {
+        Self {
+            entries: [None; SLOTS],
+            buffers: [[0; N]; SLOTS],
+            timeout,
+        }
+    }
+
+    /// Returns the number of datagrams being reassembled or discarded.
+    pub fn pending(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e, Some(e) if e.state != State::Done))
+            .count()
+    }
+
+    /// Drops every datagram that has timed out at `now`.
+    pub fn expire(&mut self, now: u64) {
+        for slot in self.entries.iter_mut() {
+            if matches!(slot, Some(e) if now.saturating_sub(e.created) >= self.timeout) {
+                *slot = None;
+            }
+        }
+    }
+
+    fn insert(
+        &mut self,
+        now: u64,
+        key: K,
+        frag: &Fragment<'_>,
+    ) -> Result<Option<Completed<'_>>, Error> {
+        let i = lookup(&mut self.entries, key, now, self.timeout).ok_or(Error::TooLarge)?;
+        let entry = self.entries[i].as_mut().ok_or(Error::Malformed)?;
+        Ok(entry
+            .add(&mut self.buffers[i], frag)?
+            .map(|(prefix, total)| (prefix, total, &mut self.buffers[i][..])))
+    }
+}
+
+impl<const SLOTS: usize, const N: usize> Reassembler<Ipv4Key, SLOTS, N> {
+    /// Adds the IPv4 fragment in `packet`, which starts with the IPv4 header, at time `now`.
+    /// Returns the reassembled packet once all of its fragments have arrived; it stays valid until
+    /// the next call.
+    pub fn insert_ipv4(&mut self, now: u64, packet: &[u8]) -> Result<Option<&[u8]>, Error> {
+        let (key, frag) = ipv4_fragment(packet)?;
+        Ok(self
+            .insert(now, key, &frag)?
+            .map(|(prefix, total, buf)| finish_ipv4(buf, prefix, total)))
+    }
+}
+
+impl<const SLOTS: usize, const N: usize> Reassembler<Ipv6Key, SLOTS, N> {
+    /// Adds the IPv6 fragment in `packet`, which starts with the IPv6 header, at time `now`.
+    /// Returns the reassembled packet, without its Fragment header, once all of its fragments
+    /// have arrived; it stays valid until the next call.
+    pub fn insert_ipv6(&mut self, now: u64, packet: &[u8]) -> Result<Option<&[u8]>, Error> {
+        let (key, frag) = ipv6_fragment(packet)?;
+        Ok(self
+            .insert(now, key, &frag)?
+            .map(|(prefix, total, buf)| finish_ipv6(buf, prefix, total)))
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod growable {
+    use super::*;
+    use alloc::vec::Vec;
+
+    struct Buffer {
+        bytes: Vec<u8>,
+        max: usize,
+    }
+
+    impl Storage for Buffer {
+        fn grow(&mut self, len: usize) -> Option<&mut [u8]> {
+            if len > self.max {
+                return None;
+            }
+            if self.bytes.len() < len {
+                self.bytes.resize(len, 0);
+            }
+            Some(&mut self.bytes)
+        }
+    }
+
+    /// Reassembly table that allocates buffers as datagrams arrive, up to `max_datagrams` at a
+    /// time of at most `max_len` bytes each (not counting [`HEADROOM`]).
+    pub struct GrowableReassembler<K> {
+        entries: Vec<Option<Entry<K>>>,
+        buffers: Vec<Buffer>,
+        max_datagrams: usize,
+        max_len: usize,
+        timeout: u64,
+    }
+
+    impl<K: Copy + PartialEq> GrowableReassembler<K> {
+        pub fn new(max_datagrams: usize, max_len: usize, timeout: u64) -> Self {
+            Self {
+                entries: Vec::new(),
+                buffers: Vec::new(),
+                max_datagrams,
+                max_len,
+                timeout,
+            }
+        }
+
+        /// Returns the number of datagrams being reassembled or discarded.
+        pub fn pending(&self) -> usize {
+            self.entries
+                .iter()
+                .filter(|e| matches!(e, Some(e) if e.state != State::Done))
+                .count()
+        }
+
+        fn insert(
+            &mut self,
+            now: u64,
+            key: K,
+            frag: &Fragment<'_>,
+        ) -> Result<Option<Completed<'_>>, Error> {
+            let known = self
+                .entries
+                .iter()
+                .any(|e| matches!(e, Some(e) if e.key == key));
+            let full = self.entries.iter().all(|e| e.is_some());
+            if !known && full && self.entries.len() < self.max_datagrams {
+                self.entries.push(None);
+                self.buffers.push(Buffer {
+                    bytes: Vec::new(),
+                    max: HEADROOM + self.max_len,
+                });
+            }
+            let i = lookup(&mut self.entries, key, now, self.timeout).ok_or(Error::TooLarge)?;
+            if !known {
+                // Release memory held for a previous datagram.
+                self.buffers[i].bytes = Vec::new();
+                self.buffers[i].grow(HEADROOM);
+            }
+            let entry = self.entries[i].as_mut().ok_or(Error::Malformed)?;
+            let buf = &mut self.buffers[i];
+            Ok(entry
+                .add(buf, frag)?
+                .map(|(prefix, total)| (prefix, total, &mut buf.bytes[..])))
+        }
+    }
+
+    impl GrowableReassembler<Ipv4Key> {
+        /// Same as [`Reassembler::insert_ipv4`].
+        pub fn insert_ipv4(&mut self, now: u64, packet: &[u8]) -> Result<Option<&[u8]>, Error> {
+            let (key, frag) = ipv4_fragment(packet)?;
+            Ok(self
+                .insert(now, key, &frag)?
+                .map(|(prefix, total, buf)| finish_ipv4(buf, prefix, total)))
+        }
+    }
+
+    impl GrowableReassembler<Ipv6Key> {
+        /// Same as [`Reassembler::insert_ipv6`].
+        pub fn insert_ipv6(&mut self, now: u64, packet: &[u8]) -> Result<Option<&[u8]>, Error> {
+            let (key, frag) = ipv6_fragment(packet)?;
+            Ok(self
+                .insert(now, key, &frag)?
+                .map(|(prefix, total, buf)| finish_ipv6(buf, prefix, total)))
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub use growable::GrowableReassembler;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD_LEN: usize = 100;
+
+    fn payload() -> [u8; PAYLOAD_LEN] {
+        core::array::from_fn(|i| i as u8)
+    }
+
+    /// Builds an IPv4 fragment carrying `payload()[start..end]`.
+    fn fragment(id: u16, start: usize, end: usize, more: bool) -> ([u8; 128], usize) {
+        let mut ip = super::super::ipv4::Header {
+            tot_len: ((20 + end - start) as u16).into(),
+            id: id.into(),
+            ttl: 64,
+            proto: super::super::ip::Proto::UDP,
+            source: [10, 0, 0, 1],
+            destination: [10, 0, 0, 2],
+            ..Default::default()
+        };
+        ip.set_more_fragments(more);
+        ip.set_fragment_offset(start as u16);
+        ip.update_checksum();
+        let mut buf = [0u8; 128];
+        crate::write_header(&mut buf, 0, &ip).unwrap();
+        buf[20..20 + end - start].copy_from_slice(&payload()[start..end]);
+        (buf, 20 + end - start)
+    }
+
+    fn check_datagram(packet: &[u8]) {
+        assert_eq!(20 + PAYLOAD_LEN, packet.len());
+        let ip: crate::ipv4::Header = crate::read_header(packet, 0).unwrap();
+        assert_eq!((20 + PAYLOAD_LEN) as u16, ip.tot_len.get());
+        assert!(!ip.is_fragment());
+        assert!(crate::checksum::verify(&packet[..20]));
+        assert_eq!(payload(), packet[20..]);
+    }
+
+    type Table = Reassembler<Ipv4Key, 2, 256>;
+
+    #[test]
+    fn not_fragment() {
+        let mut t = Table::new(30);
+        let (buf, len) = fragment(1, 0, PAYLOAD_LEN, false);
+        assert_eq!(Err(Error::NotFragment), t.insert_ipv4(0, &buf[..len]));
+        assert_eq!(Err(Error::Malformed), t.insert_ipv4(0, &buf[..len - 1]));
+    }
+
+    #[test]
+    fn in_order() {
+        let mut t = Table::new(30);
+        let (a, la) = fragment(1, 0, 48, true);
+        let (b, lb) = fragment(1, 48, 96, true);
+        let (c, lc) = fragment(1, 96, PAYLOAD_LEN, false);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a[..la]));
+        assert_eq!(Ok(None), t.insert_ipv4(1, &b[..lb]));
+        check_datagram(t.insert_ipv4(2, &c[..lc]).unwrap().unwrap());
+        assert_eq!(0, t.pending());
+    }
+
+    #[test]
+    fn out_of_order_and_duplicate() {
+        let mut t = Table::new(30);
+        let (a, la) = fragment(1, 0, 48, true);
+        let (b, lb) = fragment(1, 48, 96, true);
+        let (c, lc) = fragment(1, 96, PAYLOAD_LEN, false);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &c[..lc]));
+        assert_eq!(Ok(None), t.insert_ipv4(0, &b[..lb]));
+        assert_eq!(Ok(None), t.insert_ipv4(0, &b[..lb]));
+        check_datagram(t.insert_ipv4(0, &a[..la]).unwrap().unwrap());
+    }
+
+    #[test]
+    fn interleaved() {
+        let mut t = Table::new(30);
+        let (a1, l1) = fragment(1, 0, 48, true);
+        let (a2, l2) = fragment(2, 0, 48, true);
+        let (b1, m1) = fragment(1, 48, PAYLOAD_LEN, false);
+        let (b2, m2) = fragment(2, 48, PAYLOAD_LEN, false);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a1[..l1]));
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a2[..l2]));
+        assert_eq!(2, t.pending());
+        check_datagram(t.insert_ipv4(0, &b2[..m2]).unwrap().unwrap());
+        check_datagram(t.insert_ipv4(0, &b1[..m1]).unwrap().unwrap());
+    }
+
+    #[test]
+    fn overlap() {
+        let mut t = Table::new(30);
+        let (a, la) = fragment(1, 0, 48, true);
+        let (b, lb) = fragment(1, 40, 88, true);
+        let (c, lc) = fragment(1, 48, PAYLOAD_LEN, false);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a[..la]));
+        assert_eq!(Err(Error::Overlap), t.insert_ipv4(1, &b[..lb]));
+        // The rest of the datagram is dropped until it times out.
+        assert_eq!(Err(Error::Discarded), t.insert_ipv4(2, &c[..lc]));
+        assert_eq!(Ok(None), t.insert_ipv4(30, &a[..la]));
+        check_datagram(t.insert_ipv4(31, &c[..lc]).unwrap().unwrap());
+    }
+
+    #[test]
+    fn timeout() {
+        let mut t = Table::new(30);
+        let (a, la) = fragment(1, 0, 48, true);
+        let (b, lb) = fragment(1, 48, PAYLOAD_LEN, false);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a[..la]));
+        t.expire(29);
+        assert_eq!(1, t.pending());
+        t.expire(30);
+        assert_eq!(0, t.pending());
+        assert_eq!(Ok(None), t.insert_ipv4(31, &b[..lb]));
+    }
+
+    #[test]
+    fn eviction() {
+        let mut t = Table::new(30);
+        for id in 1..=3 {
+            let (a, la) = fragment(id, 0, 48, true);
+            assert_eq!(Ok(None), t.insert_ipv4(id as u64, &a[..la]));
+        }
+        assert_eq!(2, t.pending());
+        // The first datagram was evicted to make room for the third.
+        let (b, lb) = fragment(1, 48, PAYLOAD_LEN, false);
+        assert_eq!(Ok(None), t.insert_ipv4(4, &b[..lb]));
+        let (b, lb) = fragment(3, 48, PAYLOAD_LEN, false);
+        check_datagram(t.insert_ipv4(5, &b[..lb]).unwrap().unwrap());
+    }
+
+    #[test]
+    fn limits() {
+        let mut t = Reassembler::<Ipv4Key, 1, { HEADROOM + 64 }>::new(30);
+        let (a, la) = fragment(1, 48, PAYLOAD_LEN, false);
+        assert_eq!(Err(Error::TooLarge), t.insert_ipv4(0, &a[..la]));
+
+        let mut t = Table::new(30);
+        let (a, la) = fragment(1, 0, 44, true);
+        assert_eq!(Err(Error::Malformed), t.insert_ipv4(0, &a[..la]));
+
+        let (a, la) = fragment(1, 48, PAYLOAD_LEN, false);
+        let (b, lb) = fragment(1, 0, 56, true);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a[..la]));
+        assert_eq!(Err(Error::Overlap), t.insert_ipv4(0, &b[..lb]));
+    }
+
+    #[test]
+    fn too_long() {
+        // Builds an IPv4 fragment with `len` bytes of payload at `offset`, behind `options` bytes
+        // of IP options.
+        fn at(offset: usize, len: usize, more: bool, options: usize) -> ([u8; 64], usize) {
+            let ihl = 20 + options;
+            let mut ip = super::super::ipv4::Header {
+                tot_len: ((ihl + len) as u16).into(),
+                id: 9.into(),
+                ..Default::default()
+            };
+            ip.set_more_fragments(more);
+            ip.set_fragment_offset(offset as u16);
+            let mut buf = [0u8; 64];
+            crate::write_header(&mut buf, 0, &ip).unwrap();
+            buf[0] = 0x40 | (ihl / 4) as u8;
+            (buf, ihl + len)
+        }
+
+        let mut t = Reassembler::<Ipv4Key, 1, { HEADROOM + 0x20000 }>::new(30);
+        // The last possible offset, which would give a datagram of over 128 KiB.
+        let (a, la) = at(8191 * 8, 16, false, 0);
+        assert_eq!(Err(Error::TooLarge), t.insert_ipv4(0, &a[..la]));
+        assert_eq!(Err(Error::Discarded), t.insert_ipv4(0, &a[..la]));
+
+        // Exactly 65535 bytes is accepted.
+        let mut t = Reassembler::<Ipv4Key, 1, { HEADROOM + 0x20000 }>::new(30);
+        let (a, la) = at(65504, 11, false, 0);
+        let (b, lb) = at(0, 8, true, 0);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a[..la]));
+        assert_eq!(Ok(None), t.insert_ipv4(0, &b[..lb]));
+
+        // Too long once the options of a first fragment arriving last are counted.
+        let mut t = Reassembler::<Ipv4Key, 1, { HEADROOM + 0x20000 }>::new(30);
+        let (b, lb) = at(0, 8, true, 4);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &a[..la]));
+        assert_eq!(Err(Error::TooLarge), t.insert_ipv4(0, &b[..lb]));
+    }
+
+    #[test]
+    fn too_many_fragments() {
+        let mut t = Reassembler::<Ipv4Key, 1, 1024>::new(30);
+        let mut ip = super::super::ipv4::Header {
+            tot_len: 28u16.into(),
+            ..Default::default()
+        };
+        ip.set_more_fragments(true);
+        let mut buf = [0u8; 28];
+        for i in 0..=MAX_FRAGMENTS {
+            ip.set_fragment_offset(8 * i as u16);
+            crate::write_header(&mut buf, 0, &ip).unwrap();
+            let expected = match i {
+                MAX_FRAGMENTS => Err(Error::TooManyFragments),
+                _ => Ok(None),
+            };
+            assert_eq!(expected, t.insert_ipv4(0, &buf));
+        }
+    }
+
+    /// Builds an IPv6 fragment carrying `payload()[start..end]`, optionally behind a Hop-by-Hop
+    /// Options header.
+    fn fragment6(id: u32, start: usize, end: usize, more: bool, hbh: bool) -> ([u8; 192], usize) {
+        use crate::ipv6::{FragmentHeader, Header};
+
+        let ext = if hbh { 8 } else { 0 };
+        let ip = Header {
+            payload_len: ((ext + 8 + end - start) as u16).into(),
+            proto: match hbh {
+                true => crate::ip::Proto::HOPOPTS,
+                false => crate::ip::Proto::FRAGMENT,
+            },
+            source: [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            destination: [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            ..Default::default()
+        };
+        let mut frag = FragmentHeader {
+            next_header: crate::ip::Proto::UDP,
+            identification: id.into(),
+            ..Default::default()
+        };
+        frag.set_offset(start as u16);
+        frag.set_more_fragments(more);
+
+        let mut buf = [0u8; 192];
+        crate::write_header(&mut buf, 0, &ip).unwrap();
+        // Hop-by-Hop Options header padded with a PadN option.
+        buf[40..48].copy_from_slice(&[44, 0, 1, 4, 0, 0, 0, 0]);
+        crate::write_header(&mut buf, 40 + ext, &frag).unwrap();
+        let offset = 48 + ext;
+        buf[offset..offset + end - start].copy_from_slice(&payload()[start..end]);
+        (buf, offset + end - start)
+    }
+
+    fn check_datagram6(packet: &[u8], hbh: bool) {
+        let ext = if hbh { 8 } else { 0 };
+        assert_eq!(40 + ext + PAYLOAD_LEN, packet.len());
+        let ip: crate::ipv6::Header = crate::read_header(packet, 0).unwrap();
+        assert_eq!((ext + PAYLOAD_LEN) as u16, ip.payload_len.get());
+        match hbh {
+            true => {
+                assert_eq!(crate::ip::Proto::HOPOPTS, ip.proto);
+                assert_eq!(17, packet[40]);
+            }
+            false => assert_eq!(crate::ip::Proto::UDP, ip.proto),
+        }
+        assert_eq!(payload(), packet[40 + ext..]);
+    }
+
+    type Table6 = Reassembler<Ipv6Key, 2, 256>;
+
+    #[test]
+    fn ipv6() {
+        for hbh in [false, true] {
+            let mut t = Table6::new(60);
+            let (a, la) = fragment6(7, 0, 56, true, hbh);
+            let (b, lb) = fragment6(7, 56, PAYLOAD_LEN, false, hbh);
+            assert_eq!(Ok(None), t.insert_ipv6(0, &b[..lb]));
+            check_datagram6(t.insert_ipv6(1, &a[..la]).unwrap().unwrap(), hbh);
+        }
+    }
+
+    #[test]
+    fn ipv6_not_fragment() {
+        let mut t = Table6::new(60);
+        let (mut a, la) = fragment6(7, 0, 56, true, true);
+        a[40] = 17;
+        assert_eq!(Err(Error::NotFragment), t.insert_ipv6(0, &a[..la]));
+        assert_eq!(Err(Error::Malformed), t.insert_ipv6(0, &a[..la - 1]));
+    }
+
+    #[test]
+    fn ipv6_atomic() {
+        let mut t = Table6::new(60);
+        let (a, la) = fragment6(7, 0, 56, true, false);
+        let (atomic, l) = fragment6(7, 0, PAYLOAD_LEN, false, false);
+        assert_eq!(Ok(None), t.insert_ipv6(0, &a[..la]));
+        // An atomic fragment with the same identification doesn't disturb reassembly.
+        assert_eq!(Err(Error::AtomicFragment), t.insert_ipv6(0, &atomic[..l]));
+        let (b, lb) = fragment6(7, 56, PAYLOAD_LEN, false, false);
+        check_datagram6(t.insert_ipv6(0, &b[..lb]).unwrap().unwrap(), false);
+    }
+
+    #[test]
+    fn ipv6_overlap() {
+        let mut t = Table6::new(60);
+        let (a, la) = fragment6(7, 0, 56, true, false);
+        let (b, lb) = fragment6(7, 48, PAYLOAD_LEN, false, false);
+        assert_eq!(Ok(None), t.insert_ipv6(0, &a[..la]));
+        assert_eq!(Err(Error::Overlap), t.insert_ipv6(0, &b[..lb]));
+        assert_eq!(Err(Error::Discarded), t.insert_ipv6(0, &a[..la]));
+    }
+
+    #[test]
+    fn ipv6_too_long() {
+        // Builds a fragment with `len` bytes of payload at `offset`.
+        fn at(offset: usize, len: usize, more: bool, hbh: bool) -> ([u8; 192], usize) {
+            let (mut buf, l) = fragment6(7, 0, len, more, hbh);
+            let at = if hbh { 48 } else { 40 };
+            let mut frag: crate::ipv6::FragmentHeader = crate::read_header(&buf, at).unwrap();
+            frag.set_offset(offset as u16);
+            crate::write_header(&mut buf, at, &frag).unwrap();
+            (buf, l)
+        }
+
+        let mut t = Reassembler::<Ipv6Key, 1, { HEADROOM + 0x20000 }>::new(60);
+        let (a, la) = at(8191 * 8, 16, false, false);
+        assert_eq!(Err(Error::TooLarge), t.insert_ipv6(0, &a[..la]));
+
+        // A payload length of exactly 65535 is accepted.
+        let mut t = Reassembler::<Ipv6Key, 1, { HEADROOM + 0x20000 }>::new(60);
+        let (a, la) = at(65520, 15, false, false);
+        let (b, lb) = at(0, 8, true, false);
+        assert_eq!(Ok(None), t.insert_ipv6(0, &a[..la]));
+        assert_eq!(Ok(None), t.insert_ipv6(0, &b[..lb]));
+
+        // Too long once the Hop-by-Hop Options header of a first fragment arriving last is
+        // counted.
+        let mut t = Reassembler::<Ipv6Key, 1, { HEADROOM + 0x20000 }>::new(60);
+        let (b, lb) = at(0, 8, true, true);
+        assert_eq!(Ok(None), t.insert_ipv6(0, &a[..la]));
+        assert_eq!(Err(Error::TooLarge), t.insert_ipv6(0, &b[..lb]));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn growable() {
+        let mut t = GrowableReassembler::<Ipv4Key>::new(1, PAYLOAD_LEN, 30);
+        let (a, la) = fragment(1, 0, 48, true);
+        let (b, lb) = fragment(1, 48, PAYLOAD_LEN, false);
+        let (c, lc) = fragment(2, 0, 48, true);
+        assert_eq!(Ok(None), t.insert_ipv4(0, &c[..lc]));
+        assert_eq!(Ok(None), t.insert_ipv4(1, &a[..la]));
+        assert_eq!(1, t.pending());
+        check_datagram(t.insert_ipv4(2, &b[..lb]).unwrap().unwrap());
+
+        let mut t = GrowableReassembler::<Ipv4Key>::new(1, 64, 30);
+        assert_eq!(Err(Error::TooLarge), t.insert_ipv4(0, &b[..lb]));
+    }
+}
@@ -0,0 +1,540 @@
+//! Table-driven DSCP remarking.
+//!
+//! A [`Policy`] is a slice of [`Rule`]s, each matching on the VXLAN VNI of the tenant, address
+//! prefixes, the IP protocol and port ranges, and giving the DSCP that matching packets are
+//! remarked with. The first rule that matches wins. For VXLAN traffic the rules match the inner
+//! packet, which is the one remarked, and the outer header can be made to carry the same DSCP.
+
+use core::net::IpAddr;
+
+use super::ip::{Dscp, IpHeader, Proto};
+use super::Ptr;
+
+/// An IPv4 or IPv6 address prefix.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Prefix {
+    addr: IpAddr,
+    len: u8,
+}
+
+impl Prefix {
+    /// Returns the prefix of `addr` with the given length, or `Err` if it is longer than the
+    /// address.
+    pub const fn new(addr: IpAddr, len: u8) -> Result<Self, ()> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        match len <= max {
+            true => Ok(Self { addr, len }),
+            false => Err(()),
+        }
+    }
+
+    /// Returns true if `addr` is in the prefix. An address of the other family never is.
+    #[inline(always)]
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(prefix), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.len as u32).unwrap_or(0);
+                (u32::from(prefix) ^ u32::from(addr)) & mask == 0
+            }
+            (IpAddr::V6(prefix), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.len as u32).unwrap_or(0);
+                (u128::from(prefix) ^ u128::from(addr)) & mask == 0
+            }
+            _ => false,
+        }
+    }
+}
+
+/// An inclusive range of ports.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ports {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Ports {
+    pub const fn new(start: u16, end: u16) -> Self {
+        Self { start, end }
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+/// A row of a remarking table. Each field that is `None` matches every packet; a port range
+/// matches only TCP and UDP packets that aren't non-first fragments.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub vni: Option<u32>,
+    pub source: Option<Prefix>,
+    pub destination: Option<Prefix>,
+    pub proto: Option<Proto>,
+    pub source_ports: Option<Ports>,
+    pub destination_ports: Option<Ports>,
+    /// The DSCP matching packets are remarked with.
+    pub dscp: Dscp,
+}
+
+impl Rule {
+    /// Returns a rule that remarks every packet with `dscp`, to be narrowed down with struct
+    /// update syntax.
+    pub const fn new(dscp: Dscp) -> Self {
+        Self {
+            vni: None,
+            source: None,
+            destination: None,
+            proto: None,
+            source_ports: None,
+            destination_ports: None,
+            dscp,
+        }
+    }
+
+    /// Returns true if the rule matches `flow`.
+    #[inline(always)]
+    pub fn matches(&self, flow: &Flow) -> bool {
+        let ports = |ports: Option<Ports>, port: Option<u16>| match (ports, port) {
+            (None, _) => true,
+            (Some(ports), Some(port)) => ports.contains(port),
+            (Some(_), None) => false,
+        };
+
+        self.vni.is_none_or(|vni| flow.vni == Some(vni))
+            && self.source.is_none_or(|p| p.contains(flow.source))
+            && self
+                .destination
+                .is_none_or(|p| p.contains(flow.destination))
+            && self.proto.is_none_or(|proto| proto == flow.proto)
+            && ports(self.source_ports, flow.ports.map(|(s, _)| s))
+            && ports(self.destination_ports, flow.ports.map(|(_, d)| d))
+    }
+}
+
+/// The fields of a packet that rules match on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// The VNI of a VXLAN packet, whose other fields are those of the inner packet.
+    pub vni: Option<u32>,
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    pub proto: Proto,
+    /// The source and destination ports of a TCP or UDP packet.
+    pub ports: Option<(u16, u16)>,
+}
+
+impl Flow {
+    /// Returns the flow of the packet with the IP header `ip`, reading the ports from the TCP or
+    /// UDP header that directly follows it.
+    #[inline(always)]
+    pub fn new(
+        ip: &IpHeader,
+        vni: Option<u32>,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Self {
+        let (source, destination, proto, transport) = match ip {
+            IpHeader::V4(ip) => {
+                // An IHL below 5 leaves no room for a transport header to follow.
+                let transport = match ip.fragment_offset() {
+                    0 if ip.header_len() >= super::ipv4::Header::LEN => Some(ip.header_len()),
+                    _ => None,
+                };
+                (
+                    ip.source().into(),
+                    ip.destination().into(),
+                    ip.proto,
+                    transport,
+                )
+            }
+            IpHeader::V6(ip) => (
+                ip.source().into(),
+                ip.destination().into(),
+                ip.proto,
+                Some(super::ipv6::Header::LEN),
+            ),
+        };
+        let ports = match (proto, transport) {
+            (Proto::TCP | Proto::UDP, Some(offset)) => ports(ip, offset, frame),
+            _ => None,
+        };
+        Self {
+            vni,
+            source,
+            destination,
+            proto,
+            ports,
+        }
+    }
+}
+
+/// Reads the two ports at the start of the transport header `offset` bytes after `ip`.
+#[inline(always)]
+fn ports(
+    ip: &IpHeader,
+    offset: usize,
+    frame: core::ops::Range<*mut core::ffi::c_void>,
+) -> Option<(u16, u16)> {
+    let start = match ip {
+        IpHeader::V4(ip) => &**ip as *const super::ipv4::Header as usize,
+        IpHeader::V6(ip) => &**ip as *const super::ipv6::Header as usize,
+    } + offset;
+    if start + 4 > frame.end as usize {
+        return None;
+    }
+    let bytes = unsafe { core::ptr::read_unaligned(start as *const [u8; 4]) };
+    Some((
+        u16::from_be_bytes([bytes[0], bytes[1]]),
+        u16::from_be_bytes([bytes[2], bytes[3]]),
+    ))
+}
+
+/// A remarking table.
+#[derive(Debug, Copy, Clone)]
+pub struct Policy<'a> {
+    pub rules: &'a [Rule],
+    /// Whether the outer header of a VXLAN packet is given the DSCP of the inner packet once it
+    /// has been remarked, so that the underlay treats the packet as its tenant asked. The outer
+    /// DSCP is copied even if no rule matched.
+    pub copy_outer: bool,
+}
+
+impl<'a> Policy<'a> {
+    pub const fn new(rules: &'a [Rule]) -> Self {
+        Self {
+            rules,
+            copy_outer: false,
+        }
+    }
+
+    /// Returns the DSCP of the first rule that matches `flow`.
+    #[inline(always)]
+    pub fn lookup(&self, flow: &Flow) -> Option<Dscp> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(flow))
+            .map(|rule| rule.dscp)
+    }
+
+    /// Remarks the Ethernet frame in `frame`, or the packet inside it if it is VXLAN over UDP,
+    /// patching IPv4 header checksums. Returns the DSCP of the rule that matched, or `Err` if the
+    /// frame isn't IP or its headers are truncated.
+    #[inline(always)]
+    pub fn apply(
+        &self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<Option<Dscp>, ()> {
+        let mut eth = super::eth::Header::from_frame(frame.clone())?;
+        let mut outer = IpHeader::after_eth(&mut eth, frame.clone())?;
+        let Some((mut vxlan, vni)) = vxlan(&mut outer, frame.clone()) else {
+            return Ok(self.remark(&mut outer, None, frame));
+        };
+
+        let mut inner_eth = vxlan.ethernet(frame.clone())?;
+        let mut inner = IpHeader::after_eth(&mut inner_eth, frame.clone())?;
+        let dscp = self.remark(&mut inner, vni, frame);
+        if self.copy_outer && outer.dscp() != inner.dscp() {
+            outer.set_dscp(inner.dscp());
+        }
+        Ok(dscp)
+    }
+
+    #[inline(always)]
+    fn remark(
+        &self,
+        ip: &mut IpHeader,
+        vni: Option<u32>,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Option<Dscp> {
+        let dscp = self.lookup(&Flow::new(ip, vni, frame))?;
+        if ip.dscp() != dscp {
+            ip.set_dscp(dscp);
+        }
+        Some(dscp)
+    }
+}
+
+/// Returns the VXLAN header that follows `ip` and its VNI if `ip` carries UDP to the VXLAN port.
+#[inline(always)]
+fn vxlan(
+    ip: &mut IpHeader,
+    frame: core::ops::Range<*mut core::ffi::c_void>,
+) -> Option<(Ptr<super::vxlan::Header>, Option<u32>)> {
+    use super::NextHeader;
+
+    let mut udp = match ip {
+        IpHeader::V4(ip) if ip.proto == Proto::UDP && !ip.is_fragment() => {
+            ip.next_t::<super::udp::Header>(frame.clone()).ok()?
+        }
+        IpHeader::V6(ip) if ip.proto == Proto::UDP => {
+            ip.next_t::<super::udp::Header>(frame.clone()).ok()?
+        }
+        _ => return None,
+    };
+    if udp.destination.get() != super::vxlan::PORT {
+        return None;
+    }
+    let vxlan = udp.next_t::<super::vxlan::Header>(frame).ok()?;
+    let vni = vxlan.vni().map(|vni| vni.get());
+    Some((vxlan, vni))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::net::{Ipv4Addr, Ipv6Addr};
+
+    fn v4(a: u8, b: u8, c: u8, d: u8) -> IpAddr {
+        Ipv4Addr::new(a, b, c, d).into()
+    }
+
+    #[test]
+    fn prefix() {
+        let p = Prefix::new(v4(10, 1, 0, 0), 16).unwrap();
+        assert!(p.contains(v4(10, 1, 200, 3)));
+        assert!(!p.contains(v4(10, 2, 0, 1)));
+        assert!(!p.contains(Ipv6Addr::LOCALHOST.into()));
+        assert!(Prefix::new(v4(0, 0, 0, 0), 0)
+            .unwrap()
+            .contains(v4(1, 2, 3, 4)));
+        assert!(Prefix::new(v4(1, 2, 3, 4), 32)
+            .unwrap()
+            .contains(v4(1, 2, 3, 4)));
+        assert!(Prefix::new(v4(1, 2, 3, 4), 33).is_err());
+
+        let p = Prefix::new("2001:db8::".parse::<Ipv6Addr>().unwrap().into(), 32).unwrap();
+        assert!(p.contains("2001:db8:1::1".parse::<Ipv6Addr>().unwrap().into()));
+        assert!(!p.contains("2001:db9::1".parse::<Ipv6Addr>().unwrap().into()));
+        assert!(Prefix::new(Ipv6Addr::LOCALHOST.into(), 129).is_err());
+    }
+
+    const fn dscp(val: u8) -> Dscp {
+        match Dscp::new(val) {
+            Ok(dscp) => dscp,
+            Err(()) => panic!("DSCP out of range"),
+        }
+    }
+
+    #[test]
+    fn dscp_range() {
+        assert_eq!(Ok(63), Dscp::new(63).map(|d| d.get()));
+        assert!(Dscp::new(64).is_err());
+        assert!(Dscp::new(0xb8).is_err());
+    }
+
+    const RULES: [Rule; 3] = [
+        // Tenant 7's SIP signalling.
+        Rule {
+            vni: Some(7),
+            proto: Some(Proto::UDP),
+            destination_ports: Some(Ports::new(5060, 5061)),
+            ..Rule::new(dscp(24))
+        },
+        // Everything else of tenant 7.
+        Rule {
+            vni: Some(7),
+            ..Rule::new(dscp(10))
+        },
+        // Traffic to 192.0.2.0/24, tunnelled or not.
+        Rule {
+            destination: match Prefix::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)), 24) {
+                Ok(p) => Some(p),
+                Err(()) => None,
+            },
+            ..Rule::new(dscp(46))
+        },
+    ];
+
+    #[test]
+    fn lookup() {
+        let policy = Policy::new(&RULES);
+        let flow = Flow {
+            vni: Some(7),
+            source: v4(10, 0, 0, 1),
+            destination: v4(10, 0, 0, 2),
+            proto: Proto::UDP,
+            ports: Some((5060, 5060)),
+        };
+        assert_eq!(Some(dscp(24)), policy.lookup(&flow));
+        assert_eq!(
+            Some(dscp(10)),
+            policy.lookup(&Flow {
+                ports: Some((5060, 5062)),
+                ..flow
+            })
+        );
+        assert_eq!(
+            Some(dscp(10)),
+            policy.lookup(&Flow {
+                proto: Proto::TCP,
+                ..flow
+            })
+        );
+        assert_eq!(
+            Some(dscp(10)),
+            policy.lookup(&Flow {
+                ports: None,
+                ..flow
+            })
+        );
+        assert_eq!(None, policy.lookup(&Flow { vni: None, ..flow }));
+        assert_eq!(
+            Some(dscp(46)),
+            policy.lookup(&Flow {
+                vni: Some(8),
+                destination: v4(192, 0, 2, 9),
+                ..flow
+            })
+        );
+    }
+
+    // Ethernet, IPv4, UDP and VXLAN, then an inner Ethernet, IPv4 and UDP header.
+    const INNER_IP: usize = 14 + 20 + 8 + 8 + 14;
+    const LEN: usize = INNER_IP + 20 + 8;
+
+    fn vxlan_frame(vni: u32, dport: u16) -> crate::Aligned<LEN> {
+        let mut buf = crate::Aligned([0u8; LEN]);
+        let eth = crate::eth::Header {
+            dst_addr: [2, 0, 0, 0, 0, 1],
+            src_addr: [2, 0, 0, 0, 0, 2],
+            ether_type: crate::eth::Type::IPV4,
+        };
+        let mut ip = crate::ipv4::Header {
+            proto: Proto::UDP,
+            ttl: 64,
+            ..Default::default()
+        };
+        ip.set_source(Ipv4Addr::new(10, 0, 0, 1));
+        ip.set_destination(Ipv4Addr::new(10, 0, 0, 2));
+        ip.update_checksum();
+        let udp = crate::udp::Header {
+            destination: crate::vxlan::PORT.into(),
+            ..Default::default()
+        };
+        let mut vxlan = crate::vxlan::Header::default();
+        vxlan.set_vni(vni.into());
+
+        crate::write_header(&mut buf.0, 0, &eth).unwrap();
+        crate::write_header(&mut buf.0, 14, &ip).unwrap();
+        crate::write_header(&mut buf.0, 34, &udp).unwrap();
+        crate::write_header(&mut buf.0, 42, &vxlan).unwrap();
+        crate::write_header(&mut buf.0, 50, &eth).unwrap();
+        ip.set_dscp(Dscp::EF);
+        ip.update_checksum();
+        crate::write_header(&mut buf.0, INNER_IP, &ip).unwrap();
+        let udp = crate::udp::Header {
+            source: 5060.into(),
+            destination: dport.into(),
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, INNER_IP + 20, &udp).unwrap();
+        buf
+    }
+
+    fn ipv4(buf: &crate::Aligned<LEN>, offset: usize) -> crate::ipv4::Header {
+        let ip: crate::ipv4::Header = crate::read_header(&buf.0, offset).unwrap();
+        assert_eq!(ip.compute_checksum(), ip.check);
+        ip
+    }
+
+    #[test]
+    fn vxlan() {
+        let mut buf = vxlan_frame(7, 5061);
+        let r = buf.range(LEN);
+        assert_eq!(Ok(Some(dscp(24))), Policy::new(&RULES).apply(r.clone()));
+        assert_eq!(24, ipv4(&buf, INNER_IP).dscp().get());
+        assert_eq!(0, ipv4(&buf, 14).dscp().get());
+
+        let policy = Policy {
+            copy_outer: true,
+            ..Policy::new(&RULES)
+        };
+        let mut buf = vxlan_frame(7, 80);
+        let r = buf.range(LEN);
+        assert_eq!(Ok(Some(dscp(10))), policy.apply(r.clone()));
+        assert_eq!(10, ipv4(&buf, INNER_IP).dscp().get());
+        assert_eq!(10, ipv4(&buf, 14).dscp().get());
+
+        // No rule matches, but the outer header still follows the inner one.
+        let mut buf = vxlan_frame(8, 80);
+        let r = buf.range(LEN);
+        assert_eq!(Ok(None), policy.apply(r.clone()));
+        assert_eq!(46, ipv4(&buf, INNER_IP).dscp().get());
+        assert_eq!(46, ipv4(&buf, 14).dscp().get());
+
+        assert!(policy.apply(buf.range(INNER_IP + 10)).is_err());
+    }
+
+    #[test]
+    fn short_ihl() {
+        let mut buf = crate::Aligned([0u8; 14 + 20 + 8]);
+        let eth = crate::eth::Header {
+            dst_addr: [2, 0, 0, 0, 0, 1],
+            src_addr: [2, 0, 0, 0, 0, 2],
+            ether_type: crate::eth::Type::IPV4,
+        };
+        let mut ip = crate::ipv4::Header {
+            proto: Proto::UDP,
+            ttl: 64,
+            ..Default::default()
+        };
+        ip.set_ihl(4);
+        // Where the ports would be read from with an IHL of 4.
+        ip.set_destination(Ipv4Addr::new(0x13, 0xc4, 0x13, 0xc4));
+        crate::write_header(&mut buf.0, 0, &eth).unwrap();
+        crate::write_header(&mut buf.0, 14, &ip).unwrap();
+
+        let r = buf.range(42);
+        let mut eth = crate::eth::Header::from_frame(r.clone()).unwrap();
+        let ip = IpHeader::after_eth(&mut eth, r.clone()).unwrap();
+        assert_eq!(None, Flow::new(&ip, None, r.clone()).ports);
+        let rules = [Rule {
+            destination_ports: Some(Ports::new(5060, 5060)),
+            ..Rule::new(dscp(24))
+        }];
+        assert_eq!(Ok(None), Policy::new(&rules).apply(r));
+    }
+
+    #[test]
+    fn plain_ipv6() {
+        let mut buf = crate::Aligned([0u8; 14 + 40 + 20]);
+        let eth = crate::eth::Header {
+            dst_addr: [2, 0, 0, 0, 0, 1],
+            src_addr: [2, 0, 0, 0, 0, 2],
+            ether_type: crate::eth::Type::IPV6,
+        };
+        let mut ip = crate::ipv6::Header {
+            proto: Proto::TCP,
+            ..Default::default()
+        };
+        ip.set_destination("2001:db8::1".parse().unwrap());
+        ip.set_ecn(crate::ip::Ecn::ECT_0);
+        crate::write_header(&mut buf.0, 0, &eth).unwrap();
+        crate::write_header(&mut buf.0, 14, &ip).unwrap();
+        let tcp = crate::tcp::Header {
+            dest: 443.into(),
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 54, &tcp).unwrap();
+
+        let rules = [Rule {
+            destination: Prefix::new("2001:db8::".parse::<Ipv6Addr>().unwrap().into(), 64).ok(),
+            destination_ports: Some(Ports::new(443, 443)),
+            ..Rule::new(dscp(18))
+        }];
+        let r = buf.range(74);
+        assert_eq!(Ok(Some(dscp(18))), Policy::new(&rules).apply(r));
+        let ip: crate::ipv6::Header = crate::read_header(&buf.0, 14).unwrap();
+        assert_eq!(18, ip.dscp().get());
+        assert_eq!(crate::ip::Ecn::ECT_0, ip.ecn());
+
+        let mut arp = crate::Aligned([0u8; 42]);
+        let mut eth = eth;
+        eth.ether_type = crate::eth::Type::ARP;
+        crate::write_header(&mut arp.0, 0, &eth).unwrap();
+        assert!(Policy::new(&rules).apply(arp.range(42)).is_err());
+    }
+}
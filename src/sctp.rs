@@ -0,0 +1,530 @@
+//! SCTP common header and chunks (RFC 9260).
+//!
+//! An SCTP packet is the [`Header`] followed by chunks, each a [`ChunkHeader`] and a value padded
+//! to a multiple of 4 bytes. [`chunks`] walks them in a packet copied to a byte buffer, and the
+//! typed views on [`Chunk`] read the fixed part of the chunk types that carry one.
+
+use core::mem;
+
+use crate::{be16, be32};
+
+/// SCTP common header, which is present after the IP header.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Header {
+    pub source: be16,
+    pub destination: be16,
+    /// Verification tag.
+    pub vtag: be32,
+    /// CRC32c of the packet, least significant byte first.
+    checksum: [u8; 4],
+}
+
+const _: () = assert!(mem::size_of::<Header>() == 12 && mem::align_of::<Header>() == 2);
+
+impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Sctp;
+}
+
+impl Header {
+    pub const LEN: usize = mem::size_of::<Header>();
+
+    #[inline(always)]
+    pub fn new(source: u16, destination: u16, vtag: u32) -> Self {
+        Self {
+            source: source.into(),
+            destination: destination.into(),
+            vtag: vtag.into(),
+            checksum: [0; 4],
+        }
+    }
+
+    #[inline(always)]
+    pub fn checksum(&self) -> u32 {
+        u32::from_le_bytes(self.checksum)
+    }
+
+    #[inline(always)]
+    pub fn set_checksum(&mut self, crc: u32) {
+        self.checksum = crc.to_le_bytes();
+    }
+}
+
+/// Computes the checksum of the SCTP packet in `packet`, common header and chunks, taking the
+/// checksum field as zero. Returns `Err` if `packet` is shorter than the common header.
+#[inline(always)]
+pub fn checksum(packet: &[u8]) -> Result<u32, ()> {
+    if packet.len() < Header::LEN {
+        return Err(());
+    }
+    let crc = super::checksum::crc32c(&packet[..8], 0);
+    let crc = super::checksum::crc32c(&[0; 4], crc);
+    Ok(super::checksum::crc32c(&packet[Header::LEN..], crc))
+}
+
+/// Returns true if the checksum field of the SCTP packet in `packet` is correct.
+#[inline(always)]
+pub fn verify(packet: &[u8]) -> bool {
+    match (checksum(packet), super::read_header::<Header>(packet, 0)) {
+        (Ok(crc), Ok(h)) => crc == h.checksum(),
+        _ => false,
+    }
+}
+
+/// Sets the checksum field of the SCTP packet in `packet`.
+#[inline(always)]
+pub fn update_checksum(packet: &mut [u8]) -> Result<(), ()> {
+    let crc = checksum(packet)?;
+    packet[8..Header::LEN].copy_from_slice(&crc.to_le_bytes());
+    Ok(())
+}
+
+#[repr(transparent)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct ChunkType {
+    val: u8,
+}
+
+impl ChunkType {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    pub const DATA: ChunkType = ChunkType::new(0);
+    pub const INIT: ChunkType = ChunkType::new(1);
+    pub const INIT_ACK: ChunkType = ChunkType::new(2);
+    pub const SACK: ChunkType = ChunkType::new(3);
+    pub const HEARTBEAT: ChunkType = ChunkType::new(4);
+    pub const HEARTBEAT_ACK: ChunkType = ChunkType::new(5);
+    pub const ABORT: ChunkType = ChunkType::new(6);
+    pub const SHUTDOWN: ChunkType = ChunkType::new(7);
+    pub const SHUTDOWN_ACK: ChunkType = ChunkType::new(8);
+    pub const ERROR: ChunkType = ChunkType::new(9);
+    pub const COOKIE_ECHO: ChunkType = ChunkType::new(10);
+    pub const COOKIE_ACK: ChunkType = ChunkType::new(11);
+    pub const SHUTDOWN_COMPLETE: ChunkType = ChunkType::new(14);
+}
+
+impl core::fmt::Display for ChunkType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            ChunkType::DATA => f.write_str("DATA"),
+            ChunkType::INIT => f.write_str("INIT"),
+            ChunkType::INIT_ACK => f.write_str("INIT ACK"),
+            ChunkType::SACK => f.write_str("SACK"),
+            ChunkType::HEARTBEAT => f.write_str("HEARTBEAT"),
+            ChunkType::HEARTBEAT_ACK => f.write_str("HEARTBEAT ACK"),
+            ChunkType::ABORT => f.write_str("ABORT"),
+            ChunkType::SHUTDOWN => f.write_str("SHUTDOWN"),
+            ChunkType::SHUTDOWN_ACK => f.write_str("SHUTDOWN ACK"),
+            ChunkType::ERROR => f.write_str("ERROR"),
+            ChunkType::COOKIE_ECHO => f.write_str("COOKIE ECHO"),
+            ChunkType::COOKIE_ACK => f.write_str("COOKIE ACK"),
+            ChunkType::SHUTDOWN_COMPLETE => f.write_str("SHUTDOWN COMPLETE"),
+            _ => write!(f, "chunk type {}", self.val),
+        }
+    }
+}
+
+/// The header every chunk starts with.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct ChunkHeader {
+    pub chunk_type: ChunkType,
+    pub flags: u8,
+    /// Length of the chunk including this header, but not the padding after it.
+    pub length: be16,
+}
+
+const _: () = assert!(mem::size_of::<ChunkHeader>() == 4 && mem::align_of::<ChunkHeader>() == 2);
+
+impl ChunkHeader {
+    pub const LEN: usize = mem::size_of::<ChunkHeader>();
+}
+
+/// The fixed part of a DATA chunk, after the chunk header.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct DataChunk {
+    pub tsn: be32,
+    pub stream_id: be16,
+    pub stream_seq: be16,
+    /// Payload protocol identifier.
+    pub ppid: be32,
+}
+
+const _: () = assert!(mem::size_of::<DataChunk>() == 12 && mem::align_of::<DataChunk>() == 2);
+
+impl DataChunk {
+    pub const LEN: usize = mem::size_of::<DataChunk>();
+
+    /// The last fragment of a user message, in the chunk flags.
+    pub const END: u8 = 0x01;
+    /// The first fragment of a user message, in the chunk flags.
+    pub const BEGINNING: u8 = 0x02;
+    /// An unordered user message, in the chunk flags.
+    pub const UNORDERED: u8 = 0x04;
+    /// The receiver should SACK immediately (RFC 7053), in the chunk flags.
+    pub const IMMEDIATE: u8 = 0x08;
+}
+
+/// The fixed part of an INIT or INIT ACK chunk, after the chunk header.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct InitChunk {
+    pub initiate_tag: be32,
+    /// Advertised receiver window credit.
+    pub a_rwnd: be32,
+    pub outbound_streams: be16,
+    pub inbound_streams: be16,
+    pub initial_tsn: be32,
+}
+
+const _: () = assert!(mem::size_of::<InitChunk>() == 16 && mem::align_of::<InitChunk>() == 2);
+
+impl InitChunk {
+    pub const LEN: usize = mem::size_of::<InitChunk>();
+}
+
+/// The fixed part of a SACK chunk, after the chunk header.
+#[repr(C, packed(2))]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct SackChunk {
+    pub cum_tsn_ack: be32,
+    /// Advertised receiver window credit.
+    pub a_rwnd: be32,
+    pub num_gap_blocks: be16,
+    pub num_dup_tsns: be16,
+}
+
+const _: () = assert!(mem::size_of::<SackChunk>() == 12 && mem::align_of::<SackChunk>() == 2);
+
+impl SackChunk {
+    pub const LEN: usize = mem::size_of::<SackChunk>();
+}
+
+/// A SACK chunk with its gap ack blocks and duplicate TSNs.
+#[derive(Debug, Copy, Clone)]
+pub struct Sack<'a> {
+    pub header: SackChunk,
+    gap_blocks: &'a [u8],
+    dup_tsns: &'a [u8],
+}
+
+impl<'a> Sack<'a> {
+    /// Returns the gap ack blocks as start and end offsets from the cumulative TSN ack.
+    #[inline(always)]
+    pub fn gap_blocks(&self) -> impl Iterator<Item = (u16, u16)> + 'a {
+        self.gap_blocks.chunks_exact(4).map(|b| {
+            (
+                u16::from_be_bytes([b[0], b[1]]),
+                u16::from_be_bytes([b[2], b[3]]),
+            )
+        })
+    }
+
+    #[inline(always)]
+    pub fn dup_tsns(&self) -> impl Iterator<Item = u32> + 'a {
+        self.dup_tsns
+            .chunks_exact(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// A chunk and its value, without the padding.
+#[derive(Debug, Copy, Clone)]
+pub struct Chunk<'a> {
+    pub header: ChunkHeader,
+    pub value: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// The sender had no TCB, in the flags of an ABORT or SHUTDOWN COMPLETE chunk.
+    pub const NO_TCB: u8 = 0x01;
+
+    /// Returns the fixed part of a DATA chunk and the user data after it.
+    #[inline(always)]
+    pub fn data(&self) -> Option<(DataChunk, &'a [u8])> {
+        self.fixed(ChunkType::DATA)
+    }
+
+    /// Returns the fixed part of an INIT or INIT ACK chunk and the parameters after it.
+    #[inline(always)]
+    pub fn init(&self) -> Option<(InitChunk, &'a [u8])> {
+        self.fixed(ChunkType::INIT)
+            .or_else(|| self.fixed(ChunkType::INIT_ACK))
+    }
+
+    /// Returns a SACK chunk, or `None` if its value is too short for the blocks it counts.
+    #[inline(always)]
+    pub fn sack(&self) -> Option<Sack<'a>> {
+        let (header, rest) = self.fixed::<SackChunk>(ChunkType::SACK)?;
+        let gaps = header.num_gap_blocks.get() as usize * 4;
+        let dups = header.num_dup_tsns.get() as usize * 4;
+        Some(Sack {
+            header,
+            gap_blocks: rest.get(..gaps)?,
+            dup_tsns: rest.get(gaps..gaps + dups)?,
+        })
+    }
+
+    /// Returns the error causes of an ABORT chunk.
+    #[inline(always)]
+    pub fn abort(&self) -> Option<&'a [u8]> {
+        match self.header.chunk_type {
+            ChunkType::ABORT => Some(self.value),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    fn fixed<T: super::Plain>(&self, chunk_type: ChunkType) -> Option<(T, &'a [u8])> {
+        if self.header.chunk_type != chunk_type {
+            return None;
+        }
+        let fixed = super::read_header::<T>(self.value, 0).ok()?;
+        Some((fixed, &self.value[mem::size_of::<T>()..]))
+    }
+}
+
+/// Most chunks [`Chunks`] yields from one packet.
+pub const MAX_CHUNKS: usize = 64;
+
+/// Iterator over the chunks of an SCTP packet. It yields `Err` once and stops at a chunk whose
+/// length is shorter than its header or runs past the packet, and stops after [`MAX_CHUNKS`].
+#[derive(Debug, Clone)]
+pub struct Chunks<'a> {
+    rest: &'a [u8],
+    left: usize,
+}
+
+/// Returns an iterator over the chunks of the SCTP packet in `packet`, after its common header.
+#[inline(always)]
+pub fn chunks(packet: &[u8]) -> Chunks<'_> {
+    Chunks {
+        rest: packet.get(Header::LEN..).unwrap_or_default(),
+        left: MAX_CHUNKS,
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, ()>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() || self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        let header = match super::read_header::<ChunkHeader>(self.rest, 0) {
+            Ok(h) if (ChunkHeader::LEN..=self.rest.len()).contains(&(h.length.get() as usize)) => h,
+            _ => {
+                self.left = 0;
+                return Some(Err(()));
+            }
+        };
+        let len = header.length.get() as usize;
+        let value = &self.rest[ChunkHeader::LEN..len];
+        // The padding of the last chunk may be left out.
+        self.rest = self.rest.get(len.next_multiple_of(4)..).unwrap_or_default();
+        Some(Ok(Chunk { header, value }))
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (source, destination, vtag) = (self.source, self.destination, self.vtag);
+        write!(
+            f,
+            "{source} > {destination}: SCTP, vtag 0x{:08x}",
+            vtag.get()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An INIT from port 5060 to 5060, then a DATA chunk with 3 bytes of user data, padded.
+    const PACKET: [u8; 12 + 20 + 20] = [
+        0x13, 0xc4, 0x13, 0xc4, 0, 0, 0, 0, 0, 0, 0, 0, // common header
+        1, 0, 0, 20, // INIT
+        0x12, 0x34, 0x56, 0x78, 0, 1, 0, 0, 0, 10, 0, 10, 0, 0, 0, 1, // initiate tag etc.
+        0, 3, 0, 19, // DATA, B and E
+        0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 46, // TSN 1, stream 0, seq 0, PPID 46
+        b'a', b'b', b'c', 0,
+    ];
+
+    #[test]
+    fn checksum_round_trip() {
+        let mut packet = PACKET;
+        assert!(!verify(&packet));
+        update_checksum(&mut packet).unwrap();
+        assert!(verify(&packet));
+        let h: Header = crate::read_header(&packet, 0).unwrap();
+        assert_eq!(checksum(&packet), Ok(h.checksum()));
+
+        // Any bit flipped is caught.
+        packet[40] ^= 0x10;
+        assert!(!verify(&packet));
+        assert!(checksum(&packet[..11]).is_err());
+        assert!(!verify(&packet[..11]));
+    }
+
+    #[test]
+    fn byte_order() {
+        // 32 zero bytes, whose CRC32c goes on the wire as aa 36 91 8a (RFC 3720, appendix B.4).
+        // SCTP sends the CRC32c in the same byte order as iSCSI (RFC 3309).
+        let mut packet = [0u8; 32];
+        update_checksum(&mut packet).unwrap();
+        assert_eq!([0xaa, 0x36, 0x91, 0x8a], packet[8..12]);
+        assert!(verify(&packet));
+    }
+
+    #[test]
+    fn walk() {
+        let found: std::vec::Vec<_> = chunks(&PACKET).map(Result::unwrap).collect();
+        assert_eq!(2, found.len());
+
+        let (init, params) = found[0].init().unwrap();
+        assert_eq!(0x12345678, init.initiate_tag.get());
+        assert_eq!(10, init.outbound_streams.get());
+        assert_eq!(1, init.initial_tsn.get());
+        assert!(params.is_empty());
+        assert!(found[0].data().is_none());
+
+        let (data, user) = found[1].data().unwrap();
+        assert_eq!(DataChunk::BEGINNING | DataChunk::END, found[1].header.flags);
+        assert_eq!(1, data.tsn.get());
+        assert_eq!(46, data.ppid.get());
+        assert_eq!(b"abc", user);
+
+        // The last chunk's padding may be left out.
+        assert_eq!(2, chunks(&PACKET[..51]).filter(Result::is_ok).count());
+    }
+
+    #[test]
+    fn malformed() {
+        let mut packet = PACKET;
+        packet[15] = 3;
+        let mut it = chunks(&packet);
+        assert_eq!(Some(Err(())), it.next().map(|c| c.map(|_| ())));
+        assert!(it.next().is_none());
+
+        let mut it = chunks(&PACKET[..40]);
+        assert!(it.next().unwrap().is_ok());
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+
+        assert_eq!(0, chunks(&PACKET[..12]).count());
+        assert_eq!(0, chunks(&[]).count());
+
+        // Empty chunks claiming 4 bytes each stop at MAX_CHUNKS.
+        let mut many = [0u8; 12 + 4 * (MAX_CHUNKS + 10)];
+        for chunk in many[12..].chunks_exact_mut(4) {
+            chunk.copy_from_slice(&[ChunkType::COOKIE_ACK.get(), 0, 0, 4]);
+        }
+        assert_eq!(MAX_CHUNKS, chunks(&many).count());
+    }
+
+    #[test]
+    fn dispatch() {
+        use crate::{AutoNextHeader, HeaderPtr};
+
+        let mut buf = crate::Aligned([0u8; 40 + 12]);
+        let ip = crate::ipv4::Header {
+            proto: crate::ip::Proto::SCTP,
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 0, &ip).unwrap();
+        crate::write_header(&mut buf.0, 20, &Header::new(3868, 3868, 7)).unwrap();
+        let r = buf.range(32);
+        let mut ip = crate::Ptr::new(r.start as *mut crate::ipv4::Header);
+        match ip.next(r) {
+            Ok(HeaderPtr::Sctp(sctp)) => assert_eq!(7, sctp.vtag.get()),
+            _ => panic!("expected SCTP"),
+        }
+
+        let ip = crate::ipv6::Header {
+            proto: crate::ip::Proto::SCTP,
+            ..Default::default()
+        };
+        crate::write_header(&mut buf.0, 0, &ip).unwrap();
+        crate::write_header(&mut buf.0, 40, &Header::new(5060, 5060, 9)).unwrap();
+        let r = buf.range(52);
+        let mut ip = crate::Ptr::new(r.start as *mut crate::ipv6::Header);
+        match ip.next(r.clone()) {
+            Ok(HeaderPtr::Sctp(sctp)) => assert_eq!(5060, sctp.destination.get()),
+            _ => panic!("expected SCTP"),
+        }
+        assert!(ip.next(buf.range(51)).is_err());
+    }
+
+    #[test]
+    fn sack_and_abort() {
+        let chunk = [
+            3, 0, 0, 24, // SACK
+            0, 0, 0, 100, 0, 1, 0, 0, 0, 1, 0, 1, // cum TSN 100, one gap, one dup
+            0, 2, 0, 3, // gap 102..=103
+            0, 0, 0, 99, // dup 99
+            6, 1, 0, 8, 0, 1, 0, 4, // ABORT, T bit, one cause
+        ];
+        let mut packet = [0u8; 12 + 32];
+        packet[12..].copy_from_slice(&chunk);
+        let mut it = chunks(&packet);
+
+        let sack = it.next().unwrap().unwrap().sack().unwrap();
+        assert_eq!(100, sack.header.cum_tsn_ack.get());
+        assert_eq!(
+            std::vec![(2, 3)],
+            sack.gap_blocks().collect::<std::vec::Vec<_>>()
+        );
+        assert_eq!(std::vec![99], sack.dup_tsns().collect::<std::vec::Vec<_>>());
+
+        let abort = it.next().unwrap().unwrap();
+        assert_eq!(Chunk::NO_TCB, abort.header.flags & Chunk::NO_TCB);
+        assert_eq!(Some(&[0, 1, 0, 4][..]), abort.abort());
+        assert!(abort.sack().is_none());
+        assert!(it.next().is_none());
+
+        // More gap blocks than the chunk holds.
+        packet[12 + 13] = 5;
+        assert!(chunks(&packet).next().unwrap().unwrap().sack().is_none());
+    }
+}
@@ -0,0 +1,1431 @@
+//! Serde support, enabled by the `serde` feature.
+//!
+//! Headers are serialized through plain structs with host-order fields rather than their wire
+//! layout: addresses are strings, and protocol numbers and flags are given by name where they have
+//! one. Formats that aren't human-readable get the plain numbers and byte arrays instead.
+
+use core::fmt;
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+use ::serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Unexpected, Visitor};
+use ::serde::ser::{SerializeSeq, Serializer};
+use ::serde::{Deserialize, Serialize};
+
+use crate::eth::MacAddr;
+use crate::{be16, be32, be64};
+
+macro_rules! be_serde {
+    ($name:ident, $host:ty) => {
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                self.get().serialize(s)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                <$host>::deserialize(d).map($name::new)
+            }
+        }
+    };
+}
+
+be_serde!(be16, u16);
+be_serde!(be32, u32);
+be_serde!(be64, u64);
+
+type Names = &'static [(&'static str, u32)];
+
+/// Accepts either one of `names` or a number.
+struct NamedVisitor(Names);
+
+impl<'de> Visitor<'de> for NamedVisitor {
+    type Value = u32;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a name or a number")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<u32, E> {
+        u32::try_from(v).map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<u32, E> {
+        u32::try_from(v).map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<u32, E> {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == v)
+            .map(|(_, val)| *val)
+            .ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for NamedVisitor {
+    type Value = u32;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<u32, D::Error> {
+        d.deserialize_any(self)
+    }
+}
+
+fn serialize_named<S: Serializer>(s: S, names: Names, val: u32) -> Result<S::Ok, S::Error> {
+    match names.iter().find(|(_, v)| *v == val) {
+        Some((name, _)) if s.is_human_readable() => s.serialize_str(name),
+        _ => s.serialize_u32(val),
+    }
+}
+
+fn deserialize_named<'de, D: Deserializer<'de>>(d: D, names: Names) -> Result<u32, D::Error> {
+    match d.is_human_readable() {
+        true => d.deserialize_any(NamedVisitor(names)),
+        false => u32::deserialize(d),
+    }
+}
+
+/// Implements serde for a newtype constant such as [`crate::ip::Proto`], by name when it has
+/// one.
+macro_rules! named {
+    ($ty:ty, $host:ty, $names:expr) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                serialize_named(s, $names, self.get() as u32)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                let val = deserialize_named(d, $names)?;
+                <$host>::try_from(val).map(<$ty>::new).map_err(|_| {
+                    de::Error::invalid_value(Unexpected::Unsigned(val as u64), &"a smaller number")
+                })
+            }
+        }
+    };
+}
+
+named!(
+    crate::eth::Type,
+    u16,
+    &[("IPv4", 0x0800), ("ARP", 0x0806), ("IPv6", 0x86dd)]
+);
+named!(crate::arp::HardwareType, u16, &[("ethernet", 1)]);
+named!(crate::arp::Operation, u16, &[("request", 1), ("reply", 2)]);
+named!(
+    crate::ip::Proto,
+    u8,
+    &[
+        ("HOPOPTS", 0),
+        ("ICMP", 1),
+        ("TCP", 6),
+        ("UDP", 17),
+        ("ROUTING", 43),
+        ("FRAGMENT", 44),
+        ("ICMPv6", 58),
+        ("NONE", 59),
+        ("DSTOPTS", 60),
+        ("SCTP", 132),
+    ]
+);
+named!(
+    crate::icmp::Type,
+    u8,
+    &[
+        ("echo_reply", 0),
+        ("destination_unreachable", 3),
+        ("source_quench", 4),
+        ("redirect", 5),
+        ("echo_request", 8),
+        ("time_exceeded", 11),
+        ("parameter_problem", 12),
+    ]
+);
+named!(
+    crate::icmpv6::Type,
+    u8,
+    &[
+        ("destination_unreachable", 1),
+        ("packet_too_big", 2),
+        ("time_exceeded", 3),
+        ("parameter_problem", 4),
+        ("echo_request", 128),
+        ("echo_reply", 129),
+    ]
+);
+named!(
+    crate::gtp::MsgType,
+    u8,
+    &[
+        ("echo_request", 1),
+        ("echo_response", 2),
+        ("error_indication", 26),
+        ("supported_ext_headers", 31),
+        ("end_marker", 254),
+        ("g_pdu", 255),
+    ]
+);
+named!(
+    crate::sctp::ChunkType,
+    u8,
+    &[
+        ("DATA", 0),
+        ("INIT", 1),
+        ("INIT_ACK", 2),
+        ("SACK", 3),
+        ("HEARTBEAT", 4),
+        ("HEARTBEAT_ACK", 5),
+        ("ABORT", 6),
+        ("SHUTDOWN", 7),
+        ("SHUTDOWN_ACK", 8),
+        ("ERROR", 9),
+        ("COOKIE_ECHO", 10),
+        ("COOKIE_ACK", 11),
+        ("SHUTDOWN_COMPLETE", 14),
+    ]
+);
+named!(
+    crate::gtp::ExtType,
+    u8,
+    &[
+        ("none", 0),
+        ("service_class", 0x20),
+        ("udp_port", 0x40),
+        ("ran_container", 0x81),
+        ("long_pdcp_pdu_number", 0x82),
+        ("xw_ran_container", 0x83),
+        ("nr_ran_container", 0x84),
+        ("pdu_session_container", 0x85),
+        ("pdcp_pdu_number", 0xc0),
+    ]
+);
+named!(
+    crate::virtio_net::GsoType,
+    u8,
+    &[
+        ("none", 0),
+        ("tcpv4", 1),
+        ("udp", 3),
+        ("tcpv6", 4),
+        ("udp_l4", 5),
+    ]
+);
+named!(
+    crate::virtio_net::HashType,
+    u16,
+    &[
+        ("none", 0),
+        ("ipv4", 1),
+        ("tcpv4", 2),
+        ("udpv4", 3),
+        ("ipv6", 4),
+        ("tcpv6", 5),
+        ("udpv6", 6),
+        ("ipv6_ex", 7),
+        ("tcpv6_ex", 8),
+        ("udpv6_ex", 9),
+    ]
+);
+
+/// Accepts a sequence of flag names or numbers, or a number.
+struct FlagsVisitor(Names);
+
+impl<'de> Visitor<'de> for FlagsVisitor {
+    type Value = u32;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a list of flags")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<u32, E> {
+        NamedVisitor(self.0).visit_u64(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<u32, A::Error> {
+        let mut bits = 0;
+        while let Some(flag) = seq.next_element_seed(NamedVisitor(self.0))? {
+            bits |= flag;
+        }
+        Ok(bits)
+    }
+}
+
+/// Serializes `bits` as the names of the flags set, followed by any unnamed bits as a number.
+fn serialize_flags<S: Serializer>(s: S, names: Names, bits: u8) -> Result<S::Ok, S::Error> {
+    if !s.is_human_readable() {
+        return s.serialize_u8(bits);
+    }
+    let named = names.iter().fold(0, |acc, (_, bit)| acc | *bit as u8);
+    let rest = bits & !named;
+    let count = (bits & named).count_ones() as usize + (rest != 0) as usize;
+    let mut seq = s.serialize_seq(Some(count))?;
+    for (name, bit) in names {
+        if bits & *bit as u8 != 0 {
+            seq.serialize_element(name)?;
+        }
+    }
+    if rest != 0 {
+        seq.serialize_element(&rest)?;
+    }
+    seq.end()
+}
+
+fn deserialize_flags<'de, D: Deserializer<'de>>(d: D, names: Names) -> Result<u8, D::Error> {
+    let bits = match d.is_human_readable() {
+        true => d.deserialize_any(FlagsVisitor(names))?,
+        false => u8::deserialize(d)? as u32,
+    };
+    u8::try_from(bits).map_err(|_| {
+        de::Error::invalid_value(Unexpected::Unsigned(bits as u64), &"8 bits of flags")
+    })
+}
+
+const TCP_FLAGS: Names = &[
+    ("FIN", 0x01),
+    ("SYN", 0x02),
+    ("RST", 0x04),
+    ("PSH", 0x08),
+    ("ACK", 0x10),
+    ("URG", 0x20),
+    ("ECE", 0x40),
+    ("CWR", 0x80),
+];
+
+impl Serialize for crate::tcp::Flags {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_flags(s, TCP_FLAGS, self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::tcp::Flags {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        deserialize_flags(d, TCP_FLAGS).map(Self::new)
+    }
+}
+
+const VIRTIO_FLAGS: Names = &[("NEEDS_CSUM", 1), ("DATA_VALID", 2), ("RSC_INFO", 4)];
+
+impl Serialize for crate::virtio_net::Flags {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_flags(s, VIRTIO_FLAGS, self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::virtio_net::Flags {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        deserialize_flags(d, VIRTIO_FLAGS).map(Self::new)
+    }
+}
+
+impl Serialize for MacAddr {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match s.is_human_readable() {
+            true => s.collect_str(self),
+            false => self.0.serialize(s),
+        }
+    }
+}
+
+struct MacVisitor;
+
+impl Visitor<'_> for MacVisitor {
+    type Value = MacAddr;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a MAC address such as 02:00:5e:00:53:01")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<MacAddr, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+impl<'de> Deserialize<'de> for MacAddr {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        match d.is_human_readable() {
+            true => d.deserialize_str(MacVisitor),
+            false => <[u8; 6]>::deserialize(d).map(MacAddr),
+        }
+    }
+}
+
+/// Implements serde for `$ty` through the plain struct `$repr`, which converts from and into it.
+macro_rules! via {
+    ($ty:ty, $repr:ty) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                <$repr>::from(*self).serialize(s)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                <$repr>::deserialize(d).map(Self::from)
+            }
+        }
+    };
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "EthernetHeader")]
+struct Eth {
+    destination: MacAddr,
+    source: MacAddr,
+    ether_type: crate::eth::Type,
+}
+
+impl From<crate::eth::Header> for Eth {
+    fn from(h: crate::eth::Header) -> Self {
+        Self {
+            destination: MacAddr(h.dst_addr),
+            source: MacAddr(h.src_addr),
+            ether_type: h.ether_type,
+        }
+    }
+}
+
+impl From<Eth> for crate::eth::Header {
+    fn from(r: Eth) -> Self {
+        Self {
+            dst_addr: r.destination.0,
+            src_addr: r.source.0,
+            ether_type: r.ether_type,
+        }
+    }
+}
+
+via!(crate::eth::Header, Eth);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ArpHeader")]
+struct Arp {
+    hardware_type: crate::arp::HardwareType,
+    protocol_type: crate::eth::Type,
+    operation: crate::arp::Operation,
+    sender_mac: MacAddr,
+    sender_ip: Ipv4Addr,
+    target_mac: MacAddr,
+    target_ip: Ipv4Addr,
+}
+
+impl From<crate::arp::Header> for Arp {
+    fn from(h: crate::arp::Header) -> Self {
+        Self {
+            hardware_type: h.h_type,
+            protocol_type: h.p_type,
+            operation: h.oper,
+            sender_mac: MacAddr(h.sha),
+            sender_ip: h.spa.into(),
+            target_mac: MacAddr(h.tha),
+            target_ip: h.tpa.into(),
+        }
+    }
+}
+
+impl From<Arp> for crate::arp::Header {
+    fn from(r: Arp) -> Self {
+        Self {
+            h_type: r.hardware_type,
+            p_type: r.protocol_type,
+            h_len: 6,
+            p_len: 4,
+            oper: r.operation,
+            sha: r.sender_mac.0,
+            spa: r.sender_ip.octets(),
+            tha: r.target_mac.0,
+            tpa: r.target_ip.octets(),
+        }
+    }
+}
+
+via!(crate::arp::Header, Arp);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ArpVarHeader")]
+struct ArpVar {
+    hardware_type: crate::arp::HardwareType,
+    protocol_type: crate::eth::Type,
+    hardware_len: u8,
+    protocol_len: u8,
+    operation: crate::arp::Operation,
+}
+
+impl From<crate::arp::VarHeader> for ArpVar {
+    fn from(h: crate::arp::VarHeader) -> Self {
+        Self {
+            hardware_type: h.h_type,
+            protocol_type: h.p_type,
+            hardware_len: h.h_len,
+            protocol_len: h.p_len,
+            operation: h.oper,
+        }
+    }
+}
+
+impl From<ArpVar> for crate::arp::VarHeader {
+    fn from(r: ArpVar) -> Self {
+        Self {
+            h_type: r.hardware_type,
+            p_type: r.protocol_type,
+            h_len: r.hardware_len,
+            p_len: r.protocol_len,
+            oper: r.operation,
+        }
+    }
+}
+
+via!(crate::arp::VarHeader, ArpVar);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Ipv4Header")]
+struct Ipv4 {
+    version: u8,
+    ihl: u8,
+    tos: u8,
+    total_length: u16,
+    id: u16,
+    dont_fragment: bool,
+    more_fragments: bool,
+    /// In bytes.
+    fragment_offset: u16,
+    ttl: u8,
+    protocol: crate::ip::Proto,
+    checksum: u16,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+}
+
+impl From<crate::ipv4::Header> for Ipv4 {
+    fn from(h: crate::ipv4::Header) -> Self {
+        Self {
+            version: h.version(),
+            ihl: h.ihl(),
+            tos: h.tos,
+            total_length: h.tot_len.get(),
+            id: h.id.get(),
+            dont_fragment: h.dont_fragment(),
+            more_fragments: h.more_fragments(),
+            fragment_offset: h.fragment_offset(),
+            ttl: h.ttl,
+            protocol: h.proto,
+            checksum: h.check.get(),
+            source: h.source(),
+            destination: h.destination(),
+        }
+    }
+}
+
+impl From<Ipv4> for crate::ipv4::Header {
+    fn from(r: Ipv4) -> Self {
+        let mut h = Self {
+            _bitfields: r.version << 4 | r.ihl & 0xf,
+            tos: r.tos,
+            tot_len: r.total_length.into(),
+            id: r.id.into(),
+            ttl: r.ttl,
+            proto: r.protocol,
+            check: r.checksum.into(),
+            source: r.source.octets(),
+            destination: r.destination.octets(),
+            ..Default::default()
+        };
+        h.set_fragment_offset(r.fragment_offset);
+        h.set_dont_fragment(r.dont_fragment);
+        h.set_more_fragments(r.more_fragments);
+        h
+    }
+}
+
+via!(crate::ipv4::Header, Ipv4);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Ipv6Header")]
+struct Ipv6 {
+    version: u8,
+    traffic_class: u8,
+    flow_label: u32,
+    payload_length: u16,
+    next_header: crate::ip::Proto,
+    hop_limit: u8,
+    source: Ipv6Addr,
+    destination: Ipv6Addr,
+}
+
+impl From<crate::ipv6::Header> for Ipv6 {
+    fn from(h: crate::ipv6::Header) -> Self {
+        Self {
+            version: h.version(),
+            traffic_class: h.priority(),
+            flow_label: h.flow(),
+            payload_length: h.payload_len.get(),
+            next_header: h.proto,
+            hop_limit: h.hop_limit,
+            source: h.source(),
+            destination: h.destination(),
+        }
+    }
+}
+
+impl From<Ipv6> for crate::ipv6::Header {
+    fn from(r: Ipv6) -> Self {
+        let mut h = Self {
+            _bitfields: [r.version << 4, 0, 0, 0],
+            payload_len: r.payload_length.into(),
+            proto: r.next_header,
+            hop_limit: r.hop_limit,
+            source: r.source.octets(),
+            destination: r.destination.octets(),
+        };
+        h.set_priority(r.traffic_class);
+        h.set_flow(r.flow_label);
+        h
+    }
+}
+
+via!(crate::ipv6::Header, Ipv6);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Ipv6FragmentHeader")]
+struct Ipv6Fragment {
+    next_header: crate::ip::Proto,
+    /// In bytes.
+    offset: u16,
+    more_fragments: bool,
+    identification: u32,
+}
+
+impl From<crate::ipv6::FragmentHeader> for Ipv6Fragment {
+    fn from(h: crate::ipv6::FragmentHeader) -> Self {
+        Self {
+            next_header: h.next_header,
+            offset: h.offset(),
+            more_fragments: h.more_fragments(),
+            identification: h.identification.get(),
+        }
+    }
+}
+
+impl From<Ipv6Fragment> for crate::ipv6::FragmentHeader {
+    fn from(r: Ipv6Fragment) -> Self {
+        let mut h = Self {
+            next_header: r.next_header,
+            _reserved: 0,
+            frag_off: be16::ZERO,
+            identification: r.identification.into(),
+        };
+        h.set_offset(r.offset);
+        h.set_more_fragments(r.more_fragments);
+        h
+    }
+}
+
+via!(crate::ipv6::FragmentHeader, Ipv6Fragment);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Ipv6ExtHeader")]
+struct Ipv6Ext {
+    next_header: crate::ip::Proto,
+    hdr_ext_len: u8,
+}
+
+impl From<crate::ipv6::ExtHeader> for Ipv6Ext {
+    fn from(h: crate::ipv6::ExtHeader) -> Self {
+        Self {
+            next_header: h.next_header,
+            hdr_ext_len: h.hdr_ext_len,
+        }
+    }
+}
+
+impl From<Ipv6Ext> for crate::ipv6::ExtHeader {
+    fn from(r: Ipv6Ext) -> Self {
+        Self {
+            next_header: r.next_header,
+            hdr_ext_len: r.hdr_ext_len,
+        }
+    }
+}
+
+via!(crate::ipv6::ExtHeader, Ipv6Ext);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Ipv6JumboHeader")]
+struct Ipv6Jumbo {
+    next_header: crate::ip::Proto,
+    hdr_ext_len: u8,
+    opt_type: u8,
+    opt_len: u8,
+    jumbo_len: u32,
+}
+
+impl From<crate::ipv6::JumboHeader> for Ipv6Jumbo {
+    fn from(h: crate::ipv6::JumboHeader) -> Self {
+        Self {
+            next_header: h.next_header,
+            hdr_ext_len: h.hdr_ext_len,
+            opt_type: h.opt_type,
+            opt_len: h.opt_len,
+            jumbo_len: h.jumbo_len.get(),
+        }
+    }
+}
+
+impl From<Ipv6Jumbo> for crate::ipv6::JumboHeader {
+    fn from(r: Ipv6Jumbo) -> Self {
+        Self {
+            next_header: r.next_header,
+            hdr_ext_len: r.hdr_ext_len,
+            opt_type: r.opt_type,
+            opt_len: r.opt_len,
+            jumbo_len: r.jumbo_len.into(),
+        }
+    }
+}
+
+via!(crate::ipv6::JumboHeader, Ipv6Jumbo);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "TcpHeader")]
+struct Tcp {
+    source_port: u16,
+    destination_port: u16,
+    seq: u32,
+    ack: u32,
+    data_offset: u8,
+    flags: crate::tcp::Flags,
+    window: u16,
+    checksum: u16,
+    urgent_pointer: u16,
+}
+
+impl From<crate::tcp::Header> for Tcp {
+    fn from(h: crate::tcp::Header) -> Self {
+        Self {
+            source_port: h.source.get(),
+            destination_port: h.dest.get(),
+            seq: h.seq.get(),
+            ack: h.ack_seq.get(),
+            data_offset: h.data_offset(),
+            flags: h.flags(),
+            window: h.window.get(),
+            checksum: h.check.get(),
+            urgent_pointer: h.urg_ptr.get(),
+        }
+    }
+}
+
+impl From<Tcp> for crate::tcp::Header {
+    fn from(r: Tcp) -> Self {
+        let mut h = Self {
+            source: r.source_port.into(),
+            dest: r.destination_port.into(),
+            seq: r.seq.into(),
+            ack_seq: r.ack.into(),
+            window: r.window.into(),
+            check: r.checksum.into(),
+            urg_ptr: r.urgent_pointer.into(),
+            ..Default::default()
+        };
+        h.set_data_offset(r.data_offset);
+        h.set_flags(r.flags);
+        h
+    }
+}
+
+via!(crate::tcp::Header, Tcp);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "UdpHeader")]
+struct Udp {
+    source_port: u16,
+    destination_port: u16,
+    length: u16,
+    checksum: u16,
+}
+
+impl From<crate::udp::Header> for Udp {
+    fn from(h: crate::udp::Header) -> Self {
+        Self {
+            source_port: h.source.get(),
+            destination_port: h.destination.get(),
+            length: h.len.get(),
+            checksum: h.check.get(),
+        }
+    }
+}
+
+impl From<Udp> for crate::udp::Header {
+    fn from(r: Udp) -> Self {
+        Self {
+            source: r.source_port.into(),
+            destination: r.destination_port.into(),
+            len: r.length.into(),
+            check: r.checksum.into(),
+        }
+    }
+}
+
+via!(crate::udp::Header, Udp);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SctpHeader")]
+struct Sctp {
+    source_port: u16,
+    destination_port: u16,
+    vtag: u32,
+    checksum: u32,
+}
+
+impl From<crate::sctp::Header> for Sctp {
+    fn from(h: crate::sctp::Header) -> Self {
+        Self {
+            source_port: h.source.get(),
+            destination_port: h.destination.get(),
+            vtag: h.vtag.get(),
+            checksum: h.checksum(),
+        }
+    }
+}
+
+impl From<Sctp> for crate::sctp::Header {
+    fn from(r: Sctp) -> Self {
+        let mut h = Self::new(r.source_port, r.destination_port, r.vtag);
+        h.set_checksum(r.checksum);
+        h
+    }
+}
+
+via!(crate::sctp::Header, Sctp);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SctpChunkHeader")]
+struct SctpChunk {
+    #[serde(rename = "type")]
+    chunk_type: crate::sctp::ChunkType,
+    flags: u8,
+    length: u16,
+}
+
+impl From<crate::sctp::ChunkHeader> for SctpChunk {
+    fn from(h: crate::sctp::ChunkHeader) -> Self {
+        Self {
+            chunk_type: h.chunk_type,
+            flags: h.flags,
+            length: h.length.get(),
+        }
+    }
+}
+
+impl From<SctpChunk> for crate::sctp::ChunkHeader {
+    fn from(r: SctpChunk) -> Self {
+        Self {
+            chunk_type: r.chunk_type,
+            flags: r.flags,
+            length: r.length.into(),
+        }
+    }
+}
+
+via!(crate::sctp::ChunkHeader, SctpChunk);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SctpDataChunk")]
+struct SctpData {
+    tsn: u32,
+    stream_id: u16,
+    stream_seq: u16,
+    ppid: u32,
+}
+
+impl From<crate::sctp::DataChunk> for SctpData {
+    fn from(h: crate::sctp::DataChunk) -> Self {
+        Self {
+            tsn: h.tsn.get(),
+            stream_id: h.stream_id.get(),
+            stream_seq: h.stream_seq.get(),
+            ppid: h.ppid.get(),
+        }
+    }
+}
+
+impl From<SctpData> for crate::sctp::DataChunk {
+    fn from(r: SctpData) -> Self {
+        Self {
+            tsn: r.tsn.into(),
+            stream_id: r.stream_id.into(),
+            stream_seq: r.stream_seq.into(),
+            ppid: r.ppid.into(),
+        }
+    }
+}
+
+via!(crate::sctp::DataChunk, SctpData);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SctpInitChunk")]
+struct SctpInit {
+    initiate_tag: u32,
+    a_rwnd: u32,
+    outbound_streams: u16,
+    inbound_streams: u16,
+    initial_tsn: u32,
+}
+
+impl From<crate::sctp::InitChunk> for SctpInit {
+    fn from(h: crate::sctp::InitChunk) -> Self {
+        Self {
+            initiate_tag: h.initiate_tag.get(),
+            a_rwnd: h.a_rwnd.get(),
+            outbound_streams: h.outbound_streams.get(),
+            inbound_streams: h.inbound_streams.get(),
+            initial_tsn: h.initial_tsn.get(),
+        }
+    }
+}
+
+impl From<SctpInit> for crate::sctp::InitChunk {
+    fn from(r: SctpInit) -> Self {
+        Self {
+            initiate_tag: r.initiate_tag.into(),
+            a_rwnd: r.a_rwnd.into(),
+            outbound_streams: r.outbound_streams.into(),
+            inbound_streams: r.inbound_streams.into(),
+            initial_tsn: r.initial_tsn.into(),
+        }
+    }
+}
+
+via!(crate::sctp::InitChunk, SctpInit);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SctpSackChunk")]
+struct SctpSack {
+    cum_tsn_ack: u32,
+    a_rwnd: u32,
+    num_gap_blocks: u16,
+    num_dup_tsns: u16,
+}
+
+impl From<crate::sctp::SackChunk> for SctpSack {
+    fn from(h: crate::sctp::SackChunk) -> Self {
+        Self {
+            cum_tsn_ack: h.cum_tsn_ack.get(),
+            a_rwnd: h.a_rwnd.get(),
+            num_gap_blocks: h.num_gap_blocks.get(),
+            num_dup_tsns: h.num_dup_tsns.get(),
+        }
+    }
+}
+
+impl From<SctpSack> for crate::sctp::SackChunk {
+    fn from(r: SctpSack) -> Self {
+        Self {
+            cum_tsn_ack: r.cum_tsn_ack.into(),
+            a_rwnd: r.a_rwnd.into(),
+            num_gap_blocks: r.num_gap_blocks.into(),
+            num_dup_tsns: r.num_dup_tsns.into(),
+        }
+    }
+}
+
+via!(crate::sctp::SackChunk, SctpSack);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "IcmpHeader")]
+struct Icmp<T> {
+    #[serde(rename = "type")]
+    msg_type: T,
+    code: u8,
+    checksum: u16,
+    /// The rest of the header, whose meaning depends on the type.
+    rest: [u8; 4],
+}
+
+impl From<crate::icmp::Header> for Icmp<crate::icmp::Type> {
+    fn from(h: crate::icmp::Header) -> Self {
+        Self {
+            msg_type: h.msg_type,
+            code: h.code,
+            checksum: h.check.get(),
+            rest: h.rest,
+        }
+    }
+}
+
+impl From<Icmp<crate::icmp::Type>> for crate::icmp::Header {
+    fn from(r: Icmp<crate::icmp::Type>) -> Self {
+        Self {
+            msg_type: r.msg_type,
+            code: r.code,
+            check: r.checksum.into(),
+            rest: r.rest,
+        }
+    }
+}
+
+via!(crate::icmp::Header, Icmp<crate::icmp::Type>);
+
+impl From<crate::icmpv6::Header> for Icmp<crate::icmpv6::Type> {
+    fn from(h: crate::icmpv6::Header) -> Self {
+        Self {
+            msg_type: h.msg_type,
+            code: h.code,
+            checksum: h.check.get(),
+            rest: h.rest,
+        }
+    }
+}
+
+impl From<Icmp<crate::icmpv6::Type>> for crate::icmpv6::Header {
+    fn from(r: Icmp<crate::icmpv6::Type>) -> Self {
+        Self {
+            msg_type: r.msg_type,
+            code: r.code,
+            check: r.checksum.into(),
+            rest: r.rest,
+        }
+    }
+}
+
+via!(crate::icmpv6::Header, Icmp<crate::icmpv6::Type>);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "VxlanHeader")]
+struct Vxlan {
+    vni: Option<u32>,
+}
+
+impl From<crate::vxlan::Header> for Vxlan {
+    fn from(h: crate::vxlan::Header) -> Self {
+        Self {
+            vni: h.vni().map(be32::get),
+        }
+    }
+}
+
+impl From<Vxlan> for crate::vxlan::Header {
+    fn from(r: Vxlan) -> Self {
+        let mut h = Self::default();
+        if let Some(vni) = r.vni {
+            h.set_vni(vni.into());
+        }
+        h
+    }
+}
+
+via!(crate::vxlan::Header, Vxlan);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "GtpHeader")]
+struct Gtp {
+    flags: u8,
+    #[serde(rename = "type")]
+    msg_type: crate::gtp::MsgType,
+    length: u16,
+    teid: u32,
+}
+
+impl From<crate::gtp::Header> for Gtp {
+    fn from(h: crate::gtp::Header) -> Self {
+        Self {
+            flags: h.flags,
+            msg_type: h.msg_type,
+            length: h.length.get(),
+            teid: h.teid(),
+        }
+    }
+}
+
+impl From<Gtp> for crate::gtp::Header {
+    fn from(r: Gtp) -> Self {
+        Self {
+            flags: r.flags,
+            msg_type: r.msg_type,
+            length: r.length.into(),
+            teid: r.teid.into(),
+        }
+    }
+}
+
+via!(crate::gtp::Header, Gtp);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "GtpOptions")]
+struct GtpOptions {
+    seq: u16,
+    npdu: u8,
+    next_ext: crate::gtp::ExtType,
+}
+
+impl From<crate::gtp::Options> for GtpOptions {
+    fn from(h: crate::gtp::Options) -> Self {
+        Self {
+            seq: h.seq.get(),
+            npdu: h.npdu,
+            next_ext: h.next_ext,
+        }
+    }
+}
+
+impl From<GtpOptions> for crate::gtp::Options {
+    fn from(r: GtpOptions) -> Self {
+        Self {
+            seq: r.seq.into(),
+            npdu: r.npdu,
+            next_ext: r.next_ext,
+        }
+    }
+}
+
+via!(crate::gtp::Options, GtpOptions);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "VirtioNetHeader")]
+struct VirtioNet {
+    flags: crate::virtio_net::Flags,
+    gso_type: crate::virtio_net::GsoType,
+    hdr_len: u16,
+    gso_size: u16,
+    csum_start: u16,
+    csum_offset: u16,
+}
+
+impl From<crate::virtio_net::Header> for VirtioNet {
+    fn from(h: crate::virtio_net::Header) -> Self {
+        Self {
+            flags: h.flags,
+            gso_type: h.gso_type,
+            hdr_len: h.hdr_len(),
+            gso_size: h.gso_size(),
+            csum_start: h.csum_start(),
+            csum_offset: h.csum_offset(),
+        }
+    }
+}
+
+impl From<VirtioNet> for crate::virtio_net::Header {
+    fn from(r: VirtioNet) -> Self {
+        let mut h = Self::default();
+        h.flags = r.flags;
+        h.gso_type = r.gso_type;
+        h.set_hdr_len(r.hdr_len);
+        h.set_gso_size(r.gso_size);
+        h.set_csum_start(r.csum_start);
+        h.set_csum_offset(r.csum_offset);
+        h
+    }
+}
+
+via!(crate::virtio_net::Header, VirtioNet);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "VirtioNetMrgRxbufHeader")]
+struct VirtioNetMrgRxbuf {
+    header: crate::virtio_net::Header,
+    num_buffers: u16,
+}
+
+impl From<crate::virtio_net::MrgRxbufHeader> for VirtioNetMrgRxbuf {
+    fn from(h: crate::virtio_net::MrgRxbufHeader) -> Self {
+        Self {
+            header: h.hdr,
+            num_buffers: h.num_buffers(),
+        }
+    }
+}
+
+impl From<VirtioNetMrgRxbuf> for crate::virtio_net::MrgRxbufHeader {
+    fn from(r: VirtioNetMrgRxbuf) -> Self {
+        let mut h = Self::default();
+        h.hdr = r.header;
+        h.set_num_buffers(r.num_buffers);
+        h
+    }
+}
+
+via!(crate::virtio_net::MrgRxbufHeader, VirtioNetMrgRxbuf);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "VirtioNetHashHeader")]
+struct VirtioNetHash {
+    header: crate::virtio_net::Header,
+    num_buffers: u16,
+    hash_value: u32,
+    hash_report: crate::virtio_net::HashType,
+}
+
+impl From<crate::virtio_net::HashHeader> for VirtioNetHash {
+    fn from(h: crate::virtio_net::HashHeader) -> Self {
+        Self {
+            header: h.hdr,
+            num_buffers: h.num_buffers(),
+            hash_value: h.hash_value(),
+            hash_report: h.hash_report(),
+        }
+    }
+}
+
+impl From<VirtioNetHash> for crate::virtio_net::HashHeader {
+    fn from(r: VirtioNetHash) -> Self {
+        let mut h = Self::default();
+        h.hdr = r.header;
+        h.set_num_buffers(r.num_buffers);
+        h.set_hash_value(r.hash_value);
+        h.set_hash_report(r.hash_report);
+        h
+    }
+}
+
+via!(crate::virtio_net::HashHeader, VirtioNetHash);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Ipv4FlowKey")]
+struct Ipv4Key {
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    id: u16,
+    protocol: crate::ip::Proto,
+}
+
+impl From<crate::reassembly::Ipv4Key> for Ipv4Key {
+    fn from(k: crate::reassembly::Ipv4Key) -> Self {
+        Self {
+            source: k.source.into(),
+            destination: k.destination.into(),
+            id: k.id.get(),
+            protocol: k.proto,
+        }
+    }
+}
+
+impl From<Ipv4Key> for crate::reassembly::Ipv4Key {
+    fn from(r: Ipv4Key) -> Self {
+        Self {
+            source: r.source.octets(),
+            destination: r.destination.octets(),
+            id: r.id.into(),
+            proto: r.protocol,
+        }
+    }
+}
+
+via!(crate::reassembly::Ipv4Key, Ipv4Key);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Ipv6FlowKey")]
+struct Ipv6Key {
+    source: Ipv6Addr,
+    destination: Ipv6Addr,
+    identification: u32,
+}
+
+impl From<crate::reassembly::Ipv6Key> for Ipv6Key {
+    fn from(k: crate::reassembly::Ipv6Key) -> Self {
+        Self {
+            source: k.source.into(),
+            destination: k.destination.into(),
+            identification: k.identification.get(),
+        }
+    }
+}
+
+impl From<Ipv6Key> for crate::reassembly::Ipv6Key {
+    fn from(r: Ipv6Key) -> Self {
+        Self {
+            source: r.source.octets(),
+            destination: r.destination.octets(),
+            identification: r.identification.into(),
+        }
+    }
+}
+
+via!(crate::reassembly::Ipv6Key, Ipv6Key);
+
+#[cfg(test)]
+mod tests {
+    use crate::tcp::Flags;
+
+    fn round_trip<T>(value: T, json: &str) -> T
+    where
+        T: ::serde::Serialize + for<'de> ::serde::Deserialize<'de>,
+    {
+        assert_eq!(json, serde_json::to_string(&value).unwrap());
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn eth() {
+        let eth = crate::eth::Header {
+            dst_addr: crate::eth::BROADCAST,
+            src_addr: [2, 0, 0x5e, 0, 0x53, 1],
+            ether_type: crate::eth::Type::IPV6,
+        };
+        let back = round_trip(
+            eth,
+            r#"{"destination":"ff:ff:ff:ff:ff:ff","source":"02:00:5e:00:53:01","ether_type":"IPv6"}"#,
+        );
+        assert_eq!(eth.src_addr, back.src_addr);
+        assert_eq!(eth.ether_type, back.ether_type);
+        let unknown: crate::eth::Type = serde_json::from_str("35020").unwrap();
+        assert_eq!(crate::eth::Type::new(0x88cc), unknown);
+        assert!(serde_json::from_str::<crate::eth::Type>("70000").is_err());
+        assert!(serde_json::from_str::<crate::eth::MacAddr>(r#""02:00:5e""#).is_err());
+    }
+
+    #[test]
+    fn ipv4_tcp() {
+        let mut ip = crate::ipv4::Header {
+            tot_len: 40.into(),
+            id: 7.into(),
+            ttl: 64,
+            proto: crate::ip::Proto::TCP,
+            source: [10, 0, 0, 1],
+            destination: [10, 0, 0, 2],
+            ..Default::default()
+        };
+        ip.set_dont_fragment(true);
+        ip.update_checksum();
+        let back = round_trip(
+            ip,
+            r#"{"version":4,"ihl":5,"tos":0,"total_length":40,"id":7,"dont_fragment":true,"more_fragments":false,"fragment_offset":0,"ttl":64,"protocol":"TCP","checksum":9927,"source":"10.0.0.1","destination":"10.0.0.2"}"#,
+        );
+        assert_eq!(ip.compute_checksum(), back.compute_checksum());
+        assert_eq!(ip.check, back.check);
+
+        let mut tcp = crate::tcp::Header {
+            source: 40000.into(),
+            dest: 80.into(),
+            seq: 1.into(),
+            ..Default::default()
+        };
+        tcp.set_data_offset(5);
+        tcp.set_flags(Flags::SYN | Flags::ECE | Flags::CWR);
+        let back = round_trip(
+            tcp,
+            r#"{"source_port":40000,"destination_port":80,"seq":1,"ack":0,"data_offset":5,"flags":["SYN","ECE","CWR"],"window":0,"checksum":0,"urgent_pointer":0}"#,
+        );
+        assert_eq!(tcp.flags(), back.flags());
+        assert_eq!(tcp.header_len(), back.header_len());
+    }
+
+    #[test]
+    fn ipv6_and_keys() {
+        let mut ip = crate::ipv6::Header {
+            payload_len: 8.into(),
+            proto: crate::ip::Proto::new(253),
+            source: core::net::Ipv6Addr::LOCALHOST.octets(),
+            destination: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            ..Default::default()
+        };
+        ip.set_priority(0xb8);
+        ip.set_flow(0x12345);
+        let back = round_trip(
+            ip,
+            r#"{"version":6,"traffic_class":184,"flow_label":74565,"payload_length":8,"next_header":253,"hop_limit":64,"source":"::1","destination":"fd00::2"}"#,
+        );
+        assert_eq!(ip._bitfields, back._bitfields);
+
+        let key = crate::reassembly::Ipv4Key {
+            source: [192, 0, 2, 1],
+            destination: [192, 0, 2, 2],
+            id: 9.into(),
+            proto: crate::ip::Proto::UDP,
+        };
+        let back = round_trip(
+            key,
+            r#"{"source":"192.0.2.1","destination":"192.0.2.2","id":9,"protocol":"UDP"}"#,
+        );
+        assert_eq!(key, back);
+    }
+
+    #[test]
+    fn icmp_arp_vxlan() {
+        let icmp = crate::icmp::Header {
+            msg_type: crate::icmp::Type::ECHO_REQUEST,
+            rest: [0, 1, 0, 2],
+            ..Default::default()
+        };
+        let back = round_trip(
+            icmp,
+            r#"{"type":"echo_request","code":0,"checksum":0,"rest":[0,1,0,2]}"#,
+        );
+        assert_eq!(icmp.msg_type, back.msg_type);
+
+        let arp = crate::arp::Header::gratuitous([2, 0, 0, 0, 0, 1], [10, 0, 0, 1].into());
+        let json = serde_json::to_string(&arp).unwrap();
+        let back: crate::arp::Header = serde_json::from_str(&json).unwrap();
+        assert!(back.var().is_ethernet_ipv4());
+        assert_eq!(arp.spa, back.spa);
+
+        let mut vxlan = crate::vxlan::Header::default();
+        vxlan.set_vni(42.into());
+        let back = round_trip(vxlan, r#"{"vni":42}"#);
+        assert_eq!(Some(42.into()), back.vni());
+    }
+
+    #[test]
+    fn sctp_chunks_and_gtp() {
+        let chunk = crate::sctp::ChunkHeader {
+            chunk_type: crate::sctp::ChunkType::DATA,
+            flags: crate::sctp::DataChunk::BEGINNING | crate::sctp::DataChunk::END,
+            length: 20.into(),
+        };
+        let back = round_trip(chunk, r#"{"type":"DATA","flags":3,"length":20}"#);
+        assert_eq!({ chunk.chunk_type }, { back.chunk_type });
+        assert_eq!({ chunk.length }, { back.length });
+
+        let data = crate::sctp::DataChunk {
+            tsn: 1.into(),
+            stream_id: 2.into(),
+            stream_seq: 3.into(),
+            ppid: 46.into(),
+        };
+        let back = round_trip(data, r#"{"tsn":1,"stream_id":2,"stream_seq":3,"ppid":46}"#);
+        assert_eq!({ data.ppid }, { back.ppid });
+
+        let init = crate::sctp::InitChunk {
+            initiate_tag: 7.into(),
+            a_rwnd: 65536.into(),
+            outbound_streams: 10.into(),
+            inbound_streams: 65535.into(),
+            initial_tsn: 100.into(),
+        };
+        let back = round_trip(
+            init,
+            r#"{"initiate_tag":7,"a_rwnd":65536,"outbound_streams":10,"inbound_streams":65535,"initial_tsn":100}"#,
+        );
+        assert_eq!({ init.initial_tsn }, { back.initial_tsn });
+
+        let sack = crate::sctp::SackChunk {
+            cum_tsn_ack: 99.into(),
+            a_rwnd: 4096.into(),
+            num_gap_blocks: 1.into(),
+            num_dup_tsns: 0.into(),
+        };
+        let back = round_trip(
+            sack,
+            r#"{"cum_tsn_ack":99,"a_rwnd":4096,"num_gap_blocks":1,"num_dup_tsns":0}"#,
+        );
+        assert_eq!({ sack.num_gap_blocks }, { back.num_gap_blocks });
+
+        let options = crate::gtp::Options {
+            seq: 5.into(),
+            npdu: 0,
+            next_ext: crate::gtp::ExtType::PDU_SESSION_CONTAINER,
+        };
+        let back = round_trip(
+            options,
+            r#"{"seq":5,"npdu":0,"next_ext":"pdu_session_container"}"#,
+        );
+        assert_eq!({ options.next_ext }, { back.next_ext });
+    }
+
+    #[test]
+    fn unnamed_flags() {
+        let flags = crate::virtio_net::Flags::new(0x81);
+        let back = round_trip(flags, r#"["NEEDS_CSUM",128]"#);
+        assert_eq!(flags, back);
+    }
+}
@@ -4,9 +4,72 @@ use crate::{be16, be32};
 
 pub const TCP_HDR_LEN: usize = mem::size_of::<Header>();
 
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Flags {
+    val: u8,
+}
+
+impl Flags {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    pub const FIN: Flags = Flags::new(0x01);
+    pub const SYN: Flags = Flags::new(0x02);
+    pub const RST: Flags = Flags::new(0x04);
+    pub const PSH: Flags = Flags::new(0x08);
+    pub const ACK: Flags = Flags::new(0x10);
+    pub const URG: Flags = Flags::new(0x20);
+    pub const ECE: Flags = Flags::new(0x40);
+    pub const CWR: Flags = Flags::new(0x80);
+
+    /// Returns true if every flag set in `other` is also set in `self`.
+    #[inline(always)]
+    pub const fn contains(&self, other: Flags) -> bool {
+        self.val & other.val == other.val
+    }
+
+    #[inline(always)]
+    pub const fn bits(&self) -> u8 {
+        self.val
+    }
+}
+
+impl core::ops::BitOr for Flags {
+    type Output = Flags;
+
+    #[inline(always)]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self::new(self.val | rhs.val)
+    }
+}
+
+impl core::ops::BitAnd for Flags {
+    type Output = Flags;
+
+    #[inline(always)]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self::new(self.val & rhs.val)
+    }
+}
+
+impl core::ops::Not for Flags {
+    type Output = Flags;
+
+    #[inline(always)]
+    fn not(self) -> Self::Output {
+        Self::new(!self.val)
+    }
+}
+
 /// TCP header, which is present after the IP header.
 #[repr(C, packed(2))]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Header {
     pub source: be16,
     pub dest: be16,
@@ -18,23 +81,127 @@ pub struct Header {
     pub urg_ptr: be16,
 }
 
+const _: () = assert!(mem::size_of::<Header>() == 20 && mem::align_of::<Header>() == 2);
+
 impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Tcp;
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.header_len()
+    }
+}
 
 impl Header {
     pub const LEN: usize = mem::size_of::<Header>();
+
+    /// Returns the data offset field, the header length in 32 bit words.
+    #[inline(always)]
+    pub fn data_offset(&self) -> u8 {
+        self._bitfields[0] >> 4
+    }
+
+    #[inline(always)]
+    pub fn set_data_offset(&mut self, val: u8) {
+        self._bitfields[0] = self._bitfields[0] & 0x0f | val << 4;
+    }
+
+    /// Returns the length of the header including options, in bytes.
+    #[inline(always)]
+    pub fn header_len(&self) -> usize {
+        (self.data_offset() as usize) << 2
+    }
+
+    #[inline(always)]
+    pub fn flags(&self) -> Flags {
+        Flags::new(self._bitfields[1])
+    }
+
+    #[inline(always)]
+    pub fn set_flags(&mut self, flags: Flags) {
+        self._bitfields[1] = flags.val;
+    }
+}
+
+/// Flags are shown as tcpdump does, e.g. `S.` for SYN and ACK.
+impl core::fmt::Display for Flags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.val == 0 {
+            return f.write_str("none");
+        }
+        let names = [
+            (Flags::FIN, 'F'),
+            (Flags::SYN, 'S'),
+            (Flags::RST, 'R'),
+            (Flags::PSH, 'P'),
+            (Flags::ACK, '.'),
+            (Flags::URG, 'U'),
+            (Flags::ECE, 'E'),
+            (Flags::CWR, 'W'),
+        ];
+        for (flag, name) in names {
+            if self.contains(flag) {
+                core::fmt::Write::write_char(f, name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (source, dest, seq, window) = (self.source, self.dest, self.seq, self.window);
+        write!(f, "{source} > {dest}: Flags [{}], seq {seq}", self.flags())?;
+        if self.flags().contains(Flags::ACK) {
+            let ack_seq = self.ack_seq;
+            write!(f, ", ack {ack_seq}")?;
+        }
+        write!(f, ", win {window}")?;
+        if self.flags().contains(Flags::URG) {
+            let urg_ptr = self.urg_ptr;
+            write!(f, ", urg {urg_ptr}")?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
-    fn size() {
-        assert_eq!(20, Header::LEN);
+    fn bitfields() {
+        let mut h = Header {
+            _bitfields: [0x50, 0x18],
+            ..Default::default()
+        };
+        assert_eq!(5, h.data_offset());
+        assert_eq!(20, h.header_len());
+        assert_eq!(Flags::PSH | Flags::ACK, h.flags());
+        assert!(h.flags().contains(Flags::ACK));
+        assert!(!h.flags().contains(Flags::ACK | Flags::FIN));
+
+        h.set_data_offset(8);
+        h.set_flags(h.flags() & !Flags::PSH | Flags::FIN);
+        assert_eq!([0x80, 0x11], h._bitfields);
+        assert_eq!(32, h.header_len());
     }
 
-    #[test]
-    fn alignment() {
-        assert_eq!(2, mem::align_of::<Header>());
+    proptest! {
+        #[test]
+        fn accessors(data_offset in 0u8..16, flags: u8, raw: [u8; 2]) {
+            let mut h = Header {
+                _bitfields: raw,
+                ..Default::default()
+            };
+            h.set_data_offset(data_offset);
+            h.set_flags(Flags::new(flags));
+            prop_assert_eq!(data_offset, h.data_offset());
+            prop_assert_eq!(data_offset as usize * 4, h.header_len());
+            prop_assert_eq!(Flags::new(flags), h.flags());
+            prop_assert!(h.flags().contains(Flags::new(flags) & Flags::SYN));
+        }
     }
 }
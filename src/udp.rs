@@ -5,6 +5,11 @@ use crate::be16;
 /// UDP header, which is present after the IP header.
 #[repr(C, packed(2))]
 #[derive(Debug, Default, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Header {
     pub source: be16,
     pub destination: be16,
@@ -12,7 +17,12 @@ pub struct Header {
     pub check: be16,
 }
 
+const _: () = assert!(mem::size_of::<Header>() == 8 && mem::align_of::<Header>() == 2);
+
 impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Udp;
+}
 
 impl Header {
     pub const LEN: usize = mem::size_of::<Header>();
@@ -27,17 +37,26 @@ impl Header {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn size() {
-        assert_eq!(8, Header::LEN);
+impl super::AutoNextHeader for Header {
+    /// Returns the VXLAN or GTP-U header for their well-known destination ports.
+    #[inline(always)]
+    fn next(
+        &mut self,
+        frame: core::ops::Range<*mut core::ffi::c_void>,
+    ) -> Result<super::HeaderPtr, ()> {
+        use super::NextHeader as _;
+
+        match self.destination.get() {
+            super::vxlan::PORT => self.next_t(frame).map(super::HeaderPtr::Vxlan),
+            super::gtp::PORT => self.next_t(frame).map(super::HeaderPtr::Gtp),
+            _ => Ok(super::HeaderPtr::Unhandled()),
+        }
     }
+}
 
-    #[test]
-    fn alignment() {
-        assert_eq!(2, mem::align_of::<Header>());
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (source, destination, len) = (self.source, self.destination, self.len);
+        write!(f, "{source} > {destination}: UDP, length {len}")
     }
 }
@@ -0,0 +1,496 @@
+//! The `virtio_net_hdr` that precedes every frame on a virtio-net device, or on a TAP device
+//! opened with `IFF_VNET_HDR`.
+//!
+//! All multi-byte fields are little-endian, as for virtio 1.0 devices and TAP devices with
+//! `TUNSETVNETLE` set.
+
+use core::mem;
+
+use crate::be16;
+
+/// `flags` of the virtio-net header.
+#[repr(transparent)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Flags {
+    val: u8,
+}
+
+impl Flags {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    /// The checksum at `csum_start + csum_offset` only covers the pseudo header and must be
+    /// completed.
+    pub const NEEDS_CSUM: Flags = Flags::new(1);
+    /// The checksum has been validated by the device.
+    pub const DATA_VALID: Flags = Flags::new(2);
+    /// `csum_start` and `csum_offset` carry receive segment coalescing information.
+    pub const RSC_INFO: Flags = Flags::new(4);
+
+    #[inline(always)]
+    pub const fn contains(self, other: Flags) -> bool {
+        self.val & other.val == other.val
+    }
+
+    #[inline(always)]
+    pub const fn bits(self) -> u8 {
+        self.val
+    }
+}
+
+impl core::ops::BitOr for Flags {
+    type Output = Flags;
+
+    #[inline(always)]
+    fn bitor(self, rhs: Flags) -> Flags {
+        Flags::new(self.val | rhs.val)
+    }
+}
+
+impl core::ops::BitAnd for Flags {
+    type Output = Flags;
+
+    #[inline(always)]
+    fn bitand(self, rhs: Flags) -> Flags {
+        Flags::new(self.val & rhs.val)
+    }
+}
+
+impl core::ops::Not for Flags {
+    type Output = Flags;
+
+    #[inline(always)]
+    fn not(self) -> Flags {
+        Flags::new(!self.val)
+    }
+}
+
+/// `gso_type` of the virtio-net header.
+#[repr(transparent)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct GsoType {
+    val: u8,
+}
+
+impl GsoType {
+    pub const fn new(val: u8) -> Self {
+        Self { val }
+    }
+
+    #[inline(always)]
+    pub const fn get(&self) -> u8 {
+        self.val
+    }
+
+    pub const NONE: GsoType = GsoType::new(0);
+    pub const TCPV4: GsoType = GsoType::new(1);
+    /// UDP fragmentation offload.
+    pub const UDP: GsoType = GsoType::new(3);
+    pub const TCPV6: GsoType = GsoType::new(4);
+    /// UDP segmentation offload.
+    pub const UDP_L4: GsoType = GsoType::new(5);
+    /// Flag set alongside TCPV4 or TCPV6 when the segments carry ECN CWR.
+    pub const ECN: GsoType = GsoType::new(0x80);
+
+    /// Returns the type with the ECN flag cleared.
+    #[inline(always)]
+    pub const fn kind(self) -> GsoType {
+        GsoType::new(self.val & !Self::ECN.val)
+    }
+
+    #[inline(always)]
+    pub const fn ecn(self) -> bool {
+        self.val & Self::ECN.val != 0
+    }
+}
+
+/// `hash_report` of the virtio-net header with hash reporting.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HashType {
+    val: u16,
+}
+
+impl HashType {
+    pub const fn new(val: u16) -> Self {
+        Self { val }
+    }
+
+    pub const fn get(self) -> u16 {
+        self.val
+    }
+
+    pub const NONE: HashType = HashType::new(0);
+    pub const IPV4: HashType = HashType::new(1);
+    pub const TCPV4: HashType = HashType::new(2);
+    pub const UDPV4: HashType = HashType::new(3);
+    pub const IPV6: HashType = HashType::new(4);
+    pub const TCPV6: HashType = HashType::new(5);
+    pub const UDPV6: HashType = HashType::new(6);
+    pub const IPV6_EX: HashType = HashType::new(7);
+    pub const TCPV6_EX: HashType = HashType::new(8);
+    pub const UDPV6_EX: HashType = HashType::new(9);
+}
+
+/// The basic virtio-net header, used when neither mergeable receive buffers nor hash reporting
+/// have been negotiated.
+#[repr(C, packed(2))]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct Header {
+    pub flags: Flags,
+    pub gso_type: GsoType,
+    hdr_len: [u8; 2],
+    gso_size: [u8; 2],
+    csum_start: [u8; 2],
+    csum_offset: [u8; 2],
+}
+
+const _: () = assert!(mem::size_of::<Header>() == 10 && mem::align_of::<Header>() == 1);
+
+impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::VirtioNet;
+}
+
+impl Header {
+    pub const LEN: usize = mem::size_of::<Header>();
+
+    /// Length of the headers, up to and including the transport header, repeated in every
+    /// segment.
+    #[inline(always)]
+    pub fn hdr_len(&self) -> u16 {
+        u16::from_le_bytes(self.hdr_len)
+    }
+
+    #[inline(always)]
+    pub fn set_hdr_len(&mut self, len: u16) {
+        self.hdr_len = len.to_le_bytes();
+    }
+
+    /// Payload length of each segment.
+    #[inline(always)]
+    pub fn gso_size(&self) -> u16 {
+        u16::from_le_bytes(self.gso_size)
+    }
+
+    #[inline(always)]
+    pub fn set_gso_size(&mut self, size: u16) {
+        self.gso_size = size.to_le_bytes();
+    }
+
+    /// Offset in the frame from which the checksum is computed.
+    #[inline(always)]
+    pub fn csum_start(&self) -> u16 {
+        u16::from_le_bytes(self.csum_start)
+    }
+
+    #[inline(always)]
+    pub fn set_csum_start(&mut self, start: u16) {
+        self.csum_start = start.to_le_bytes();
+    }
+
+    /// Offset of the checksum field from `csum_start`.
+    #[inline(always)]
+    pub fn csum_offset(&self) -> u16 {
+        u16::from_le_bytes(self.csum_offset)
+    }
+
+    #[inline(always)]
+    pub fn set_csum_offset(&mut self, offset: u16) {
+        self.csum_offset = offset.to_le_bytes();
+    }
+
+    /// Returns true if the frame carries a partial checksum that must be completed.
+    #[inline(always)]
+    pub fn needs_checksum(&self) -> bool {
+        self.flags.contains(Flags::NEEDS_CSUM)
+    }
+
+    /// Completes the partial checksum of `frame`, which follows this header, and clears
+    /// `NEEDS_CSUM`. Does nothing if no checksum is pending.
+    ///
+    /// The checksum field must hold the sum of the pseudo header, as left by a device with
+    /// checksum offload. `proto` is the protocol of the header at `csum_start`: a UDP checksum
+    /// that completes to zero is sent as 0xffff, since zero means no checksum.
+    pub fn complete_checksum(
+        &mut self,
+        frame: &mut [u8],
+        proto: super::ip::Proto,
+    ) -> Result<(), ()> {
+        if !self.needs_checksum() {
+            return Ok(());
+        }
+        let start = self.csum_start() as usize;
+        let field = start + self.csum_offset() as usize;
+        if start > frame.len() || field + 2 > frame.len() {
+            return Err(());
+        }
+        let mut check = super::checksum::checksum(&frame[start..]);
+        if check == be16::ZERO && proto == super::ip::Proto::UDP {
+            check = be16::MAX;
+        }
+        frame[field..field + 2].copy_from_slice(&check.to_be_bytes());
+        self.flags = self.flags & !Flags::NEEDS_CSUM;
+        Ok(())
+    }
+
+    /// Leaves the checksum of `frame` for the device to complete: stores `pseudo`, the sum of
+    /// the pseudo header as returned by [`super::checksum::pseudo_ipv4`] or
+    /// [`super::checksum::pseudo_ipv6`], in the checksum field at `start + offset` and sets
+    /// `NEEDS_CSUM`.
+    pub fn partial_checksum(
+        &mut self,
+        frame: &mut [u8],
+        start: u16,
+        offset: u16,
+        pseudo: u32,
+    ) -> Result<(), ()> {
+        let field = start as usize + offset as usize;
+        if field + 2 > frame.len() {
+            return Err(());
+        }
+        let sum = super::checksum::fold(pseudo);
+        frame[field..field + 2].copy_from_slice(&sum.to_be_bytes());
+        self.set_csum_start(start);
+        self.set_csum_offset(offset);
+        self.flags = self.flags | Flags::NEEDS_CSUM;
+        Ok(())
+    }
+}
+
+/// The virtio-net header used with `VIRTIO_NET_F_MRG_RXBUF`, and by every virtio 1.0 device.
+#[repr(C, packed(2))]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct MrgRxbufHeader {
+    pub hdr: Header,
+    num_buffers: [u8; 2],
+}
+
+const _: () =
+    assert!(mem::size_of::<MrgRxbufHeader>() == 12 && mem::align_of::<MrgRxbufHeader>() == 1);
+
+impl super::NextHeader for MrgRxbufHeader {}
+impl super::Header for MrgRxbufHeader {
+    const PROTOCOL: super::Protocol = super::Protocol::VirtioNet;
+}
+
+impl MrgRxbufHeader {
+    pub const LEN: usize = mem::size_of::<MrgRxbufHeader>();
+
+    /// Number of receive buffers the frame is spread over.
+    #[inline(always)]
+    pub fn num_buffers(&self) -> u16 {
+        u16::from_le_bytes(self.num_buffers)
+    }
+
+    #[inline(always)]
+    pub fn set_num_buffers(&mut self, num: u16) {
+        self.num_buffers = num.to_le_bytes();
+    }
+}
+
+/// The virtio-net header used with `VIRTIO_NET_F_HASH_REPORT`.
+#[repr(C, packed(2))]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct HashHeader {
+    pub hdr: Header,
+    num_buffers: [u8; 2],
+    hash_value: [u8; 4],
+    hash_report: [u8; 2],
+    _padding: [u8; 2],
+}
+
+const _: () = assert!(mem::size_of::<HashHeader>() == 20 && mem::align_of::<HashHeader>() == 1);
+
+impl super::NextHeader for HashHeader {}
+impl super::Header for HashHeader {
+    const PROTOCOL: super::Protocol = super::Protocol::VirtioNet;
+}
+
+impl HashHeader {
+    pub const LEN: usize = mem::size_of::<HashHeader>();
+
+    #[inline(always)]
+    pub fn num_buffers(&self) -> u16 {
+        u16::from_le_bytes(self.num_buffers)
+    }
+
+    #[inline(always)]
+    pub fn set_num_buffers(&mut self, num: u16) {
+        self.num_buffers = num.to_le_bytes();
+    }
+
+    /// The receive side scaling hash computed by the device.
+    #[inline(always)]
+    pub fn hash_value(&self) -> u32 {
+        u32::from_le_bytes(self.hash_value)
+    }
+
+    #[inline(always)]
+    pub fn set_hash_value(&mut self, hash: u32) {
+        self.hash_value = hash.to_le_bytes();
+    }
+
+    /// The fields the hash was computed over.
+    #[inline(always)]
+    pub fn hash_report(&self) -> HashType {
+        HashType::new(u16::from_le_bytes(self.hash_report))
+    }
+
+    #[inline(always)]
+    pub fn set_hash_report(&mut self, report: HashType) {
+        self.hash_report = report.get().to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn parse() {
+        let bytes = [
+            0x01, 0x81, 0x36, 0x00, 0xa8, 0x05, 0x22, 0x00, 0x10, 0x00, 0x02, 0x00, 0x78, 0x56,
+            0x34, 0x12, 0x02, 0x00, 0x00, 0x00,
+        ];
+        let h: HashHeader = crate::read_header(&bytes, 0).unwrap();
+        assert!(h.hdr.needs_checksum());
+        assert_eq!(GsoType::TCPV4, h.hdr.gso_type.kind());
+        assert!(h.hdr.gso_type.ecn());
+        assert_eq!(54, h.hdr.hdr_len());
+        assert_eq!(1448, h.hdr.gso_size());
+        assert_eq!(34, h.hdr.csum_start());
+        assert_eq!(16, h.hdr.csum_offset());
+        assert_eq!(2, h.num_buffers());
+        assert_eq!(0x12345678, h.hash_value());
+        assert_eq!(HashType::TCPV4, h.hash_report());
+
+        let m: MrgRxbufHeader = crate::read_header(&bytes, 0).unwrap();
+        assert_eq!(h.hdr, m.hdr);
+        assert_eq!(2, m.num_buffers());
+
+        let mut out = [0u8; 20];
+        crate::write_header(&mut out, 0, &h).unwrap();
+        assert_eq!(bytes, out);
+    }
+
+    #[test]
+    fn checksum_offload() {
+        const ETH: usize = crate::eth::Header::LEN;
+        const L4: usize = ETH + crate::ipv4::Header::LEN;
+        let mut frame = [0u8; L4 + 8 + 5];
+        let ip = crate::ipv4::Header {
+            tot_len: ((frame.len() - ETH) as u16).into(),
+            ttl: 64,
+            proto: crate::ip::Proto::UDP,
+            source: [192, 168, 0, 1],
+            destination: [192, 168, 0, 2],
+            ..Default::default()
+        };
+        crate::write_header(&mut frame, ETH, &ip).unwrap();
+        let udp = crate::udp::Header {
+            source: 1234.into(),
+            destination: 53.into(),
+            len: 13.into(),
+            check: be16::ZERO,
+        };
+        crate::write_header(&mut frame, L4, &udp).unwrap();
+        frame[L4 + 8..].copy_from_slice(b"hello");
+
+        let pseudo = crate::checksum::pseudo_ipv4(ip.source, ip.destination, 17, 13);
+        let mut h = Header::default();
+        h.partial_checksum(&mut frame, L4 as u16, 6, pseudo)
+            .unwrap();
+        assert!(h.needs_checksum());
+        assert_eq!(L4 as u16, h.csum_start());
+
+        h.complete_checksum(&mut frame, crate::ip::Proto::UDP)
+            .unwrap();
+        assert!(!h.needs_checksum());
+        let sum = crate::checksum::sum(&frame[L4..], pseudo);
+        assert_eq!(0xffff, crate::checksum::fold(sum));
+
+        // Nothing left to do.
+        let copy = frame;
+        h.complete_checksum(&mut frame, crate::ip::Proto::UDP)
+            .unwrap();
+        assert_eq!(copy, frame);
+
+        h.flags = Flags::NEEDS_CSUM;
+        h.set_csum_start(frame.len() as u16);
+        assert_eq!(
+            Err(()),
+            h.complete_checksum(&mut frame, crate::ip::Proto::UDP)
+        );
+
+        // Only UDP turns a zero checksum into 0xffff, wherever its checksum field is.
+        for (proto, check) in [(crate::ip::Proto::UDP, 0xffff), (crate::ip::Proto::TCP, 0)] {
+            let mut frame = [0xff, 0xff, 0, 0, 0, 0, 0, 0];
+            h.flags = Flags::NEEDS_CSUM;
+            h.set_csum_start(0);
+            h.set_csum_offset(6);
+            h.complete_checksum(&mut frame, proto).unwrap();
+            assert_eq!(check, u16::from_be_bytes([frame[6], frame[7]]));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn accessors(
+            hdr_len: u16,
+            gso_size: u16,
+            csum_start: u16,
+            csum_offset: u16,
+            num_buffers: u16,
+            hash_value: u32,
+            hash_report: u16,
+        ) {
+            let mut h = HashHeader::default();
+            h.hdr.set_hdr_len(hdr_len);
+            h.hdr.set_gso_size(gso_size);
+            h.hdr.set_csum_start(csum_start);
+            h.hdr.set_csum_offset(csum_offset);
+            h.set_num_buffers(num_buffers);
+            h.set_hash_value(hash_value);
+            h.set_hash_report(HashType::new(hash_report));
+            prop_assert_eq!(hdr_len, h.hdr.hdr_len());
+            prop_assert_eq!(gso_size, h.hdr.gso_size());
+            prop_assert_eq!(csum_start, h.hdr.csum_start());
+            prop_assert_eq!(csum_offset, h.hdr.csum_offset());
+            prop_assert_eq!(num_buffers, h.num_buffers());
+            prop_assert_eq!(hash_value, h.hash_value());
+            prop_assert_eq!(HashType::new(hash_report), h.hash_report());
+
+            // The fields are little-endian on the wire.
+            let bytes: [u8; HashHeader::LEN] = unsafe { core::mem::transmute(h) };
+            prop_assert_eq!(csum_start.to_le_bytes(), [bytes[6], bytes[7]]);
+            prop_assert_eq!(hash_value.to_le_bytes(), [bytes[12], bytes[13], bytes[14], bytes[15]]);
+        }
+    }
+}
@@ -2,7 +2,16 @@ use core::mem;
 
 use crate::{be16, be32};
 
+/// UDP port of VXLAN (RFC 7348).
+pub const PORT: u16 = 4789;
+
+#[repr(transparent)]
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Flags {
     val: u8,
 }
@@ -18,6 +27,11 @@ impl Flags {
 
 #[repr(C, packed(1))]
 #[derive(Default, Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::FromZeroes, zerocopy::FromBytes, zerocopy::AsBytes)
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Header {
     /// Flags
     flags: Flags,
@@ -29,6 +43,8 @@ pub struct Header {
     _reserved: u8,
 }
 
+const _: () = assert!(mem::size_of::<Header>() == 8 && mem::align_of::<Header>() == 1);
+
 impl Header {
     pub const LEN: usize = mem::size_of::<Header>();
 
@@ -63,7 +79,7 @@ impl Header {
     ) -> Result<super::Ptr<super::eth::Header>, ()> {
         use super::NextHeader;
 
-        Ok(self.next_t::<super::eth::Header>(frame)?)
+        self.next_t::<super::eth::Header>(frame)
     }
 
     #[inline(always)]
@@ -85,6 +101,9 @@ impl Header {
 }
 
 impl super::NextHeader for Header {}
+impl super::Header for Header {
+    const PROTOCOL: super::Protocol = super::Protocol::Vxlan;
+}
 impl super::AutoNextHeader for Header {
     #[inline(always)]
     fn next(
@@ -95,19 +114,19 @@ impl super::AutoNextHeader for Header {
     }
 }
 
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.vni() {
+            Some(vni) => write!(f, "VXLAN, flags [I] (0x{:02x}), vni {vni}", self.flags.val),
+            None => write!(f, "VXLAN, flags [none] (0x{:02x})", self.flags.val),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn size() {
-        assert_eq!(8, Header::LEN);
-    }
-
-    #[test]
-    fn alignment() {
-        assert_eq!(1, mem::align_of::<Header>());
-    }
+    use proptest::prelude::*;
 
     #[test]
     fn vni() {
@@ -140,4 +159,15 @@ mod tests {
         h.clear_vni();
         assert_eq!(None, h.vni());
     }
+
+    proptest! {
+        #[test]
+        fn vni_accessors(vni: u32) {
+            let mut h = Header::default();
+            h.set_vni(vni.into());
+            prop_assert_eq!(Some(be32::new(vni & 0xff_ffff)), h.vni());
+            h.clear_vni();
+            prop_assert_eq!(None, h.vni());
+        }
+    }
 }